@@ -0,0 +1,227 @@
+//! Derive macros for fox, re-exported by `fox` behind the `derive` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Attribute, Data, DeriveInput, Expr, Fields, GenericArgument, Lit, LitStr, PathArguments, Type};
+
+/// Generate a `fox::cli` parser from a struct, with a typed `parse()` and `try_parse()`.
+///
+/// Field types decide the kind of argument:
+/// - `bool` is a flag
+/// - `Option<T>` is an optional argument with a value
+/// - `Vec<T>` is a repeatable argument
+/// - anything else is required, unless it has a `default`
+///
+/// Arguments are named after their field, e.g. `dry_run` becomes `--dry-run`.
+/// Doc comments become descriptions.
+///
+/// ```rs
+/// #[derive(fox::FoxArgs)]
+/// #[fox(name = "serve", version = "1.0.0")]
+/// /// Serve a directory over HTTP
+/// struct Args {
+///     /// Directory to serve
+///     root: std::path::PathBuf,
+///     #[fox(default = "8080")]
+///     port: u16,
+///     #[fox(long = "--log")]
+///     log_file: Option<String>,
+///     #[fox(short = "-v")]
+///     verbose: bool,
+/// }
+///
+/// let args = Args::parse();
+///
+/// // Or handle the errors yourself
+/// let args = Args::parser().try_parse_str("--root ./public --port 80").and_then(|args| Args::try_from_arguments(&args))?;
+/// ```
+#[proc_macro_derive(FoxArgs, attributes(fox))]
+pub fn derive_fox_args(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+#[derive(Default)]
+struct Options {
+    name: Option<LitStr>,
+    about: Option<LitStr>,
+    version: Option<LitStr>,
+    long: Option<LitStr>,
+    short: Option<LitStr>,
+    help: Option<LitStr>,
+    default: Option<LitStr>,
+}
+
+impl Options {
+    fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut options = Options::default();
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("fox")) {
+            attr.parse_nested_meta(|meta| {
+                let slot = match meta.path.get_ident().map(|ident| ident.to_string()).as_deref() {
+                    Some("name") => &mut options.name,
+                    Some("about") => &mut options.about,
+                    Some("version") => &mut options.version,
+                    Some("long") => &mut options.long,
+                    Some("short") => &mut options.short,
+                    Some("help") => &mut options.help,
+                    Some("default") => &mut options.default,
+                    _ => return Err(meta.error("unknown fox attribute")),
+                };
+                *slot = Some(meta.value()?.parse()?);
+                Ok(())
+            })?;
+        }
+
+        Ok(options)
+    }
+}
+
+/// Join the `///` comments of an item into a single line
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs.iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta.require_name_value().ok()?.value {
+            Expr::Lit(expr) => match &expr.lit {
+                Lit::Str(text) => Some(text.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    match lines.is_empty() {
+        true => None,
+        false => Some(lines.join(" ")),
+    }
+}
+
+/// The `T` in `Wrapper<T>`, if `ty` is a `Wrapper`
+fn inner_type<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let Type::Path(path) = ty else { return None };
+    let segment = path.path.segments.last()?;
+    if segment.ident != wrapper {
+        return None;
+    }
+
+    let PathArguments::AngleBracketed(args) = &segment.arguments else { return None };
+    match args.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
+
+fn is_bool(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.is_ident("bool"))
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(ident, "FoxArgs needs a struct with named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(ident, "FoxArgs can only be derived for structs")),
+    };
+
+    let options = Options::from_attrs(&input.attrs)?;
+    let mut builder = Vec::new();
+    if let Some(name) = &options.name {
+        builder.push(quote! { .name(#name) });
+    }
+    if let Some(about) = options.about.map(|about| about.value()).or_else(|| doc_comment(&input.attrs)) {
+        builder.push(quote! { .about(#about) });
+    }
+    if let Some(version) = &options.version {
+        builder.push(quote! { .version(#version) });
+    }
+
+    let mut values = Vec::new();
+    for field in fields {
+        let field_ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let field_options = Options::from_attrs(&field.attrs)?;
+
+        let long = match &field_options.long {
+            Some(long) => long.value(),
+            None => format!("--{}", field_ident.to_string().trim_start_matches("r#").replace('_', "-")),
+        };
+        let short = field_options.short.as_ref().map(|short| short.value());
+        let default = field_options.default.as_ref().map(|default| default.value());
+
+        let (register, value) = if is_bool(ty) {
+            if default.is_some() {
+                return Err(syn::Error::new_spanned(field, "flags can't have a default"));
+            }
+            let register = match &short {
+                Some(short) => quote! { .optional_with_short(#long, #short, false) },
+                None => quote! { .optional(#long, false) },
+            };
+            (register, quote! { args.try_has_flag(#long)? })
+        } else if let Some(inner) = inner_type(ty, "Option") {
+            if default.is_some() {
+                return Err(syn::Error::new_spanned(field, "optional arguments can't have a default, use a plain type instead"));
+            }
+            let register = match &short {
+                Some(short) => quote! { .optional_with_short(#long, #short, true) },
+                None => quote! { .optional(#long, true) },
+            };
+            (register, quote! { args.try_get::<#inner>(#long)? })
+        } else if let Some(inner) = inner_type(ty, "Vec") {
+            if short.is_some() || default.is_some() {
+                return Err(syn::Error::new_spanned(field, "repeatable arguments can't have a short name or a default"));
+            }
+            (quote! { .optional_multi(#long) }, quote! { args.try_get_all::<#inner>(#long)? })
+        } else if let Some(default) = &default {
+            if short.is_some() {
+                return Err(syn::Error::new_spanned(field, "arguments with a default can't have a short name"));
+            }
+            (quote! { .optional_with_default(#long, #default) }, quote! { args.try_get::<#ty>(#long)?.ok_or_else(|| ::fox::cli::ParseError::MissingRequired(#long.to_string()))? })
+        } else {
+            if short.is_some() {
+                return Err(syn::Error::new_spanned(field, "required arguments can't have a short name"));
+            }
+            (quote! { .required(#long) }, quote! { args.try_get::<#ty>(#long)?.ok_or_else(|| ::fox::cli::ParseError::MissingRequired(#long.to_string()))? })
+        };
+
+        builder.push(register);
+        if let Some(help) = field_options.help.map(|help| help.value()).or_else(|| doc_comment(&field.attrs)) {
+            builder.push(quote! { .describe(#long, #help) });
+        }
+        values.push(quote! { #field_ident: #value });
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        #[allow(deprecated)]
+        impl #impl_generics #ident #ty_generics #where_clause {
+            /// The argument parser generated for this struct
+            pub fn parser() -> ::fox::cli::ArgumentsParser {
+                ::fox::cli::ArgumentsParser::new() #(#builder)*
+            }
+
+            /// Parse the CLI arguments into this struct, exiting on errors or `--help`
+            pub fn parse() -> Self {
+                ::fox::cli::exit_on_error(Self::try_parse(), &Self::parser().usage())
+            }
+
+            /// Parse the CLI arguments into this struct, returning an error instead of exiting
+            pub fn try_parse() -> ::std::result::Result<Self, ::fox::cli::ParseError> {
+                Self::try_from_arguments(&Self::parser().try_parse()?)
+            }
+
+            /// Build this struct from already parsed arguments, exiting if a value doesn't parse
+            pub fn from_arguments(args: &::fox::cli::Arguments) -> Self {
+                ::fox::cli::exit_on_error(Self::try_from_arguments(args), &Self::parser().usage())
+            }
+
+            /// Build this struct from already parsed arguments, returning an error if a value doesn't parse
+            pub fn try_from_arguments(args: &::fox::cli::Arguments) -> ::std::result::Result<Self, ::fox::cli::ParseError> {
+                ::std::result::Result::Ok(Self { #(#values),* })
+            }
+        }
+    })
+}
@@ -2,11 +2,82 @@
 
 use crate::*;
 use crate as fox;
+use colored::Colorize;
 
 #[derive(Clone)]
 struct Parameter {
     long: String,
-    has_value: bool
+    short: Option<String>,
+    has_value: bool,
+    description: Option<String>,
+    default: Option<String>,
+    multiple: bool,
+    choices: Vec<String>,
+    validator: Option<Validator>,
+    env: Option<String>,
+    hidden: bool,
+    section: Option<String>,
+    aliases: Vec<Alias>,
+    meta: Option<String>,
+}
+
+#[derive(Clone)]
+struct Alias {
+    name: String,
+    deprecated: bool,
+}
+
+type Validator = std::sync::Arc<dyn Fn(&str) -> Result<(), String> + Send + Sync>;
+
+impl Parameter {
+    fn new(long: String, short: Option<String>, has_value: bool) -> Self {
+        Self { long, short, has_value, description: None, default: None, multiple: false, choices: vec![], validator: None, env: None, hidden: false, section: None, aliases: vec![], meta: None }
+    }
+
+    /// Whether this parameter goes by `name`, either its long name, its short alias or one of its aliases
+    fn matches(&self, name: &str) -> bool {
+        self.long == name || self.short.as_deref() == Some(name) || self.aliases.iter().any(|alias| alias.name == name)
+    }
+
+    /// The placeholder for this parameter's value, e.g. `<DIR>`
+    fn value_name(&self) -> &str {
+        self.meta.as_deref().unwrap_or("<value>")
+    }
+
+    /// Check a value against the allowed choices and the custom validator
+    fn validate(&self, value: &str) -> Result<(), ParseError> {
+        let invalid = |reason: String| ParseError::InvalidValue { name: self.long.clone(), value: value.to_string(), reason };
+
+        if !self.choices.is_empty() && !self.choices.iter().any(|choice| choice == value) {
+            return Err(invalid(format!("expected one of {}", self.choices.join(", "))));
+        }
+
+        match &self.validator {
+            Some(validator) => validator(value).map_err(invalid),
+            None => Ok(()),
+        }
+    }
+
+    /// The left column of the help page, e.g. `-o, --out <value>`
+    ///
+    /// `align`: Indent long-only arguments to line up with ones that have a short alias
+    fn usage(&self, align: bool) -> String {
+        let mut usage = match &self.short {
+            Some(short) => format!("{}, {}", short, self.long),
+            None if align => format!("    {}", self.long),
+            None => self.long.clone(),
+        };
+
+        if self.has_value {
+            usage.push_str(&format!(" {}", self.value_name()));
+        }
+
+        if self.multiple {
+            usage.push_str("...");
+        }
+
+        usage
+    }
 }
 
 /// Used to specify what CLI arguments the program may take.
@@ -14,6 +85,20 @@ struct Parameter {
 pub struct ArgumentsParser {
     required: Vec<Parameter>,
     optional: Vec<Parameter>,
+    name: Option<String>,
+    about: Option<String>,
+    version: Option<String>,
+    build_info: Option<String>,
+    subcommands: Vec<(String, ArgumentsParser)>,
+    exclusive: Vec<Vec<String>>,
+    requires: Vec<(String, String)>,
+    required_if: Vec<(String, String, String)>,
+    config: Option<(String, std::path::PathBuf)>,
+    section: Option<String>,
+    log_flags: bool,
+    allow_unmatched: bool,
+    trace: bool,
+    last_required: bool,
 }
 
 impl ArgumentsParser {
@@ -21,147 +106,1743 @@ impl ArgumentsParser {
         Self {
             required: vec![],
             optional: vec![],
+            name: None,
+            about: None,
+            version: None,
+            build_info: None,
+            subcommands: vec![],
+            exclusive: vec![],
+            requires: vec![],
+            required_if: vec![],
+            config: None,
+            section: None,
+            log_flags: false,
+            allow_unmatched: false,
+            trace: false,
+            last_required: false,
+        }
+    }
+
+    /// Set the program name shown in the help page. Defaults to the executable's name
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set a short description of the program, shown at the top of the help page
+    pub fn about<S: Into<String>>(mut self, about: S) -> Self {
+        self.about = Some(about.into());
+        self
+    }
+
+    /// Set the program version, printed by `--version` and `-V`
+    ///
+    /// Example: `.version(env!("CARGO_PKG_VERSION"))`
+    pub fn version<S: Into<String>>(mut self, version: S) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Set extra build details printed after the version, such as a git hash or build date
+    pub fn build_info<S: Into<String>>(mut self, info: S) -> Self {
+        self.build_info = Some(info.into());
+        self
+    }
+
+    /// Set the description of an already specified argument, shown in the help page
+    ///
+    /// `long`: Name of the argument
+    pub fn describe<S: Into<String>>(mut self, long: &str, description: S) -> Self {
+        if let Some(param) = self.required.iter_mut().chain(self.optional.iter_mut()).find(|param| param.long == long) {
+            param.description = Some(description.into());
         }
+        self
     }
 
     /// Specify that this CLI argument must exist
     ///
     /// `long`: Name of the argument
-    pub fn required<S: Into<String>>(mut self, long: S) -> Self {
-        self.required.push(Parameter { long: long.into(), has_value: true });
-        self.clone()
+    pub fn required<S: Into<String>>(self, long: S) -> Self {
+        self.add(true, Parameter::new(long.into(), None, true))
     }
 
     /// Specify that this CLI argument may exist
     ///
     /// `long`: Name of the argument
     /// `has_value`: If true, argument must be followed by a value, otherwise it's a flag
-    pub fn optional<S: Into<String>>(mut self, long: S, has_value: bool) -> Self {
-        self.optional.push(Parameter { long: long.into(), has_value });
-        self.clone()
+    pub fn optional<S: Into<String>>(self, long: S, has_value: bool) -> Self {
+        self.add(false, Parameter::new(long.into(), None, has_value))
+    }
+
+    /// Specify that this CLI argument may exist, with a single-letter alias
+    ///
+    /// `long`: Name of the argument, e.g. `--verbose`
+    /// `short`: Alias for the argument, e.g. `-v`. Short flags can be clustered, e.g. `-vq`
+    /// `has_value`: If true, argument must be followed by a value, otherwise it's a flag
+    pub fn optional_with_short<S: Into<String>, T: Into<String>>(self, long: S, short: T, has_value: bool) -> Self {
+        self.add(false, Parameter::new(long.into(), Some(short.into()), has_value))
+    }
+
+    /// Specify that this CLI argument may exist, using `default` as its value when it doesn't
+    ///
+    /// `long`: Name of the argument
+    /// `default`: Value returned by `get_value()` when the argument isn't provided
+    pub fn optional_with_default<S: Into<String>, D: Into<String>>(self, long: S, default: D) -> Self {
+        self.add(false, Parameter {
+            default: Some(default.into()),
+            ..Parameter::new(long.into(), None, true)
+        })
+    }
+
+    /// Specify that this CLI argument may exist any number of times, collecting every value
+    ///
+    /// Example: `--include a --include b`, read with `get_values("--include")`
+    ///
+    /// `long`: Name of the argument
+    pub fn optional_multi<S: Into<String>>(self, long: S) -> Self {
+        self.add(false, Parameter {
+            multiple: true,
+            ..Parameter::new(long.into(), None, true)
+        })
+    }
+
+    /// Specify that this CLI argument may exist, but leave it out of the help page.
+    /// Meant for maintenance and diagnostic switches
+    ///
+    /// `long`: Name of the argument, e.g. `--internal-debug-dump`
+    /// `has_value`: If true, argument must be followed by a value, otherwise it's a flag
+    pub fn hidden<S: Into<String>>(self, long: S, has_value: bool) -> Self {
+        self.add(false, Parameter {
+            hidden: true,
+            ..Parameter::new(long.into(), None, has_value)
+        })
+    }
+
+    /// Specify a flag that may be repeated, counting how many times it was given
+    ///
+    /// Example: `-vvv` or `--verbose --verbose --verbose`, read with `count("--verbose")`
+    ///
+    /// `long`: Name of the flag, e.g. `--verbose`
+    /// `short`: Alias for the flag, e.g. `-v`
+    pub fn optional_count<S: Into<String>, T: Into<String>>(self, long: S, short: T) -> Self {
+        self.add(false, Parameter {
+            multiple: true,
+            ..Parameter::new(long.into(), Some(short.into()), false)
+        })
+    }
+
+    /// Add `--verbose`/`-v`, `--quiet`/`-q` and `--log-level <level>`, applied to [`fox::log`] when parsing.
+    ///
+    /// Each `-v` makes logging one level more verbose and each `-q` one level quieter.
+    /// `--log-level` sets the level directly, e.g. `--log-level warn`
+    pub fn with_log_level_flags(self) -> Self {
+        let mut parser = self
+            .optional_count("--verbose", "-v")
+            .describe("--verbose", "Log more, can be repeated")
+            .optional_count("--quiet", "-q")
+            .describe("--quiet", "Log less, can be repeated")
+            .optional("--log-level", true)
+            .describe("--log-level", "Set the log level: debug, info, warn, error or critical")
+            .validator(|value| match value.parse::<log::LogLevel>() {
+                Ok(_) => Ok(()),
+                Err(_) => Err("expected one of debug, info, warn, error, critical".into()),
+            });
+        parser.log_flags = true;
+        parser
+    }
+
+    /// Specify that this CLI argument may exist with a value, shown as `meta` in the help page and errors
+    ///
+    /// Example: `.optional_with_meta("--out", "<DIR>", "Output directory")` renders as `--out <DIR>`
+    pub fn optional_with_meta<S: Into<String>, M: Into<String>, D: Into<String>>(self, long: S, meta: M, description: D) -> Self {
+        self.add(false, Parameter {
+            meta: Some(meta.into()),
+            description: Some(description.into()),
+            ..Parameter::new(long.into(), None, true)
+        })
+    }
+
+    /// Specify that this CLI argument may exist, and must be one of `choices` if it does
+    ///
+    /// Example: `.optional_choices("--format", &["json", "toml", "yaml"])`
+    pub fn optional_choices<S: Into<String>>(self, long: S, choices: &[&str]) -> Self {
+        self.add(false, Parameter {
+            choices: choices.iter().map(|choice| choice.to_string()).collect(),
+            ..Parameter::new(long.into(), None, true)
+        })
+    }
+
+    /// Validate the values of the last specified argument at parse time.
+    /// Return an error message to reject a value
+    ///
+    /// Example: `.optional("--port", true).validator(|v| v.parse::<u16>().map(|_| ()).map_err(|_| "not a port".into()))`
+    pub fn validator<F: Fn(&str) -> Result<(), String> + Send + Sync + 'static>(mut self, validator: F) -> Self {
+        if let Some(param) = self.last_param() {
+            param.validator = Some(std::sync::Arc::new(validator));
+        }
+        self
+    }
+
+    /// Read the last specified argument from an environment variable when it isn't given on the command line.
+    /// Flags accept values like `true`, `1` or `no`
+    ///
+    /// Example: `.optional("--port", true).env("APP_PORT")`
+    pub fn env<S: Into<String>>(mut self, var: S) -> Self {
+        if let Some(param) = self.last_param() {
+            param.env = Some(var.into());
+        }
+        self
+    }
+
+    /// Specify an argument pointing to a JSON or TOML file with values for the other arguments.
+    /// Keys are argument names without the dashes, e.g. `{"port": 8080, "dry_run": true}`.
+    /// Files ending in `.toml` are read as TOML, which needs the `config` feature, and others as JSON.
+    ///
+    /// Values are taken from the command line first, then environment variables, then the file, then defaults.
+    /// A missing file at `default_path` is ignored, but a missing file passed explicitly is an error
+    ///
+    /// Example: `.config_file("--config", "app.json")`
+    pub fn config_file<S: Into<String>, P: Into<std::path::PathBuf>>(self, long: S, default_path: P) -> Self {
+        let long = long.into();
+        let mut parser = self.add(false, Parameter {
+            description: Some("Read argument values from a JSON or TOML file".into()),
+            ..Parameter::new(long.clone(), None, true)
+        });
+        parser.config = Some((long, default_path.into()));
+        parser
+    }
+
+    /// Group the optional arguments specified after this under their own heading in the help page
+    ///
+    /// Example: `.section("Networking").optional("--port", true).optional("--host", true)`
+    pub fn section<S: Into<String>>(mut self, heading: S) -> Self {
+        self.section = Some(heading.into());
+        self
+    }
+
+    /// Collect unknown `-` arguments into [`Arguments::unmatched`] instead of failing,
+    /// e.g. to forward them to an inner program
+    pub fn allow_unmatched(mut self, allow: bool) -> Self {
+        self.allow_unmatched = allow;
+        self
+    }
+
+    /// Accept `alias` as another name for the argument `long`
+    ///
+    /// Example: `.alias("--color", "--colour")`
+    pub fn alias(self, long: &str, alias: &str) -> Self {
+        self.add_alias(long, alias, false)
+    }
+
+    /// Accept `old` as another name for the argument `long`, warning that it's deprecated when used.
+    /// Deprecated aliases are left out of the help page
+    ///
+    /// Example: `.deprecated_alias("--output", "--out-file")`
+    pub fn deprecated_alias(self, long: &str, old: &str) -> Self {
+        self.add_alias(long, old, true)
+    }
+
+    fn add_alias(mut self, long: &str, alias: &str, deprecated: bool) -> Self {
+        if let Some(param) = self.required.iter_mut().chain(&mut self.optional).find(|param| param.long == long) {
+            param.aliases.push(Alias { name: alias.to_string(), deprecated });
+        }
+        self
+    }
+
+    /// Log how each argument is matched while parsing, at debug level, to debug argument definitions.
+    /// Parsing is silent otherwise
+    pub fn trace(mut self, trace: bool) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Specify arguments that can't be used together. Providing more than one of them is a parse error
+    ///
+    /// Example: `.group_exclusive(&["--json", "--toml"])`
+    pub fn group_exclusive(mut self, names: &[&str]) -> Self {
+        self.exclusive.push(names.iter().map(|name| name.to_string()).collect());
+        self
+    }
+
+    /// Specify that `name` can only be used together with `other`
+    ///
+    /// Example: `.requires("--key", "--cert")`
+    pub fn requires(mut self, name: &str, other: &str) -> Self {
+        self.requires.push((name.to_string(), other.to_string()));
+        self
+    }
+
+    /// Specify that `target` must be provided when the argument `name` has the value `value`
+    ///
+    /// Example: `.required_if("--mode", "server", "--port")`
+    pub fn required_if(mut self, name: &str, value: &str, target: &str) -> Self {
+        self.required_if.push((name.to_string(), value.to_string(), target.to_string()));
+        self
+    }
+
+    /// Check the argument specification for mistakes, returning every problem found.
+    /// This also happens at the start of parsing, failing with [`ParseError::Definition`]
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = vec![];
+        let mut seen: Vec<&str> = vec![];
+
+        for param in self.required.iter().chain(&self.optional) {
+            if param.long.is_empty() {
+                problems.push("an argument has an empty name".to_string());
+                continue;
+            }
+
+            if self.required.iter().any(|required| required.long == param.long) && self.optional.iter().any(|optional| optional.long == param.long) {
+                if !seen.contains(&param.long.as_str()) {
+                    problems.push(format!("`{}` is specified as both required and optional", param.long));
+                }
+                seen.push(&param.long);
+                continue;
+            }
+
+            let names = std::iter::once(&param.long).chain(&param.short).chain(param.aliases.iter().map(|alias| &alias.name));
+            for name in names {
+                if !name.starts_with('-') {
+                    problems.push(format!("`{}` doesn't start with `-`", name));
+                }
+                if seen.contains(&name.as_str()) {
+                    problems.push(format!("`{}` is specified more than once", name));
+                }
+                seen.push(name);
+            }
+        }
+
+        let references = self.exclusive.iter().flatten()
+            .chain(self.requires.iter().flat_map(|(name, other)| [name, other]))
+            .chain(self.required_if.iter().flat_map(|(name, _, target)| [name, target]));
+        for name in references {
+            if !self.required.iter().chain(&self.optional).any(|param| param.matches(name)) {
+                problems.push(format!("`{}` is used in a relation but isn't specified", name));
+            }
+        }
+
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(problems),
+        }
+    }
+
+    /// Resolve a short name to the long name of its argument
+    fn long_name(&self, name: &str) -> String {
+        self.required.iter().chain(&self.optional)
+            .find(|param| param.matches(name))
+            .map(|param| param.long.clone())
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    fn last_param(&mut self) -> Option<&mut Parameter> {
+        match self.last_required {
+            true => self.required.last_mut(),
+            false => self.optional.last_mut(),
+        }
+    }
+
+    /// Read the config file, if one was specified, as values keyed by argument name
+    fn load_config(&self, args: &[Argument]) -> Result<Vec<(String, Vec<String>)>, ParseError> {
+        let Some((long, default_path)) = &self.config else {
+            return Ok(vec![]);
+        };
+
+        let param = self.optional.iter().find(|param| &param.long == long);
+        let explicit = args.iter().find(|arg| &arg.name == long).and_then(|arg| arg.value.clone())
+            .or_else(|| param.and_then(|param| param.env.as_ref()).and_then(|var| std::env::var(var).ok()));
+
+        let path = match &explicit {
+            Some(path) => std::path::PathBuf::from(path),
+            None if default_path.exists() => default_path.clone(),
+            None => return Ok(vec![]),
+        };
+
+        let fail = |reason: String| ParseError::Config { path: path.display().to_string(), reason };
+        let entries = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => {
+                let contents = std::fs::read_to_string(&path).map_err(|err| fail(err.to_string()))?;
+                parse_toml(&contents).map_err(fail)?
+            },
+            _ => {
+                let object: serde_json::Map<String, serde_json::Value> = disk::read_json(&path).map_err(|err| fail(err.io_error().to_string()))?;
+                object.into_iter()
+                    .filter_map(|(key, value)| {
+                        let values = match value {
+                            serde_json::Value::Array(items) => items.iter().filter_map(json_scalar).collect(),
+                            value => vec![json_scalar(&value)?],
+                        };
+                        Some((key, values))
+                    })
+                    .collect()
+            },
+        };
+
+        Ok(entries.into_iter()
+            .map(|(key, values)| match key.starts_with('-') {
+                true => (key, values),
+                false => (format!("--{}", key.replace('_', "-")), values),
+            })
+            .collect())
+    }
+
+    fn add(mut self, required: bool, mut param: Parameter) -> Self {
+        param.section = self.section.clone();
+        match required {
+            true => self.required.push(param),
+            false => self.optional.push(param),
+        }
+        self.last_required = required;
+        self
+    }
+
+    /// Specify a subcommand with its own arguments, e.g. `build` in `tool build --target x`
+    ///
+    /// Arguments before the subcommand belong to this parser, everything after it to the subcommand's.
+    /// Give the subcommand an `about()` to describe it in the help page.
+    ///
+    /// `name`: Name of the subcommand
+    /// `build`: Specifies the subcommand's arguments on a fresh parser
+    pub fn subcommand<S: Into<String>, F: FnOnce(ArgumentsParser) -> ArgumentsParser>(mut self, name: S, build: F) -> Self {
+        self.subcommands.push((name.into(), build(ArgumentsParser::new())));
+        self
+    }
+
+    /// Render the help page: usage line, then required and optional arguments with their descriptions
+    pub fn help(&self) -> String {
+        let name = self.program_name();
+        let builtins = self.builtin_params();
+
+        let all = || self.required.iter().chain(&self.optional).chain(&builtins).filter(|param| !param.hidden);
+        let align = all().any(|param| param.short.is_some());
+        let width = all().map(|param| param.usage(align).len()).max().unwrap_or(0);
+
+        let line = |param: &Parameter| {
+            let mut text = param.description.clone().unwrap_or_default();
+            let mut note = |note: String| {
+                if !text.is_empty() {
+                    text.push(' ');
+                }
+                text.push_str(&log::dim(&note));
+            };
+
+            if !param.choices.is_empty() {
+                note(format!("[possible values: {}]", param.choices.join(", ")));
+            }
+            let aliases: Vec<&str> = param.aliases.iter().filter(|alias| !alias.deprecated).map(|alias| alias.name.as_str()).collect();
+            if !aliases.is_empty() {
+                note(format!("[aliases: {}]", aliases.join(", ")));
+            }
+            if let Some(var) = &param.env {
+                note(format!("[env: {}]", var));
+            }
+            if let Some(default) = &param.default {
+                note(format!("[default: {}]", default));
+            }
+
+            match text.is_empty() {
+                true => format!("  {}\n", param.usage(align).bright_cyan()),
+                false => format!("  {}  {}\n", format!("{:width$}", param.usage(align), width = width).bright_cyan(), text),
+            }
+        };
+
+        let mut help = format!("{}", name.bold());
+        if let Some(version) = &self.version {
+            help.push_str(&format!(" {}", version));
+        }
+        if let Some(about) = &self.about {
+            help.push_str(&format!(" - {}", about));
+        }
+        help.push_str("\n\n");
+
+        help.push_str(&self.usage());
+        help.push_str("\n\n");
+
+        if !self.subcommands.is_empty() {
+            let width = self.subcommands.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+
+            help.push_str(&format!("{}\n", "Commands:".bright_yellow().bold()));
+            for (name, parser) in &self.subcommands {
+                match &parser.about {
+                    Some(about) => help.push_str(&format!("  {}  {}\n", format!("{:width$}", name, width = width).bright_cyan(), about)),
+                    None => help.push_str(&format!("  {}\n", name.bright_cyan())),
+                }
+            }
+            help.push('\n');
+        }
+
+        if !self.required.is_empty() {
+            help.push_str(&format!("{}\n", "Required:".bright_yellow().bold()));
+            for param in &self.required {
+                help.push_str(&line(param));
+            }
+            help.push('\n');
+        }
+
+        let visible: Vec<&Parameter> = self.optional.iter().filter(|param| !param.hidden).collect();
+
+        help.push_str(&format!("{}\n", "Optional:".bright_yellow().bold()));
+        for param in visible.iter().filter(|param| param.section.is_none()) {
+            help.push_str(&line(param));
+        }
+        for param in &builtins {
+            help.push_str(&line(param));
+        }
+
+        let mut sections: Vec<&String> = vec![];
+        for section in visible.iter().filter_map(|param| param.section.as_ref()) {
+            if !sections.contains(&section) {
+                sections.push(section);
+            }
+        }
+
+        for section in sections {
+            help.push_str(&format!("\n{}\n", format!("{}:", section).bright_yellow().bold()));
+            for param in visible.iter().filter(|param| param.section.as_ref() == Some(section)) {
+                help.push_str(&line(param));
+            }
+        }
+
+        help
+    }
+
+    /// Render a man page in roff format, e.g. to ship as `fox.1`
+    ///
+    /// Example: `disk::write_file("fox.1", parser.generate_man())`
+    pub fn generate_man(&self) -> String {
+        let name = self.program_name();
+        let builtins = self.builtin_params();
+
+        let mut man = format!(".TH \"{}\" 1", roff_escape(&name.to_uppercase()));
+        if let Some(version) = &self.version {
+            man.push_str(&format!(" \"\" \"{} {}\"", roff_escape(&name), roff_escape(version)));
+        }
+        man.push('\n');
+
+        man.push_str(".SH NAME\n");
+        match &self.about {
+            Some(about) => man.push_str(&format!("{} \\- {}\n", roff_escape(&name), roff_escape(about))),
+            None => man.push_str(&format!("{}\n", roff_escape(&name))),
+        }
+
+        man.push_str(&format!(".SH SYNOPSIS\n.B {}\n", roff_escape(&name)));
+        for param in &self.required {
+            man.push_str(&format!("\\fB{}\\fR \\fI{}\\fR\n", roff_escape(&param.long), roff_escape(param.value_name())));
+        }
+        man.push_str("[\\fIOPTIONS\\fR]\n");
+        if !self.subcommands.is_empty() {
+            man.push_str("\\fI<COMMAND>\\fR\n");
+        }
+
+        if let Some(info) = &self.build_info {
+            man.push_str(&format!(".SH VERSION\n{}\n", roff_escape(&format!("{} ({})", self.version.as_deref().unwrap_or("unknown"), info))));
+        }
+
+        let entry = |param: &Parameter| {
+            let mut names = vec![];
+            if let Some(short) = &param.short {
+                names.push(format!("\\fB{}\\fR", roff_escape(short)));
+            }
+            names.push(format!("\\fB{}\\fR", roff_escape(&param.long)));
+
+            let mut entry = format!(".TP\n{}", names.join(", "));
+            if param.has_value {
+                entry.push_str(&format!(" \\fI{}\\fR", roff_escape(param.value_name())));
+            }
+            if param.multiple {
+                entry.push_str("...");
+            }
+            entry.push('\n');
+
+            let mut notes = vec![];
+            if let Some(description) = &param.description {
+                notes.push(description.clone());
+            }
+            if !param.choices.is_empty() {
+                notes.push(format!("Possible values: {}.", param.choices.join(", ")));
+            }
+            let aliases: Vec<&str> = param.aliases.iter().filter(|alias| !alias.deprecated).map(|alias| alias.name.as_str()).collect();
+            if !aliases.is_empty() {
+                notes.push(format!("Aliases: {}.", aliases.join(", ")));
+            }
+            if let Some(var) = &param.env {
+                notes.push(format!("Environment variable: {}.", var));
+            }
+            if let Some(default) = &param.default {
+                notes.push(format!("Default: {}.", default));
+            }
+            for note in notes {
+                entry.push_str(&format!("{}\n", roff_escape(&note)));
+            }
+
+            entry
+        };
+
+        let visible: Vec<&Parameter> = self.optional.iter().filter(|param| !param.hidden).collect();
+
+        man.push_str(".SH OPTIONS\n");
+        for param in &self.required {
+            man.push_str(&entry(param));
+        }
+        for param in visible.iter().filter(|param| param.section.is_none()) {
+            man.push_str(&entry(param));
+        }
+        for param in &builtins {
+            man.push_str(&entry(param));
+        }
+
+        let mut sections: Vec<&String> = vec![];
+        for section in visible.iter().filter_map(|param| param.section.as_ref()) {
+            if !sections.contains(&section) {
+                sections.push(section);
+            }
+        }
+
+        for section in sections {
+            man.push_str(&format!(".SH \"{}\"\n", roff_escape(&section.to_uppercase())));
+            for param in visible.iter().filter(|param| param.section.as_ref() == Some(section)) {
+                man.push_str(&entry(param));
+            }
+        }
+
+        if !self.subcommands.is_empty() {
+            man.push_str(".SH COMMANDS\n");
+            for (command, parser) in &self.subcommands {
+                man.push_str(&format!(".TP\n\\fB{}\\fR\n", roff_escape(command)));
+                if let Some(about) = &parser.about {
+                    man.push_str(&format!("{}\n", roff_escape(about)));
+                }
+            }
+        }
+
+        man
+    }
+
+    /// The one-line usage summary, e.g. `Usage: fox --input <value> [OPTIONS]`
+    pub fn usage(&self) -> String {
+        let mut usage = format!("{} {}", "Usage:".bright_yellow().bold(), self.program_name());
+        for param in &self.required {
+            usage.push_str(&format!(" {} {}", param.long, param.value_name()));
+        }
+        for group in &self.exclusive {
+            let names: Vec<String> = group.iter()
+                .map(|name| self.long_name(name))
+                .filter(|long| !self.optional.iter().any(|param| &param.long == long && param.hidden))
+                .collect();
+            if !names.is_empty() {
+                usage.push_str(&format!(" [{}]", names.join(" | ")));
+            }
+        }
+        usage.push_str(" [OPTIONS]");
+        if !self.subcommands.is_empty() {
+            usage.push_str(" <COMMAND>");
+        }
+        usage
     }
 
+    /// The version line printed by `--version`, e.g. `fox 0.4.0 (a1b2c3d, 2024-05-01)`
+    pub fn version_line(&self) -> String {
+        let mut line = format!("{} {}", self.program_name(), self.version.as_deref().unwrap_or("unknown"));
+        if let Some(info) = &self.build_info {
+            line.push_str(&format!(" ({})", info));
+        }
+        line
+    }
+
+    /// Arguments handled by the parser itself: `--help`, and `--version` once a version is set
+    fn builtin_params(&self) -> Vec<Parameter> {
+        let mut builtins = vec![Parameter {
+            description: Some("Print this help page".into()),
+            ..Parameter::new("--help".into(), Some("-h".into()), false)
+        }];
+
+        if self.version.is_some() {
+            builtins.push(Parameter {
+                description: Some("Print the version".into()),
+                ..Parameter::new("--version".into(), Some("-V".into()), false)
+            });
+        }
+
+        builtins
+    }
+
+    /// Print the help page to stdout
+    pub fn print_help(&self) {
+        print!("{}", self.help());
+    }
+
+    fn program_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            std::env::args()
+                .next()
+                .map(|path| std::path::Path::new(&path).file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or(path))
+                .unwrap_or_default()
+        })
+    }
+
+    /// Parse the program's CLI arguments, exiting with an error message if they don't match the specification.
+    ///
+    /// `--help`/`-h` print the help page and `--version`/`-V` print the version, then exit,
+    /// unless the program specified those arguments itself.
     pub fn parse(self) -> Arguments {
-        let cli_args = std::env::args().skip(1).collect::<Vec<String>>();
+        self.parse_from(std::env::args().skip(1))
+    }
+
+    /// Parse a list of arguments, without the program name, exiting like [`ArgumentsParser::parse`] if they don't match the specification.
+    ///
+    /// Example: `parser.parse_from(["--port", "8080"])`
+    pub fn parse_from<I: IntoIterator<Item = S>, S: Into<String>>(self, args: I) -> Arguments {
+        let usage = self.usage();
+        exit_on_error(self.try_parse_from(args), &usage)
+    }
+
+    /// Split a command line into arguments like a shell would, then parse them,
+    /// exiting like [`ArgumentsParser::parse`] if they don't match the specification.
+    ///
+    /// Example: `parser.parse_str("deploy --env 'prod us' --force")`
+    pub fn parse_str(self, line: &str) -> Arguments {
+        let usage = self.usage();
+        exit_on_error(self.try_parse_str(line), &usage)
+    }
+
+    /// Split a command line into arguments like a shell would, then parse them,
+    /// returning an error instead of exiting. Useful for REPLs and chat bot commands.
+    ///
+    /// Single quotes keep everything literally, double quotes allow `\"` and `\\` escapes,
+    /// and a backslash outside of quotes escapes the next character
+    pub fn try_parse_str(self, line: &str) -> Result<Arguments, ParseError> {
+        self.try_parse_from(split_command_line(line)?)
+    }
+
+    /// Parse the program's CLI arguments, returning an error instead of exiting if they don't match the specification.
+    ///
+    /// Requests for the help page or version are returned as [`ParseError::Help`] and [`ParseError::Version`].
+    pub fn try_parse(self) -> Result<Arguments, ParseError> {
+        self.try_parse_from(std::env::args().skip(1))
+    }
+
+    /// Parse a list of arguments, without the program name, returning an error instead of exiting if they don't match the specification.
+    ///
+    /// Useful for tests and for parsing commands typed into a REPL.
+    pub fn try_parse_from<I: IntoIterator<Item = S>, S: Into<String>>(self, args: I) -> Result<Arguments, ParseError> {
+        self.parse_tokens(args.into_iter().map(Into::into).collect())
+    }
+
+    fn parse_tokens(self, cli_args: Vec<String>) -> Result<Arguments, ParseError> {
+        self.validate().map_err(ParseError::Definition)?;
+
         let mut i = 0;
         let mut args: Vec<Argument> = vec![];
         let mut found_args: Vec<String> = vec![];
+        let mut subcommand = None;
+        let mut trailing = vec![];
+        let mut unmatched = vec![];
 
-        let combined: Vec<Parameter> = self.required.clone().into_iter().chain(self.optional).collect();
+        let combined: Vec<Parameter> = self.required.clone().into_iter().chain(self.optional.clone()).collect();
+        let builtins = self.builtin_params();
 
-        for cli_arg in &cli_args {
-            for param in &combined {
-                if *param.long != *cli_arg {
-                    continue;
+        let find = |name: &str| combined.iter().find(|param| param.matches(name));
+
+        let tracing = self.trace;
+        macro_rules! trace {
+            ($($args:tt)*) => {
+                if tracing {
+                    sdebug!("{} {}", log::dim("[cli]"), format!($($args)*));
                 }
+            };
+        }
 
-                if param.has_value {
-                    match cli_args.get(i + 1) {
-                        Some(_) => {
-                            args.push(Argument {
-                                name: cli_args[i].clone(),
-                                value: Some(cli_args[i + 1].clone())
-                            });
-
-                            if found_args.contains(&cli_args[i]) {
-                                critical!("Argument `{}` provided twice.", cli_args[i]);
-                                std::process::exit(1);
-                            }
-                            else {
-                                found_args.push(cli_args[i].clone());
-                            }
+        trace!("parsing {:?} for `{}`", cli_args, self.program_name());
+
+        while i < cli_args.len() {
+            let token = &cli_args[i];
+            i += 1;
+
+            // Everything after `--` is passed through untouched
+            if token == "--" {
+                trailing = cli_args[i..].to_vec();
+                trace!("`--` passes on {:?}", trailing);
+                break;
+            }
+
+            // `--name=value` and `-n=value`
+            let (cli_arg, inline_value) = match token.split_once('=') {
+                Some((name, value)) if find(name).is_some() => (name, Some(value.to_string())),
+                _ => (token.as_str(), None),
+            };
+
+            if let Some(param) = find(cli_arg) {
+                if param.aliases.iter().any(|alias| alias.name == cli_arg && alias.deprecated) {
+                    swarn!("`{}` is deprecated, use `{}` instead", cli_arg, param.long);
+                }
+
+                let value = if let Some(value) = inline_value {
+                    if !param.has_value {
+                        return Err(ParseError::UnexpectedValue(cli_arg.to_string()));
+                    }
+                    Some(value)
+                }
+                else if param.has_value {
+                    match cli_args.get(i) {
+                        Some(value) => {
+                            i += 1;
+                            Some(value.clone())
                         },
-                        None => {
-                            scritical!("No value provided for argument `{}`", cli_args[i]);
-                            std::process::exit(1);
-                        }
+                        None => return Err(ParseError::MissingValue(format!("{} {}", cli_arg, param.value_name()))),
                     }
                 }
                 else {
-                    args.push(Argument {
-                        name: cli_args[i].clone(),
-                        value: None
-                    });
-
-                    if found_args.contains(&cli_args[i]) {
-                        scritical!("Argument `{}` provided twice.", cli_args[i]);
-                        std::process::exit(1);
+                    None
+                };
+
+                trace!("`{}` matched `{}`{}", token, param.long, value.as_ref().map(|value| format!(" with value {:?}", value)).unwrap_or_default());
+                record(&mut args, &mut found_args, param, value)?;
+                continue;
+            }
+
+            if let Some(builtin) = builtins.iter().find(|param| param.long == cli_arg || param.short.as_deref() == Some(cli_arg)) {
+                trace!("`{}` matched builtin `{}`", token, builtin.long);
+                return match builtin.long.as_str() {
+                    "--version" => Err(ParseError::Version(self.version_line())),
+                    _ => Err(ParseError::Help(self.help())),
+                };
+            }
+
+            if let Some((name, parser)) = self.subcommands.iter().find(|(name, _)| name == cli_arg) {
+                let mut parser = parser.clone();
+                if parser.name.is_none() {
+                    parser.name = Some(format!("{} {}", self.program_name(), name));
+                }
+                parser.trace |= self.trace;
+
+                trace!("`{}` starts subcommand `{}`", token, name);
+
+                subcommand = Some((name.clone(), Box::new(parser.parse_tokens(cli_args[i..].to_vec())?)));
+                break;
+            }
+
+            // Clustered short flags, e.g. `-abc` for `-a -b -c`
+            if cli_arg.len() > 2 && cli_arg.starts_with('-') && !cli_arg.starts_with("--") {
+                let letters: Vec<char> = cli_arg.chars().skip(1).collect();
+
+                if self.allow_unmatched {
+                    let mut unknown = false;
+                    for letter in &letters {
+                        match find(&format!("-{}", letter)) {
+                            None => {
+                                unknown = true;
+                                break;
+                            },
+                            Some(param) if param.has_value => break,
+                            Some(_) => {},
+                        }
                     }
-                    else {
-                        found_args.push(cli_args[i].clone());
+
+                    // Pass on the whole cluster rather than splitting it up
+                    if unknown {
+                        trace!("`{}` has unknown letters, leaving it unmatched", token);
+                        unmatched.push(token.clone());
+                        continue;
+                    }
+                }
+
+                for (j, letter) in letters.iter().enumerate() {
+                    let short = format!("-{}", letter);
+                    let Some(param) = combined.iter().find(|param| param.short.as_ref() == Some(&short)) else {
+                        return Err(ParseError::Unknown(short));
+                    };
+
+                    if !param.has_value {
+                        trace!("`{}` in `{}` matched `{}`", short, token, param.long);
+                        record(&mut args, &mut found_args, param, None)?;
+                        continue;
+                    }
+
+                    // A value-taking flag ends the cluster, taking the rest of it or the next argument as its value
+                    let rest: String = letters[j + 1..].iter().collect();
+                    let value = if !rest.is_empty() {
+                        rest.strip_prefix('=').map(str::to_string).unwrap_or(rest)
+                    }
+                    else if let Some(value) = cli_args.get(i) {
+                        i += 1;
+                        value.clone()
                     }
+                    else {
+                        return Err(ParseError::MissingValue(format!("{} {}", short, param.value_name())));
+                    };
+
+                    trace!("`{}` in `{}` matched `{}` with value {:?}", short, token, param.long, value);
+                    record(&mut args, &mut found_args, param, Some(value))?;
+                    break;
+                }
+
+                continue;
+            }
+
+            if cli_arg.starts_with('-') && cli_arg.len() > 1 && !self.allow_unmatched {
+                let name = cli_arg.split('=').next().unwrap_or(cli_arg);
+                return Err(ParseError::Unknown(name.to_string()));
+            }
+
+            trace!("`{}` didn't match any argument", token);
+            unmatched.push(token.clone());
+        }
+
+        // Fill in arguments missing from the command line, from the environment and then the config file
+        let config = self.load_config(&args)?;
+        for param in &combined {
+            if found_args.contains(&param.long) {
+                continue;
+            }
+
+            let values = match param.env.as_ref().and_then(|var| std::env::var(var).ok()) {
+                Some(value) => {
+                    trace!("`{}` read {:?} from ${}", param.long, value, param.env.as_deref().unwrap_or_default());
+                    vec![value]
+                },
+                None => match config.iter().find(|(name, _)| name == &param.long) {
+                    Some((_, values)) => {
+                        trace!("`{}` read {:?} from the config file", param.long, values);
+                        values.clone()
+                    },
+                    None => continue,
+                },
+            };
+
+            if values.len() > 1 && !param.multiple {
+                return Err(ParseError::InvalidValue { name: param.long.clone(), value: values.join(", "), reason: "expected a single value".into() });
+            }
+
+            for value in values {
+                if param.has_value {
+                    record(&mut args, &mut found_args, param, Some(value))?;
+                    continue;
+                }
+
+                match bool::from_argument(&value) {
+                    Some(true) => record(&mut args, &mut found_args, param, None)?,
+                    Some(false) => {},
+                    None => return Err(ParseError::InvalidValue { name: param.long.clone(), value, reason: format!("expected {}", bool::expected()) }),
                 }
             }
+        }
+
+        for group in &self.exclusive {
+            let mut present = group.iter().map(|name| self.long_name(name)).filter(|long| found_args.contains(long));
+
+            if let (Some(first), Some(second)) = (present.next(), present.next()) {
+                return Err(ParseError::Conflict(first, second));
+            }
+        }
+
+        // Arguments with a default always count as provided
+        let provided = |name: &str| {
+            let long = self.long_name(name);
+            found_args.contains(&long) || combined.iter().any(|param| param.long == long && param.default.is_some())
+        };
+
+        for (name, other) in &self.requires {
+            if found_args.contains(&self.long_name(name)) && !provided(other) {
+                return Err(ParseError::Requires(self.long_name(name), self.long_name(other)));
+            }
+        }
 
-            i += 1
+        for (name, value, target) in &self.required_if {
+            let long = self.long_name(name);
+            let current = args.iter().find(|arg| arg.name == long).and_then(|arg| arg.value.clone())
+                .or_else(|| combined.iter().find(|param| param.long == long).and_then(|param| param.default.clone()));
+
+            if current.as_ref() == Some(value) && !provided(target) {
+                return Err(ParseError::RequiredIf { name: self.long_name(target), condition: long, value: value.clone() });
+            }
         }
 
         for required_arg in self.required {
             if !found_args.contains(&required_arg.long) {
-                scritical!("Missing required argument `{}`", required_arg.long);
-                std::process::exit(1);
+                return Err(ParseError::MissingRequired(required_arg.long));
             }
         }
 
-        Arguments { arguments: args }
+        let defaults = combined.iter()
+            .filter_map(|param| {
+                param.default.as_ref().map(|default| Argument {
+                    name: param.long.clone(),
+                    short: param.short.clone(),
+                    value: Some(default.clone()),
+                })
+            })
+            .collect();
+
+        for param in combined.iter().filter(|param| !found_args.contains(&param.long)) {
+            if let Some(default) = &param.default {
+                trace!("`{}` defaults to {:?}", param.long, default);
+            }
+        }
+
+        let arguments = Arguments { arguments: args, defaults, subcommand, trailing, unmatched, raw: cli_args };
+        if self.log_flags {
+            let level = match arguments.value_of("--log-level").and_then(|level| level.parse::<log::LogLevel>().ok()) {
+                Some(level) => level.as_u8(),
+                None => {
+                    let current = log::get_logging_level().as_u8() as usize;
+                    (current + arguments.count("--verbose")).saturating_sub(arguments.count("--quiet")).clamp(1, 5) as u8
+                },
+            };
+            log::set_logging_level(log::LogLevel::from_u8(level).unwrap_or(log::LogLevel::Info));
+        }
+
+        Ok(arguments)
+    }
+}
+
+/// Why CLI arguments didn't match the parser's specification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// A required argument wasn't provided
+    MissingRequired(String),
+    /// A value-taking argument was last, with nothing after it. Contains the argument and its placeholder, e.g. `--out <DIR>`
+    MissingValue(String),
+    /// A flag was given a value with `--flag=value`
+    UnexpectedValue(String),
+    /// An argument was provided more than once
+    Duplicate(String),
+    /// Two arguments from an exclusive group were provided
+    Conflict(String, String),
+    /// An argument was provided without another one it requires
+    Requires(String, String),
+    /// An argument is required because another one has a certain value
+    RequiredIf { name: String, condition: String, value: String },
+    /// A command line given to [`ArgumentsParser::parse_str`] has a quote that's never closed
+    UnclosedQuote(char),
+    /// The parser itself was specified incorrectly. Contains every problem found
+    Definition(Vec<String>),
+    /// The config file couldn't be read or parsed
+    Config { path: String, reason: String },
+    /// A value was rejected by the argument's choices or validator
+    InvalidValue { name: String, value: String, reason: String },
+    /// An argument starting with `-` that the parser doesn't know
+    Unknown(String),
+    /// `--help` was requested. Contains the rendered help page
+    Help(String),
+    /// `--version` was requested. Contains the version line
+    Version(String),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::MissingRequired(name) => write!(f, "Missing required argument `{}`", name),
+            ParseError::MissingValue(name) => write!(f, "No value provided for argument `{}`", name),
+            ParseError::UnexpectedValue(name) => write!(f, "Argument `{}` is a flag and doesn't take a value", name),
+            ParseError::Duplicate(name) => write!(f, "Argument `{}` provided twice.", name),
+            ParseError::Conflict(first, second) => write!(f, "Arguments `{}` and `{}` can't be used together", first, second),
+            ParseError::Requires(name, other) => write!(f, "Argument `{}` requires `{}` to also be provided", name, other),
+            ParseError::RequiredIf { name, condition, value } => write!(f, "Argument `{}` is required when `{}` is `{}`", name, condition, value),
+            ParseError::UnclosedQuote(quote) => write!(f, "Unclosed quote `{}` in command line", quote),
+            ParseError::Definition(problems) => write!(f, "Invalid argument specification: {}", problems.join("; ")),
+            ParseError::Config { path, reason } => write!(f, "Couldn't load config file `{}`: {}", path, reason),
+            ParseError::InvalidValue { name, value, reason } => write!(f, "Invalid value `{}` for argument `{}`: {}", value, name, reason),
+            ParseError::Unknown(name) => write!(f, "Unknown argument `{}`", name),
+            ParseError::Help(page) => write!(f, "{}", page),
+            ParseError::Version(line) => write!(f, "{}", line),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ArgumentError> for ParseError {
+    fn from(err: ArgumentError) -> Self {
+        match err {
+            ArgumentError::Invalid { name, value, expected } => ParseError::InvalidValue { name, value, reason: format!("expected {}", expected) },
+            // Reading a flag as a value or the other way around is a mistake in the program, not in its arguments
+            err => ParseError::Definition(vec![err.to_string()]),
+        }
+    }
+}
+
+/// Print the help page, version or error for a failed parse and exit, or pass the result through.
+/// Public for the code generated by `#[derive(FoxArgs)]`
+#[doc(hidden)]
+pub fn exit_on_error<T>(result: Result<T, ParseError>, usage: &str) -> T {
+    match result {
+        Ok(args) => args,
+        Err(ParseError::Help(page)) => {
+            print!("{}", page);
+            std::process::exit(0);
+        },
+        Err(ParseError::Version(line)) => {
+            println!("{}", line);
+            std::process::exit(0);
+        },
+        Err(err) => {
+            eprintln!("{} {}\n\n{}\n\nFor more information, try `{}`.", "error:".bright_red().bold(), err, usage, "--help".bright_cyan());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Split a command line into arguments, honoring quotes and backslash escapes
+fn split_command_line(line: &str) -> Result<Vec<String>, ParseError> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    // Distinguishes `''` (an empty argument) from no argument at all
+    let mut in_token = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_token = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => current.push(c),
+                        None => return Err(ParseError::UnclosedQuote('\'')),
+                    }
+                }
+            },
+            '"' => {
+                in_token = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped @ ('"' | '\\')) => current.push(escaped),
+                            Some(c) => {
+                                current.push('\\');
+                                current.push(c);
+                            },
+                            None => return Err(ParseError::UnclosedQuote('"')),
+                        },
+                        Some(c) => current.push(c),
+                        None => return Err(ParseError::UnclosedQuote('"')),
+                    }
+                }
+            },
+            '\\' => {
+                in_token = true;
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            },
+            c if c.is_whitespace() => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            },
+            c => {
+                in_token = true;
+                current.push(c);
+            },
+        }
+    }
+
+    if in_token {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+/// Stores a parsed argument under its long name, failing if it was already provided.
+fn record(args: &mut Vec<Argument>, found_args: &mut Vec<String>, param: &Parameter, value: Option<String>) -> Result<(), ParseError> {
+    if found_args.contains(&param.long) && !param.multiple {
+        return Err(ParseError::Duplicate(param.long.clone()));
+    }
+
+    if let Some(value) = &value {
+        param.validate(value)?;
+    }
+
+    found_args.push(param.long.clone());
+    args.push(Argument {
+        name: param.long.clone(),
+        short: param.short.clone(),
+        value
+    });
+
+    Ok(())
+}
+
+/// Escape text for roff, so dashes stay dashes and lines aren't read as requests
+fn roff_escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    match escaped.starts_with('.') || escaped.starts_with('\'') {
+        true => format!("\\&{}", escaped),
+        false => escaped,
+    }
+}
+
+/// A config file value as an argument value. Objects and nulls have no equivalent
+fn json_scalar(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(text) => Some(text.clone()),
+        serde_json::Value::Number(number) => Some(number.to_string()),
+        serde_json::Value::Bool(boolean) => Some(boolean.to_string()),
+        _ => None,
+    }
+}
+
+/// Read the top-level values of a TOML file. Tables are skipped, since arguments are flat
+#[cfg(feature = "config")]
+fn parse_toml(contents: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let table: toml::Table = contents.parse().map_err(|err: toml::de::Error| err.to_string().trim_end().to_string())?;
+    Ok(table.into_iter()
+        .filter_map(|(key, value)| {
+            let values = match value {
+                toml::Value::Array(items) => items.iter().filter_map(toml_scalar).collect(),
+                value => vec![toml_scalar(&value)?],
+            };
+            Some((key, values))
+        })
+        .collect())
+}
+
+#[cfg(not(feature = "config"))]
+fn parse_toml(_contents: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    Err("reading TOML needs fox's `config` feature".to_string())
+}
+
+/// A TOML value as an argument value. Tables have no equivalent
+#[cfg(feature = "config")]
+fn toml_scalar(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(text) => Some(text.clone()),
+        toml::Value::Table(_) | toml::Value::Array(_) => None,
+        value => Some(value.to_string()),
+    }
+}
+
+impl Default for ArgumentsParser {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 pub struct Argument {
     name: String,
+    short: Option<String>,
     value: Option<String>,
 }
 
+impl Argument {
+    /// Whether this argument goes by `name`, either its long name or its short alias.
+    fn is(&self, name: &str) -> bool {
+        self.name == name || self.short.as_deref() == Some(name)
+    }
+}
+
 /// Reader for values and presence of CLI arguments.
 pub struct Arguments {
-    arguments: Vec<Argument>
+    arguments: Vec<Argument>,
+    defaults: Vec<Argument>,
+    subcommand: Option<(String, Box<Arguments>)>,
+    trailing: Vec<String>,
+    unmatched: Vec<String>,
+    raw: Vec<String>,
 }
 
 impl Arguments {
-    /// Get the value of a CLI argument.
+    /// Get the subcommand that was invoked, along with its own arguments
+    ///
+    /// Example: `./my_program build --target x` gives `Some(("build", args))`
+    pub fn subcommand(&self) -> Option<(&str, &Arguments)> {
+        self.subcommand.as_ref().map(|(name, args)| (name.as_str(), args.as_ref()))
+    }
+
+    /// Get the value of a CLI argument, or its default if it wasn't provided.
     /// Note that this will fail if your argument is a flag (value-less)
     ///
     /// Example: `./my_program --out_dir /dev/null
     pub fn get_value<S: Into<String>>(&self, name: S) -> Option<String> {
-        let name = name.into();
-        for arg in &self.arguments {
-            if arg.name == name {
-                if let Some(val) = &arg.value {
-                    return Some(val.clone())
-                }
+        self.get_value_opt(name).unwrap_or_else(|err| {
+            scritical!("{}", err);
+            std::process::exit(1);
+        })
+    }
 
-                if let None = &arg.value {
-                    scritical!("Tried to get the value of an argument ({}), but the argument is a flag. Did you mean to use `has_flag()`?", arg.name);
-                    std::process::exit(1);
-                }
+    /// Get the value of a CLI argument, or its default if it wasn't provided, without ever exiting.
+    /// Returns [`ArgumentError::IsFlag`] if the argument is a flag
+    pub fn get_value_opt<S: Into<String>>(&self, name: S) -> Result<Option<String>, ArgumentError> {
+        let name = name.into();
+        for arg in self.arguments.iter().chain(&self.defaults) {
+            if arg.is(&name) {
+                return match &arg.value {
+                    Some(value) => Ok(Some(value.clone())),
+                    None => Err(ArgumentError::IsFlag(arg.name.clone())),
+                };
             }
         }
 
-        None
+        Ok(None)
+    }
 
-        // scritical!("Tried to find the value of an argument ({}) that was not specified for parsing", name);
-        // std::process::exit(1);
+    /// Borrow the value of a CLI argument, or its default if it wasn't provided.
+    /// Never exits, giving `None` for flags as well as missing arguments
+    ///
+    /// Example: `let out = args.value_of("--out").unwrap_or("out");`
+    pub fn value_of(&self, name: &str) -> Option<&str> {
+        self.arguments.iter().chain(&self.defaults).find(|arg| arg.is(name))?.value.as_deref()
     }
 
-    /// Determine if a CLI flag is present
-    /// Note that this will fail if the argument has a value
+    /// Get every value given for a CLI argument, in order. Falls back to the default if it wasn't provided.
     ///
-    /// Example: `./my_program --debug
-    pub fn has_flag<S: Into<String>>(&self, name: S) -> bool {
+    /// Example: `./my_program --include src --include tests` gives `["src", "tests"]`
+    pub fn get_values<S: Into<String>>(&self, name: S) -> Vec<String> {
         let name = name.into();
-        for arg in &self.arguments {
-            if arg.name == name {
-                if let None = &arg.value {
-                    return true
+        let values: Vec<String> = self.arguments.iter()
+            .filter(|arg| arg.is(&name))
+            .filter_map(|arg| arg.value.clone())
+            .collect();
+
+        if !values.is_empty() {
+            return values;
+        }
+
+        self.defaults.iter().filter(|arg| arg.is(&name)).filter_map(|arg| arg.value.clone()).collect()
+    }
+
+    /// Get the value of a CLI argument parsed into a type, exiting with an error naming the argument
+    /// and the expected type if it doesn't parse.
+    /// Note that this will fail if your argument is a flag (value-less)
+    ///
+    /// Example: `let port = args.get::<u16>("--port").unwrap_or(8080);`
+    pub fn get<T: FromArgument>(&self, name: &str) -> Option<T> {
+        self.try_get(name).unwrap_or_else(|err| {
+            scritical!("{}", err);
+            std::process::exit(1);
+        })
+    }
+
+    /// Get the value of a CLI argument parsed into a type, returning an error instead of exiting
+    /// if it doesn't parse or the argument is a flag
+    ///
+    /// Example: `let port = args.try_get::<u16>("--port")?.unwrap_or(8080);`
+    pub fn try_get<T: FromArgument>(&self, name: &str) -> Result<Option<T>, ArgumentError> {
+        match self.get_value_opt(name)? {
+            Some(value) => Self::convert(name, &value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Get every value of a repeatable CLI argument parsed into a type, exiting like [`Arguments::get`]
+    /// if any of them doesn't parse
+    ///
+    /// Example: `let ports = args.get_all::<u16>("--port");`
+    pub fn get_all<T: FromArgument>(&self, name: &str) -> Vec<T> {
+        self.try_get_all(name).unwrap_or_else(|err| {
+            scritical!("{}", err);
+            std::process::exit(1);
+        })
+    }
+
+    /// Get every value of a repeatable CLI argument parsed into a type, returning an error instead of
+    /// exiting if any of them doesn't parse
+    pub fn try_get_all<T: FromArgument>(&self, name: &str) -> Result<Vec<T>, ArgumentError> {
+        self.get_values(name).iter().map(|value| Self::convert(name, value)).collect()
+    }
+
+    fn convert<T: FromArgument>(name: &str, value: &str) -> Result<T, ArgumentError> {
+        T::from_argument(value).ok_or_else(|| ArgumentError::Invalid {
+            name: name.to_string(),
+            value: value.to_string(),
+            expected: T::expected(),
+        })
+    }
+
+    /// The arguments after a `--` separator, exactly as given
+    ///
+    /// Example: `./wrapper --verbose -- cargo build --release` gives `["cargo", "build", "--release"]`
+    pub fn trailing(&self) -> &[String] {
+        &self.trailing
+    }
+
+    /// The arguments exactly as given to the parser, without the program name
+    pub fn raw(&self) -> &[String] {
+        &self.raw
+    }
+
+    /// The arguments that didn't match any specified argument, in order.
+    /// These are positional arguments, plus unknown `-` arguments with [`ArgumentsParser::allow_unmatched`]
+    ///
+    /// Example: `./wrapper --verbose build --release` gives `["build", "--release"]` if only `--verbose` is known
+    pub fn unmatched(&self) -> &[String] {
+        &self.unmatched
+    }
+
+    /// Count how many times a CLI flag was given
+    ///
+    /// Example: `let verbosity = args.count("--verbose");` is 3 for `-vvv`
+    pub fn count(&self, name: &str) -> usize {
+        self.arguments.iter().filter(|arg| arg.is(name) && arg.value.is_none()).count()
+    }
+
+    /// Determine if a CLI flag is present, returning [`ArgumentError::HasValue`] if the argument takes a value
+    pub fn try_has_flag(&self, name: &str) -> Result<bool, ArgumentError> {
+        match self.arguments.iter().find(|arg| arg.is(name)) {
+            Some(arg) if arg.value.is_some() => Err(ArgumentError::HasValue(arg.name.clone())),
+            Some(_) => Ok(true),
+            None => Ok(false),
+        }
+    }
+
+    /// Determine if a CLI flag is present, exiting with an error if the argument has a value
+    ///
+    /// Example: `./my_program --debug`
+    pub fn has_flag<S: Into<String>>(&self, name: S) -> bool {
+        self.try_has_flag(&name.into()).unwrap_or_else(|err| {
+            scritical!("{}", err);
+            std::process::exit(1);
+        })
+    }
+}
+
+/// Why a value couldn't be read from [`Arguments`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgumentError {
+    /// The value of a flag was requested
+    IsFlag(String),
+    /// An argument with a value was checked as a flag
+    HasValue(String),
+    /// The value didn't parse into the requested type
+    Invalid { name: String, value: String, expected: String },
+}
+
+impl std::fmt::Display for ArgumentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgumentError::IsFlag(name) => write!(f, "Tried to get the value of an argument ({}), but the argument is a flag. Did you mean to use `has_flag()`?", name),
+            ArgumentError::HasValue(name) => write!(f, "Tried to determine if a flag is present ({}), but the flag has a value. Did you mean to use `get_value()`?", name),
+            ArgumentError::Invalid { name, value, expected } => write!(f, "Invalid value `{}` for argument `{}`: expected {}", value, name, expected),
+        }
+    }
+}
+
+impl std::error::Error for ArgumentError {}
+
+/// Types that argument values can be parsed into with [`Arguments::get`].
+pub trait FromArgument: Sized {
+    /// Describes the expected value for error messages, e.g. `an integer between 0 and 255`
+    fn expected() -> String;
+
+    fn from_argument(value: &str) -> Option<Self>;
+}
+
+macro_rules! impl_from_argument_int {
+    ($($int:ty),*) => {
+        $(
+            impl FromArgument for $int {
+                fn expected() -> String {
+                    format!("an integer between {} and {}", <$int>::MIN, <$int>::MAX)
                 }
 
-                if let None = &arg.value {
-                    scritical!("Tried to determine if a flag is present ({}), but the flag has a value. Did you mean to use `get_value()`?", arg.name);
-                    std::process::exit(1);
+                fn from_argument(value: &str) -> Option<Self> {
+                    value.trim().replace('_', "").parse().ok()
                 }
             }
+        )*
+    };
+}
+
+impl_from_argument_int!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl FromArgument for f32 {
+    fn expected() -> String {
+        "a number".into()
+    }
+
+    fn from_argument(value: &str) -> Option<Self> {
+        value.trim().parse().ok()
+    }
+}
+
+impl FromArgument for f64 {
+    fn expected() -> String {
+        "a number".into()
+    }
+
+    fn from_argument(value: &str) -> Option<Self> {
+        value.trim().parse().ok()
+    }
+}
+
+impl FromArgument for bool {
+    fn expected() -> String {
+        "true or false".into()
+    }
+
+    fn from_argument(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "true" | "yes" | "y" | "on" | "1" => Some(true),
+            "false" | "no" | "n" | "off" | "0" => Some(false),
+            _ => None,
         }
+    }
+}
+
+impl FromArgument for String {
+    fn expected() -> String {
+        "text".into()
+    }
+
+    fn from_argument(value: &str) -> Option<Self> {
+        Some(value.to_string())
+    }
+}
+
+impl FromArgument for std::path::PathBuf {
+    fn expected() -> String {
+        "a path".into()
+    }
+
+    fn from_argument(value: &str) -> Option<Self> {
+        (!value.is_empty()).then(|| value.into())
+    }
+}
+
+impl FromArgument for std::time::Duration {
+    fn expected() -> String {
+        "a duration like 500ms, 30s, 5m, 2h or 1d".into()
+    }
+
+    fn from_argument(value: &str) -> Option<Self> {
+        // Plain numbers are seconds
+        let value = value.trim();
+        let split = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+        let (number, unit) = value.split_at(split);
+        let number: f64 = number.parse().ok()?;
+
+        let seconds = match unit.trim() {
+            "ms" => number / 1000.0,
+            "" | "s" | "sec" | "secs" => number,
+            "m" | "min" | "mins" => number * 60.0,
+            "h" | "hr" | "hrs" => number * 3600.0,
+            "d" | "day" | "days" => number * 86400.0,
+            _ => return None,
+        };
+
+        std::time::Duration::try_from_secs_f64(seconds).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build() -> ArgumentsParser {
+        ArgumentsParser::new()
+            .name("deploy")
+            .version("1.2.0")
+            .required("--env")
+            .optional_with_short("--force", "-f", false)
+            .optional_with_short("--out", "-o", true)
+            .optional_with_default("--port", "8080")
+            .optional_multi("--tag")
+            .optional_count("--verbose", "-v")
+    }
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn arguments_are_parsed() {
+        let args = build().try_parse_from(["--env", "prod", "-f", "--port=9000", "--tag", "a", "--tag=b", "-vvv", "site", "--", "--env", "x"]).unwrap();
+        assert_eq!(args.value_of("--env"), Some("prod"));
+        assert!(args.has_flag("--force") && args.has_flag("-f"));
+        assert_eq!(args.get::<u16>("--port"), Some(9000));
+        assert_eq!(args.get_all::<String>("--tag"), ["a", "b"]);
+        assert_eq!(args.count("--verbose"), 3);
+        assert_eq!(args.unmatched(), ["site"]);
+        assert_eq!(args.trailing(), ["--env", "x"]);
+        assert_eq!(args.raw().len(), 12);
+
+        let args = build().try_parse_from(["--env", "dev", "-fo", "dist"]).unwrap();
+        assert_eq!(args.value_of("-o"), Some("dist"));
+        assert_eq!(args.get::<u16>("--port"), Some(8080));
+        assert!(args.get_all::<String>("--tag").is_empty());
+
+        // A value-taking short flag takes the rest of its cluster
+        let args = build().try_parse_from(["--env", "dev", "-odist"]).unwrap();
+        assert_eq!(args.value_of("--out"), Some("dist"));
+        let args = build().try_parse_from(["--env", "dev", "-vo=dist"]).unwrap();
+        assert_eq!((args.count("-v"), args.value_of("--out")), (1, Some("dist")));
+    }
+
+    #[test]
+    fn values_are_typed() {
+        let args = build().try_parse_from(["--env", "dev", "--port", "http", "--tag", "1", "--tag", "x"]).unwrap();
+        assert_eq!(args.try_get::<u16>("--port"), Err(ArgumentError::Invalid {
+            name: "--port".to_string(),
+            value: "http".to_string(),
+            expected: u16::expected(),
+        }));
+        assert!(args.try_get_all::<u8>("--tag").is_err());
+        assert_eq!(args.try_get::<String>("--force"), Ok(None));
+        assert_eq!(ParseError::from(args.try_get::<u16>("--port").unwrap_err()), ParseError::InvalidValue {
+            name: "--port".to_string(),
+            value: "http".to_string(),
+            reason: "expected an integer between 0 and 65535".to_string(),
+        });
+    }
+
+    #[test]
+    fn parse_errors_are_returned() {
+        let parse = |args: &[&str]| build().try_parse_from(args.iter().copied()).err();
+        assert_eq!(parse(&[]), Some(ParseError::MissingRequired("--env".to_string())));
+        assert_eq!(parse(&["--env"]), Some(ParseError::MissingValue("--env <value>".to_string())));
+        assert_eq!(parse(&["--env", "a", "--force=yes"]), Some(ParseError::UnexpectedValue("--force".to_string())));
+        assert_eq!(parse(&["--env", "a", "--env", "b"]), Some(ParseError::Duplicate("--env".to_string())));
+        assert_eq!(parse(&["--env", "a", "--unknown"]), Some(ParseError::Unknown("--unknown".to_string())));
+        assert_eq!(parse(&["--env", "a", "--unknown=1"]), Some(ParseError::Unknown("--unknown".to_string())));
+        assert_eq!(parse(&["--env", "a", "-fx"]), Some(ParseError::Unknown("-x".to_string())));
+        assert!(matches!(parse(&["--env", "a", "--help"]), Some(ParseError::Help(page)) if page.contains("--force")));
+        assert_eq!(parse(&["-V"]), Some(ParseError::Version("deploy 1.2.0".to_string())));
+
+        // Unknown arguments can be passed on instead
+        let args = build().allow_unmatched(true).try_parse_from(["--env", "a", "--unknown", "-fx"]).unwrap();
+        assert_eq!(args.unmatched(), ["--unknown", "-fx"]);
+        assert!(!args.has_flag("--force"));
+    }
+
+    #[test]
+    fn values_are_validated() {
+        let parser = || ArgumentsParser::new()
+            .optional_choices("--format", &["json", "toml"])
+            .optional("--port", true)
+            .validator(|value| value.parse::<u16>().map(|_| ()).map_err(|_| "not a port".to_string()));
+
+        assert!(parser().try_parse_from(["--format", "toml", "--port", "80"]).is_ok());
+        assert_eq!(parser().try_parse_from(["--format", "yaml"]).err(), Some(ParseError::InvalidValue {
+            name: "--format".to_string(),
+            value: "yaml".to_string(),
+            reason: "expected one of json, toml".to_string(),
+        }));
+        assert_eq!(parser().try_parse_from(["--port=http"]).err(), Some(ParseError::InvalidValue {
+            name: "--port".to_string(),
+            value: "http".to_string(),
+            reason: "not a port".to_string(),
+        }));
+    }
+
+    #[test]
+    fn relations_between_arguments_are_enforced() {
+        let parser = || ArgumentsParser::new()
+            .optional("--json", false)
+            .optional_with_short("--toml", "-t", false)
+            .optional("--key", true)
+            .optional("--cert", true)
+            .optional_with_default("--mode", "client")
+            .optional("--port", true)
+            .group_exclusive(&["--json", "-t"])
+            .requires("--key", "--cert")
+            .required_if("--mode", "server", "--port");
+        let parse = |args: &[&str]| parser().try_parse_from(args.iter().copied()).err();
+
+        assert_eq!(parse(&["--json", "-t"]), Some(ParseError::Conflict("--json".to_string(), "--toml".to_string())));
+        assert_eq!(parse(&["--key", "k"]), Some(ParseError::Requires("--key".to_string(), "--cert".to_string())));
+        assert_eq!(parse(&["--mode", "server"]), Some(ParseError::RequiredIf {
+            name: "--port".to_string(),
+            condition: "--mode".to_string(),
+            value: "server".to_string(),
+        }));
+        assert_eq!(parse(&["--json", "--key", "k", "--cert", "c", "--mode", "server", "--port", "1"]), None);
+        assert_eq!(parse(&[]), None);
+
+        let broken = ArgumentsParser::new().optional("--a", false).optional("--a", true).optional("b", false).requires("--c", "--a");
+        assert_eq!(broken.validate(), Err(strings(&["`--a` is specified more than once", "`b` doesn't start with `-`", "`--c` is used in a relation but isn't specified"])));
+        assert!(matches!(broken.try_parse_from(Vec::<String>::new()), Err(ParseError::Definition(problems)) if problems.len() == 3));
+    }
+
+    #[test]
+    fn aliases_and_environment_variables_are_read() {
+        let parser = || ArgumentsParser::new()
+            .optional("--color", true)
+            .alias("--color", "--colour")
+            .optional("--out-file", true)
+            .deprecated_alias("--out-file", "--output")
+            .optional("--token", true)
+            .env("FOX_CLI_TEST_TOKEN");
+
+        let args = parser().try_parse_from(["--colour=red", "--output", "a.txt"]).unwrap();
+        assert_eq!(args.value_of("--color"), Some("red"));
+        assert_eq!(args.value_of("--out-file"), Some("a.txt"));
+        assert!(parser().help().contains("--colour") && !parser().help().contains("--output"));
+        assert_eq!(parser().try_parse_from(["--color", "a", "--colour", "b"]).err(), Some(ParseError::Duplicate("--color".to_string())));
+
+        std::env::set_var("FOX_CLI_TEST_TOKEN", "secret");
+        assert_eq!(parser().try_parse_from(Vec::<String>::new()).unwrap().value_of("--token"), Some("secret"));
+        assert_eq!(parser().try_parse_from(["--token", "given"]).unwrap().value_of("--token"), Some("given"));
+        std::env::remove_var("FOX_CLI_TEST_TOKEN");
+    }
+
+    #[test]
+    fn subcommands_get_the_rest_of_the_arguments() {
+        let parser = ArgumentsParser::new()
+            .optional("--verbose", false)
+            .subcommand("push", |parser| parser.required("--remote").optional("--force", false));
+
+        let args = parser.clone().try_parse_from(["--verbose", "push", "--remote", "origin", "--force"]).unwrap();
+        let (name, push) = args.subcommand().unwrap();
+        assert_eq!(name, "push");
+        assert_eq!(push.value_of("--remote"), Some("origin"));
+        assert!(args.has_flag("--verbose") && push.has_flag("--force"));
+        assert_eq!(parser.try_parse_from(["push"]).err(), Some(ParseError::MissingRequired("--remote".to_string())));
+    }
+
+    #[test]
+    fn command_lines_are_split_like_a_shell() {
+        assert_eq!(split_command_line("deploy --env 'prod us' --force").unwrap(), strings(&["deploy", "--env", "prod us", "--force"]));
+        assert_eq!(split_command_line(r#"say "a \"quoted\" \n word""#).unwrap(), strings(&["say", r#"a "quoted" \n word"#]));
+        assert_eq!(split_command_line(r#"one\ arg 'it''s' '' """#).unwrap(), strings(&["one arg", "its", "", ""]));
+        assert_eq!(split_command_line("  \t ").unwrap(), Vec::<String>::new());
+        assert_eq!(split_command_line("echo 'open"), Err(ParseError::UnclosedQuote('\'')));
+        assert_eq!(split_command_line(r#"echo "open \""#), Err(ParseError::UnclosedQuote('"')));
+
+        let args = build().try_parse_str("--env 'prod us' -v").unwrap();
+        assert_eq!(args.value_of("--env"), Some("prod us"));
+    }
+
+    #[test]
+    fn flags_with_values_are_not_flags() {
+        let parser = || ArgumentsParser::new().optional("--debug", false).optional("--out", true);
+        let args = parser().try_parse_from(["--debug", "--out", "dist"]).unwrap();
+        assert!(args.has_flag("--debug"));
+        assert_eq!(args.try_has_flag("--out"), Err(ArgumentError::HasValue("--out".to_string())));
+
+        let args = parser().try_parse_from(["--out", "dist"]).unwrap();
+        assert!(!args.has_flag("--debug"));
+    }
+
+    #[test]
+    #[cfg(feature = "config")]
+    fn toml_config_files_fill_in_arguments() {
+        let toml = "# deploy settings\nport = 8_080\nname = \"edge \\\"eu\\\"\"\ntags = ['a', \"b\"]\ndry_run = true\n\n[server]\nport = 1\n";
+        let entries = parse_toml(toml).unwrap();
+        assert_eq!(entries, [
+            ("dry_run", vec!["true"]),
+            ("name", vec!["edge \"eu\""]),
+            ("port", vec!["8080"]),
+            ("tags", vec!["a", "b"]),
+        ].map(|(key, values)| (key.to_string(), values.into_iter().map(String::from).collect::<Vec<_>>())));
+        assert!(parse_toml("port = ").unwrap_err().contains("line 1"));
+
+        let path = std::env::temp_dir().join(format!("fox-cli-{}.toml", std::process::id()));
+        std::fs::write(&path, toml).unwrap();
+        let parser = || ArgumentsParser::new()
+            .config_file("--config", path.clone())
+            .optional_with_default("--port", "80")
+            .optional("--name", true)
+            .optional_multi("--tags")
+            .optional("--dry-run", false);
 
-        false
+        let args = parser().try_parse_from(["--name", "local"]).unwrap();
+        assert_eq!(args.get::<u16>("--port"), Some(8080));
+        assert_eq!(args.value_of("--name"), Some("local"));
+        assert_eq!(args.get_values("--tags"), ["a", "b"]);
+        assert!(args.has_flag("--dry-run"));
+        std::fs::remove_file(&path).unwrap();
     }
 }
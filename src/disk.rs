@@ -1,74 +1,229 @@
 //! Quick file system operations, with pretty logging.
 
-use crate::serror;
+use crate::{sdebug, serror, sinfo};
 use crate as fox;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-/// Deletes the given file.
-pub fn delete_file<P: AsRef<Path>>(file_path: P) -> Result<(), std::io::Error> {
-    let path = file_path.as_ref();
-    std::fs::remove_file(path).map_err(|err| {
-        match err.kind() {
-            std::io::ErrorKind::NotFound => {
-                serror!("File `{}` not found for deletion.", path.display());
-            }
-            std::io::ErrorKind::PermissionDenied => {
-                serror!("Not permitted to delete file `{}`.", path.display());
-            }
-            std::io::ErrorKind::IsADirectory => {
-                serror!("Cannot delete `{}`, as it is a directory.", path.display());
-            }
-            _ => {
-                serror!("Failed to delete file `{}`: {}", path.display(), err);
+#[cfg(feature = "archive")]
+mod archive;
+#[cfg(feature = "archive")]
+pub use archive::*;
+
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "mmap")]
+pub use mmap::*;
+
+#[cfg(feature = "csv")]
+mod csv;
+#[cfg(feature = "csv")]
+pub use self::csv::*;
+
+static LOG_ERRORS: AtomicBool = AtomicBool::new(true);
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables logging of disk errors as they happen. Enabled by default.
+///
+/// Errors are returned either way, so disable this when you report them yourself.
+pub fn set_error_logging(enabled: bool) {
+    LOG_ERRORS.store(enabled, Ordering::Relaxed);
+}
+
+/// Enables or disables dry-run mode for every destructive disk operation. Disabled by default.
+///
+/// While enabled, deletions, writes, copies, downloads, archive extraction and creation, permission changes
+/// and syncs only log what they would do.
+pub fn set_dry_run(enabled: bool) {
+    DRY_RUN.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns true if dry-run mode is enabled with [`set_dry_run`].
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Logs what a destructive operation would do and returns true, if dry-run mode is enabled.
+pub(crate) fn skip_dry_run<F: FnOnce() -> String>(action: F) -> bool {
+    if !is_dry_run() {
+        return false;
+    }
+
+    sinfo!("Would {}.", action());
+    true
+}
+
+/// An error from a disk operation, with the operation and the path(s) it was working on.
+#[derive(Debug)]
+pub struct Error {
+    operation: &'static str,
+    path: PathBuf,
+    target: Option<PathBuf>,
+    source: std::io::Error,
+}
+
+impl Error {
+    pub fn new<P: Into<PathBuf>>(operation: &'static str, path: P, source: std::io::Error) -> Self {
+        Self {
+            operation,
+            path: path.into(),
+            target: None,
+            source,
+        }
+    }
+
+    /// Adds a second path, for operations like copies that go from one path to another.
+    pub fn with_target<P: Into<PathBuf>>(mut self, target: P) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// What was attempted, like `"delete file"`.
+    pub fn operation(&self) -> &str {
+        self.operation
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn target(&self) -> Option<&Path> {
+        self.target.as_deref()
+    }
+
+    pub fn kind(&self) -> std::io::ErrorKind {
+        self.source.kind()
+    }
+
+    pub fn io_error(&self) -> &std::io::Error {
+        &self.source
+    }
+
+    pub fn into_io_error(self) -> std::io::Error {
+        self.source
+    }
+
+    fn subject(&self) -> String {
+        match &self.target {
+            Some(target) => format!("{} `{}` to `{}`", self.operation, self.path.display(), target.display()),
+            None => format!("{} `{}`", self.operation, self.path.display()),
+        }
+    }
+
+    /// Logs the error, unless disabled with [`set_error_logging`], and passes it on.
+    fn logged(self) -> Self {
+        if LOG_ERRORS.load(Ordering::Relaxed) {
+            // Errors we made up ourselves carry a better explanation than their kind
+            let kind = match self.source.raw_os_error() {
+                Some(_) => self.kind(),
+                None => std::io::ErrorKind::Other,
+            };
+
+            match kind {
+                std::io::ErrorKind::NotFound => {
+                    serror!("Failed to {}: not found.", self.subject());
+                }
+                std::io::ErrorKind::PermissionDenied => {
+                    serror!("Not permitted to {}.", self.subject());
+                }
+                std::io::ErrorKind::IsADirectory => {
+                    serror!("Failed to {}, as it is a directory.", self.subject());
+                }
+                _ => {
+                    serror!("Failed to {}: {}", self.subject(), self.source);
+                }
             }
         }
 
-        err
-    })
+        self
+    }
 }
 
-/// Reads the metadata of a file.
-pub fn file_info<P: AsRef<Path>>(file_path: P) -> Result<std::fs::Metadata, std::io::Error> {
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to {}: {}", self.subject(), self.source)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> Self {
+        std::io::Error::new(err.kind(), err)
+    }
+}
+
+/// Builds a logged [`Error`] for an operation on a single path.
+pub(crate) fn fail<P: Into<PathBuf>>(operation: &'static str, path: P, source: std::io::Error) -> Error {
+    Error::new(operation, path, source).logged()
+}
+
+/// Builds a logged [`Error`] for an operation going from one path to another.
+pub(crate) fn fail_to<P: Into<PathBuf>, Q: Into<PathBuf>>(operation: &'static str, path: P, target: Q, source: std::io::Error) -> Error {
+    Error::new(operation, path, source).with_target(target).logged()
+}
+
+/// Opens a file with `options`, for callers writing it piece by piece. Doesn't check for dry-run mode.
+pub(crate) fn open_with(path: &Path, options: &std::fs::OpenOptions) -> Result<std::fs::File, Error> {
+    options.open(path).map_err(|err| fail("open file", path, err))
+}
+
+/// Creates a directory and its parents, if they don't exist yet. Doesn't check for dry-run mode.
+pub(crate) fn create_dir_all(path: &Path) -> Result<(), Error> {
+    std::fs::create_dir_all(path).map_err(|err| fail("create directory", path, err))
+}
+
+/// Moves a file to `target`, replacing it. Doesn't check for dry-run mode.
+pub(crate) fn move_file(path: &Path, target: &Path) -> Result<(), Error> {
+    std::fs::rename(path, target).map_err(|err| fail_to("move", path, target, err))
+}
+
+/// Deletes a temporary file, if it's still there. Doesn't check for dry-run mode.
+pub(crate) fn remove_temp_file(path: &Path) -> Result<(), Error> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(fail("delete file", path, err)),
+        _ => Ok(()),
+    }
+}
+
+/// Deletes the given file.
+pub fn delete_file<P: AsRef<Path>>(file_path: P) -> Result<(), Error> {
     let path = file_path.as_ref();
-    std::fs::metadata(path).map_err(|err| {
-        match err.kind() {
-            std::io::ErrorKind::NotFound => {
-                serror!("File `{}` not found.", path.display());
-            }
-            std::io::ErrorKind::PermissionDenied => {
-                serror!("Not permitted to read metadata of file `{}`.", path.display());
-            }
-            _ => {
-                serror!("Failed to read metadata of file `{}`: {}", path.display(), err);
-            }
-        }
+    if skip_dry_run(|| format!("delete file `{}`", path.display())) {
+        return Ok(());
+    }
 
-        err
-    })
+    std::fs::remove_file(path).map_err(|err| fail("delete file", path, err))
+}
+
+/// Deletes the given directory, along with everything inside of it.
+pub fn delete_dir<P: AsRef<Path>>(dir_path: P) -> Result<(), Error> {
+    let path = dir_path.as_ref();
+    if skip_dry_run(|| format!("delete directory `{}`", path.display())) {
+        return Ok(());
+    }
+
+    std::fs::remove_dir_all(path).map_err(|err| fail("delete directory", path, err))
+}
+
+/// Reads the metadata of a file.
+pub fn file_info<P: AsRef<Path>>(file_path: P) -> Result<std::fs::Metadata, Error> {
+    let path = file_path.as_ref();
+    std::fs::metadata(path).map_err(|err| fail("read metadata of file", path, err))
 }
 
 /// Lists the content of a directory.
-pub fn list_dir<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>, std::io::Error> {
+pub fn list_dir<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>, Error> {
     let path = path.as_ref();
-    let entries = std::fs::read_dir(path).map_err(|err| {
-        match err.kind() {
-            std::io::ErrorKind::NotFound => {
-                serror!("Directory `{}` not found.", path.display());
-            }
-            std::io::ErrorKind::PermissionDenied => {
-                serror!("Not permitted to read directory `{}`.", path.display());
-            }
-            _ => {
-                serror!("Failed to read directory `{}`: {}", path.display(), err);
-            }
-        }
-
-        err
-    })?;
+    let entries = std::fs::read_dir(path).map_err(|err| fail("read directory", path, err))?;
 
     let mut files = Vec::new();
     for entry in entries {
-        let entry = entry?;
+        let entry = entry.map_err(|err| fail("read directory", path, err))?;
         let entry_path = entry.path();
 
         files.push(entry_path);
@@ -79,29 +234,15 @@ pub fn list_dir<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>, std::io::Error>
 }
 
 /// Lists the content of a directory, recursively.
-pub fn list_dir_all<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>, std::io::Error> {
+pub fn list_dir_all<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>, Error> {
     let root_path = path.as_ref();
     let mut files = Vec::new();
 
-    fn read_dir_recursive(path: &Path, result: &mut Vec<PathBuf>) -> Result<(), std::io::Error> {
-        let entries = std::fs::read_dir(path).map_err(|err| {
-            match err.kind() {
-                std::io::ErrorKind::NotFound => {
-                    serror!("Directory `{}` not found.", path.display());
-                }
-                std::io::ErrorKind::PermissionDenied => {
-                    serror!("Not permitted to read directory `{}`.", path.display());
-                }
-                _ => {
-                    serror!("Failed to read directory `{}`: {}", path.display(), err);
-                }
-            }
-
-            err
-        })?;
+    fn read_dir_recursive(path: &Path, result: &mut Vec<PathBuf>) -> Result<(), Error> {
+        let entries = std::fs::read_dir(path).map_err(|err| fail("read directory", path, err))?;
 
         for entry in entries {
-            let entry = entry?;
+            let entry = entry.map_err(|err| fail("read directory", path, err))?;
             let entry_path = entry.path();
 
             result.push(entry_path.clone());
@@ -119,7 +260,7 @@ pub fn list_dir_all<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>, std::io::Er
 }
 
 /// Lists the content of a directory, recursively. Returns relative paths from the root.
-pub fn list_dir_all_relative<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>, std::io::Error> {
+pub fn list_dir_all_relative<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>, Error> {
     let root_path = path.as_ref();
     let all_paths = list_dir_all(root_path)?;
 
@@ -130,3 +271,1758 @@ pub fn list_dir_all_relative<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>, st
 
     Ok(relative_paths)
 }
+
+/// A portable summary of a file's permissions.
+///
+/// On unix this carries the raw mode bits, on Windows only the read-only attribute is meaningful.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Permissions {
+    pub readonly: bool,
+    pub executable: bool,
+    pub mode: Option<u32>,
+}
+
+impl Permissions {
+    /// Builds a permissions summary from already fetched metadata.
+    pub fn from_metadata(metadata: &std::fs::Metadata) -> Self {
+        let permissions = metadata.permissions();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = permissions.mode() & 0o7777;
+
+            Self {
+                readonly: mode & 0o222 == 0,
+                executable: metadata.is_file() && mode & 0o111 != 0,
+                mode: Some(mode),
+            }
+        }
+
+        #[cfg(not(unix))]
+        {
+            Self {
+                readonly: permissions.readonly(),
+                executable: false,
+                mode: None,
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Permissions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.mode {
+            Some(mode) => {
+                let flags = ['r', 'w', 'x'];
+                let mut text = String::with_capacity(9);
+                for shift in [6, 3, 0] {
+                    for (i, flag) in flags.iter().enumerate() {
+                        if mode >> shift & (0b100 >> i) != 0 {
+                            text.push(*flag);
+                        } else {
+                            text.push('-');
+                        }
+                    }
+                }
+                write!(f, "{} ({:o})", text, mode)
+            }
+            None => {
+                if self.readonly {
+                    write!(f, "read-only")
+                } else {
+                    write!(f, "read-write")
+                }
+            }
+        }
+    }
+}
+
+/// Reads a portable summary of the permissions of a file.
+pub fn permissions<P: AsRef<Path>>(file_path: P) -> Result<Permissions, Error> {
+    let metadata = file_info(file_path)?;
+    Ok(Permissions::from_metadata(&metadata))
+}
+
+/// Marks a file as read-only, or makes it writable again.
+///
+/// On unix this clears every write bit, and restores only the owner's write bit when `readonly` is false.
+pub fn set_readonly<P: AsRef<Path>>(file_path: P, readonly: bool) -> Result<(), Error> {
+    let path = file_path.as_ref();
+    let mut permissions = file_info(path)?.permissions();
+    if skip_dry_run(|| format!("mark `{}` as {}", path.display(), if readonly { "read-only" } else { "writable" })) {
+        return Ok(());
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = permissions.mode();
+        if readonly {
+            permissions.set_mode(mode & !0o222);
+        } else {
+            permissions.set_mode(mode | 0o200);
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(readonly);
+    }
+
+    std::fs::set_permissions(path, permissions).map_err(|err| fail("change permissions of", path, err))
+}
+
+/// Makes a file executable by everyone who can read it.
+///
+/// Windows has no executable bit, so this only checks that the file exists there.
+pub fn make_executable<P: AsRef<Path>>(file_path: P) -> Result<(), Error> {
+    let path = file_path.as_ref();
+    #[allow(unused_mut)]
+    let mut permissions = file_info(path)?.permissions();
+    if skip_dry_run(|| format!("make `{}` executable", path.display())) {
+        return Ok(());
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = permissions.mode();
+        permissions.set_mode(mode | (mode & 0o444) >> 2);
+    }
+
+    std::fs::set_permissions(path, permissions).map_err(|err| fail("set permissions of", path, err))
+}
+
+/// How [`diff_dirs_by`] decides whether a file present on both sides was modified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareBy {
+    /// Compare size and modification time. Fast, but trusts the timestamps.
+    Metadata,
+    /// Compare size, then the hash of the content.
+    Hash,
+}
+
+/// The differences between two directory trees, as paths relative to their roots.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirDiff {
+    /// Entries that only exist in the second tree.
+    pub added: Vec<PathBuf>,
+    /// Entries that only exist in the first tree.
+    pub removed: Vec<PathBuf>,
+    /// Files that exist in both trees, but differ.
+    pub modified: Vec<PathBuf>,
+}
+
+impl DirDiff {
+    /// Returns true if both trees are identical.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// Hashes the content of a file, streaming it in chunks.
+fn hash_file(path: &Path) -> Result<u64, Error> {
+    use std::hash::Hasher;
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path).map_err(|err| fail("hash file", path, err))?;
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buffer).map_err(|err| fail("hash file", path, err))?;
+        if n == 0 {
+            break;
+        }
+        hasher.write(&buffer[..n]);
+    }
+
+    Ok(hasher.finish())
+}
+
+/// Compares two directory trees, using size and modification time to detect modified files.
+pub fn diff_dirs<P: AsRef<Path>, Q: AsRef<Path>>(a: P, b: Q) -> Result<DirDiff, Error> {
+    diff_dirs_by(a, b, CompareBy::Metadata)
+}
+
+/// Compares two directory trees, using the given strategy to detect modified files.
+pub fn diff_dirs_by<P: AsRef<Path>, Q: AsRef<Path>>(a: P, b: Q, compare: CompareBy) -> Result<DirDiff, Error> {
+    let a = a.as_ref();
+    let b = b.as_ref();
+
+    let a_entries: std::collections::BTreeSet<PathBuf> = if a.exists() {
+        list_dir_all_relative(a)?.into_iter().collect()
+    } else {
+        Default::default()
+    };
+    let b_entries: std::collections::BTreeSet<PathBuf> = list_dir_all_relative(b)?.into_iter().collect();
+
+    let mut diff = DirDiff {
+        added: b_entries.difference(&a_entries).cloned().collect(),
+        removed: a_entries.difference(&b_entries).cloned().collect(),
+        modified: Vec::new(),
+    };
+
+    for entry in a_entries.intersection(&b_entries) {
+        let a_path = a.join(entry);
+        let b_path = b.join(entry);
+        let a_meta = file_info(&a_path)?;
+        let b_meta = file_info(&b_path)?;
+
+        if a_meta.is_dir() && b_meta.is_dir() {
+            continue;
+        }
+
+        let modified = if a_meta.is_dir() != b_meta.is_dir() || a_meta.len() != b_meta.len() {
+            true
+        } else {
+            match compare {
+                CompareBy::Metadata => a_meta.modified().ok() != b_meta.modified().ok(),
+                CompareBy::Hash => hash_file(&a_path)? != hash_file(&b_path)?,
+            }
+        };
+
+        if modified {
+            diff.modified.push(entry.clone());
+        }
+    }
+
+    Ok(diff)
+}
+
+/// Makes `dst` an exact copy of `src`, copying added and modified files and deleting removed ones.
+///
+/// With `dry_run`, or while [`set_dry_run`] is enabled, the planned changes are logged
+/// and returned without touching the filesystem.
+pub fn sync_dirs<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q, dry_run: bool) -> Result<DirDiff, Error> {
+    let src = src.as_ref();
+    let dst = dst.as_ref();
+    let diff = diff_dirs(dst, src)?;
+
+    if dry_run || is_dry_run() {
+        for entry in &diff.added {
+            sinfo!("Would copy `{}` to `{}`.", src.join(entry).display(), dst.join(entry).display());
+        }
+        for entry in &diff.modified {
+            sinfo!("Would update `{}`.", dst.join(entry).display());
+        }
+        for entry in &diff.removed {
+            sinfo!("Would delete `{}`.", dst.join(entry).display());
+        }
+
+        return Ok(diff);
+    }
+
+    std::fs::create_dir_all(dst).map_err(|err| fail("create directory", dst, err))?;
+
+    // Deepest entries first, so directories are empty by the time they are removed
+    for entry in diff.removed.iter().rev() {
+        let path = dst.join(entry);
+        let result = if path.is_dir() && !path.is_symlink() {
+            std::fs::remove_dir(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+
+        result.map_err(|err| fail("delete", &path, err))?;
+    }
+
+    // Parents sort before their children, so directories exist before anything is copied into them
+    let mut changed: Vec<&PathBuf> = diff.added.iter().chain(&diff.modified).collect();
+    changed.sort();
+
+    for entry in changed {
+        let from = src.join(entry);
+        let to = dst.join(entry);
+
+        let result = if from.is_dir() {
+            if to.is_file() {
+                std::fs::remove_file(&to).and_then(|_| std::fs::create_dir_all(&to))
+            } else {
+                std::fs::create_dir_all(&to)
+            }
+        } else {
+            let cleared = if to.is_dir() { std::fs::remove_dir_all(&to) } else { Ok(()) };
+            cleared
+                .and_then(|_| std::fs::copy(&from, &to))
+                .and_then(|_| std::fs::metadata(&from)?.modified())
+                .and_then(|modified| std::fs::File::options().write(true).open(&to)?.set_modified(modified))
+        };
+
+        result.map_err(|err| fail_to("copy", &from, &to, err))?;
+    }
+
+    Ok(diff)
+}
+
+/// Finds files with identical content under a directory, recursively.
+///
+/// Files are grouped by size first, so only files sharing a size are hashed.
+/// Each returned set holds two or more duplicates, sorted by path. Empty files are ignored.
+pub fn find_duplicates<P: AsRef<Path>>(path: P) -> Result<Vec<Vec<PathBuf>>, Error> {
+    let mut by_size: std::collections::BTreeMap<u64, Vec<PathBuf>> = std::collections::BTreeMap::new();
+    for entry in list_dir_all(path)? {
+        let metadata = std::fs::symlink_metadata(&entry).map_err(|err| fail("read metadata of file", &entry, err))?;
+        if metadata.is_file() && metadata.len() > 0 {
+            by_size.entry(metadata.len()).or_default().push(entry);
+        }
+    }
+
+    let mut duplicates = Vec::new();
+    for files in by_size.into_values().filter(|files| files.len() > 1) {
+        let mut by_hash: std::collections::HashMap<u64, Vec<PathBuf>> = std::collections::HashMap::new();
+        for file in files {
+            let hash = hash_file(&file)?;
+            by_hash.entry(hash).or_default().push(file);
+        }
+
+        duplicates.extend(by_hash.into_values().filter(|files| files.len() > 1));
+    }
+
+    for set in &mut duplicates {
+        set.sort();
+    }
+    duplicates.sort();
+    Ok(duplicates)
+}
+
+/// Copies a file or a directory tree, reporting progress in bytes.
+///
+/// `progress` is called with the amount of bytes copied so far and the total amount of bytes,
+/// which makes it easy to drive a [`crate::snips::ProgressBar`].
+pub fn copy_with_progress<P, Q, F>(src: P, dst: Q, mut progress: F) -> Result<u64, Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    F: FnMut(u64, u64),
+{
+    let src = src.as_ref();
+    let dst = dst.as_ref();
+    let metadata = file_info(src)?;
+
+    if !metadata.is_dir() {
+        let total = metadata.len();
+        if skip_dry_run(|| format!("copy `{}` to `{}` ({})", src.display(), dst.display(), format_size(total))) {
+            return Ok(total);
+        }
+
+        let mut copied = 0;
+        copy_file_chunked(src, dst, &mut copied, total, &mut progress)?;
+        return Ok(copied);
+    }
+
+    let entries = list_dir_all_relative(src)?;
+    let mut total = 0;
+    for entry in &entries {
+        let metadata = file_info(src.join(entry))?;
+        if metadata.is_file() {
+            total += metadata.len();
+        }
+    }
+
+    if skip_dry_run(|| format!("copy `{}` to `{}` ({})", src.display(), dst.display(), format_size(total))) {
+        return Ok(total);
+    }
+
+    std::fs::create_dir_all(dst).map_err(|err| fail("create directory", dst, err))?;
+
+    let mut copied = 0;
+    for entry in &entries {
+        let from = src.join(entry);
+        let to = dst.join(entry);
+
+        if from.is_dir() {
+            std::fs::create_dir_all(&to).map_err(|err| fail("create directory", &to, err))?;
+        } else {
+            copy_file_chunked(&from, &to, &mut copied, total, &mut progress)?;
+        }
+    }
+
+    Ok(copied)
+}
+
+fn copy_file_chunked<F: FnMut(u64, u64)>(
+    from: &Path,
+    to: &Path,
+    copied: &mut u64,
+    total: u64,
+    progress: &mut F,
+) -> Result<(), Error> {
+    use std::io::{Read, Write};
+
+    let result = (|| {
+        let mut reader = std::fs::File::open(from)?;
+        let mut writer = std::fs::File::create(to)?;
+        let mut buffer = vec![0u8; 256 * 1024];
+
+        loop {
+            let n = reader.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..n])?;
+
+            *copied += n as u64;
+            progress(*copied, total);
+        }
+
+        writer.flush()?;
+        std::fs::set_permissions(to, reader.metadata()?.permissions())
+    })();
+
+    result.map_err(|err| fail_to("copy", from, to, err))
+}
+
+/// Joins an untrusted relative path onto `root`, making sure the result stays inside of `root`.
+///
+/// `.` and `..` components are resolved lexically. Absolute paths, drive prefixes, and `..`
+/// components that would climb above `root` are rejected.
+pub fn safe_join<P: AsRef<Path>, Q: AsRef<Path>>(root: P, untrusted: Q) -> Result<PathBuf, Error> {
+    use std::path::Component;
+
+    let root = root.as_ref();
+    let untrusted = untrusted.as_ref();
+    let mut relative = PathBuf::new();
+
+    for component in untrusted.components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !relative.pop() {
+                    let err = std::io::Error::new(std::io::ErrorKind::InvalidInput, "path escapes the root");
+                    return Err(fail_to("join", untrusted, root, err));
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                let err = std::io::Error::new(std::io::ErrorKind::InvalidInput, "path is absolute");
+                return Err(fail_to("join", untrusted, root, err));
+            }
+        }
+    }
+
+    if relative.as_os_str().is_empty() {
+        return Ok(root.to_path_buf());
+    }
+
+    Ok(root.join(relative))
+}
+
+/// Returns the home directory of the current user.
+fn home_dir() -> Option<PathBuf> {
+    let var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+    std::env::var_os(var).filter(|home| !home.is_empty()).map(PathBuf::from)
+}
+
+/// Looks up the home directory of another user through the system user database, so NSS, LDAP and SSSD users are found too.
+#[cfg(unix)]
+fn user_home_dir(user: &str) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let name = std::ffi::CString::new(user).ok()?;
+    let mut buffer = vec![0 as libc::c_char; 1024];
+
+    loop {
+        // SAFETY: passwd is plain old data, so zeroed memory is a valid value.
+        let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result: *mut libc::passwd = std::ptr::null_mut();
+
+        // SAFETY: `name` is nul-terminated, and `buffer` is writable for the given length and outlives the
+        // strings getpwnam_r points `entry` into.
+        let code = unsafe { libc::getpwnam_r(name.as_ptr(), &mut entry, buffer.as_mut_ptr(), buffer.len(), &mut result) };
+
+        if code == libc::ERANGE && buffer.len() < 1 << 20 {
+            buffer.resize(buffer.len() * 2, 0);
+            continue;
+        }
+        if code != 0 || result.is_null() || entry.pw_dir.is_null() {
+            return None;
+        }
+
+        // SAFETY: on success pw_dir points to a nul-terminated string inside `buffer`.
+        let dir = unsafe { std::ffi::CStr::from_ptr(entry.pw_dir) };
+        return Some(PathBuf::from(std::ffi::OsStr::from_bytes(dir.to_bytes())));
+    }
+}
+
+#[cfg(not(unix))]
+fn user_home_dir(_user: &str) -> Option<PathBuf> {
+    None
+}
+
+/// Expands a leading `~` or `~user`, and `$VAR`, `${VAR}` and `%VAR%` environment variables in a path.
+///
+/// Unknown users and unset variables are reported as errors, rather than being left in the path.
+pub fn expand<P: AsRef<Path>>(path: P) -> Result<PathBuf, Error> {
+    let path = path.as_ref();
+    let Some(text) = path.to_str() else {
+        let err = std::io::Error::new(std::io::ErrorKind::InvalidData, "path is not valid UTF-8");
+        return Err(fail("expand", path, err));
+    };
+
+    let not_found = |what: String| {
+        let err = std::io::Error::new(std::io::ErrorKind::NotFound, format!("{} not found", what));
+        fail("expand", path, err)
+    };
+
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    if let Some(after_tilde) = text.strip_prefix('~') {
+        let end = after_tilde.find(['/', '\\']).unwrap_or(after_tilde.len());
+        let user = &after_tilde[..end];
+        let home = if user.is_empty() {
+            home_dir().ok_or_else(|| not_found("home directory".to_string()))?
+        } else {
+            user_home_dir(user).ok_or_else(|| not_found(format!("user `{}`", user)))?
+        };
+
+        result.push_str(&home.to_string_lossy());
+        rest = &after_tilde[end..];
+    }
+
+    let mut chars = rest.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let name = match c {
+            '$' if rest[i + 1..].starts_with('{') => {
+                let Some(len) = rest[i + 2..].find('}') else {
+                    result.push(c);
+                    continue;
+                };
+                let name = &rest[i + 2..i + 2 + len];
+                while chars.next_if(|(j, _)| *j <= i + 2 + len).is_some() {}
+                name
+            }
+            '$' => {
+                let len = rest[i + 1..]
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len() - i - 1);
+                if len == 0 {
+                    result.push(c);
+                    continue;
+                }
+                let name = &rest[i + 1..i + 1 + len];
+                while chars.next_if(|(j, _)| *j <= i + len).is_some() {}
+                name
+            }
+            '%' => {
+                let Some(len) = rest[i + 1..].find('%') else {
+                    result.push(c);
+                    continue;
+                };
+                let name = &rest[i + 1..i + 1 + len];
+                if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || "_()".contains(c)) {
+                    result.push(c);
+                    continue;
+                }
+                while chars.next_if(|(j, _)| *j <= i + 1 + len).is_some() {}
+                name
+            }
+            _ => {
+                result.push(c);
+                continue;
+            }
+        };
+
+        let value = std::env::var(name).map_err(|_| not_found(format!("environment variable `{}`", name)))?;
+        result.push_str(&value);
+    }
+
+    Ok(PathBuf::from(result))
+}
+
+#[derive(Clone, Copy)]
+enum AppDir {
+    Config,
+    Data,
+    Cache,
+}
+
+fn app_dir(kind: AppDir, app: &str, create: bool) -> Result<PathBuf, Error> {
+    let from_env = |var: &str| {
+        std::env::var_os(var)
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+    };
+    let from_home = |relative: &str| home_dir().map(|home| home.join(relative));
+
+    let base = if cfg!(windows) {
+        match kind {
+            AppDir::Config | AppDir::Data => from_env("APPDATA"),
+            AppDir::Cache => from_env("LOCALAPPDATA"),
+        }
+    } else if cfg!(target_os = "macos") {
+        match kind {
+            AppDir::Config | AppDir::Data => from_home("Library/Application Support"),
+            AppDir::Cache => from_home("Library/Caches"),
+        }
+    } else {
+        match kind {
+            AppDir::Config => from_env("XDG_CONFIG_HOME").or_else(|| from_home(".config")),
+            AppDir::Data => from_env("XDG_DATA_HOME").or_else(|| from_home(".local/share")),
+            AppDir::Cache => from_env("XDG_CACHE_HOME").or_else(|| from_home(".cache")),
+        }
+    };
+
+    let Some(base) = base else {
+        let err = std::io::Error::new(std::io::ErrorKind::NotFound, "home directory not found");
+        return Err(fail("locate the directory of", app, err));
+    };
+
+    let dir = base.join(app);
+    if create {
+        std::fs::create_dir_all(&dir).map_err(|err| fail("create directory", &dir, err))?;
+    }
+
+    Ok(dir)
+}
+
+/// Resolves the configuration directory of an application, creating it if `create` is set.
+///
+/// `$XDG_CONFIG_HOME/app` on Linux, `~/Library/Application Support/app` on macOS, and `%APPDATA%\app` on Windows.
+pub fn config_dir(app: &str, create: bool) -> Result<PathBuf, Error> {
+    app_dir(AppDir::Config, app, create)
+}
+
+/// Resolves the data directory of an application, creating it if `create` is set.
+///
+/// `$XDG_DATA_HOME/app` on Linux, `~/Library/Application Support/app` on macOS, and `%APPDATA%\app` on Windows.
+pub fn data_dir(app: &str, create: bool) -> Result<PathBuf, Error> {
+    app_dir(AppDir::Data, app, create)
+}
+
+/// Resolves the cache directory of an application, creating it if `create` is set.
+///
+/// `$XDG_CACHE_HOME/app` on Linux, `~/Library/Caches/app` on macOS, and `%LOCALAPPDATA%\app` on Windows.
+pub fn cache_dir(app: &str, create: bool) -> Result<PathBuf, Error> {
+    app_dir(AppDir::Cache, app, create)
+}
+
+/// A buffered iterator over the lines of a file, created by [`lines`].
+pub struct Lines {
+    path: PathBuf,
+    line: u64,
+    inner: std::io::Lines<std::io::BufReader<std::fs::File>>,
+}
+
+impl Iterator for Lines {
+    type Item = Result<String, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.inner.next()?;
+        self.line += 1;
+
+        Some(next.map_err(|err| {
+            let err = std::io::Error::new(err.kind(), format!("line {}: {}", self.line, err));
+            fail("read file", &self.path, err)
+        }))
+    }
+}
+
+/// Opens a file for reading line by line, without loading it into memory.
+pub fn lines<P: AsRef<Path>>(file_path: P) -> Result<Lines, Error> {
+    use std::io::BufRead;
+
+    let path = file_path.as_ref();
+    let file = std::fs::File::open(path).map_err(|err| fail("open file", path, err))?;
+
+    Ok(Lines {
+        path: path.to_path_buf(),
+        line: 0,
+        inner: std::io::BufReader::new(file).lines(),
+    })
+}
+
+/// Calls `f` for every line of a file, stopping at the first read error.
+pub fn for_each_line<P: AsRef<Path>, F: FnMut(&str)>(file_path: P, mut f: F) -> Result<(), Error> {
+    for line in lines(file_path)? {
+        f(&line?);
+    }
+
+    Ok(())
+}
+
+/// Formats a byte count as a human-readable size, like `1.5 MiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// The kind of filesystem entry described by a [`FileInfo`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Directory,
+    Symlink,
+    Other,
+}
+
+impl std::fmt::Display for FileKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileKind::File => write!(f, "file"),
+            FileKind::Directory => write!(f, "directory"),
+            FileKind::Symlink => write!(f, "symlink"),
+            FileKind::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// Everything worth knowing about a file, in a printable form.
+#[derive(Debug, Clone)]
+pub struct FileInfo {
+    pub path: PathBuf,
+    pub kind: FileKind,
+    pub size: u64,
+    pub permissions: Permissions,
+    /// Name of the owning user, or their id if it has no name. Always `None` outside of unix.
+    pub owner: Option<String>,
+    pub created: Option<chrono::DateTime<chrono::Local>>,
+    pub modified: Option<chrono::DateTime<chrono::Local>>,
+    pub accessed: Option<chrono::DateTime<chrono::Local>>,
+}
+
+impl FileInfo {
+    /// The size of the file, formatted with [`format_size`].
+    pub fn size_human(&self) -> String {
+        format_size(self.size)
+    }
+}
+
+impl std::fmt::Display for FileInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = |time: &Option<chrono::DateTime<chrono::Local>>| match time {
+            Some(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None => "unknown".to_string(),
+        };
+
+        let rows = [
+            ("path", self.path.display().to_string()),
+            ("type", self.kind.to_string()),
+            ("size", format!("{} ({} bytes)", self.size_human(), self.size)),
+            ("permissions", self.permissions.to_string()),
+            ("owner", self.owner.clone().unwrap_or_else(|| "unknown".to_string())),
+            ("created", time(&self.created)),
+            ("modified", time(&self.modified)),
+            ("accessed", time(&self.accessed)),
+        ];
+
+        for (i, (key, value)) in rows.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{} {}", crate::log::dim(&format!("{:>11} │", key)), value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Looks up the name of a user id through the system user database, so NSS, LDAP and SSSD users are found too.
+#[cfg(unix)]
+fn user_name(uid: u32) -> Option<String> {
+    let mut buffer = vec![0 as libc::c_char; 1024];
+
+    loop {
+        // SAFETY: passwd is plain old data, so zeroed memory is a valid value.
+        let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result: *mut libc::passwd = std::ptr::null_mut();
+
+        // SAFETY: `buffer` is writable for the given length and outlives the strings getpwuid_r points `entry` into.
+        let code = unsafe { libc::getpwuid_r(uid as libc::uid_t, &mut entry, buffer.as_mut_ptr(), buffer.len(), &mut result) };
+
+        if code == libc::ERANGE && buffer.len() < 1 << 20 {
+            buffer.resize(buffer.len() * 2, 0);
+            continue;
+        }
+        if code != 0 || result.is_null() || entry.pw_name.is_null() {
+            return None;
+        }
+
+        // SAFETY: on success pw_name points to a nul-terminated string inside `buffer`.
+        let name = unsafe { std::ffi::CStr::from_ptr(entry.pw_name) };
+        return Some(name.to_string_lossy().into_owned());
+    }
+}
+
+/// Reads everything about a file into a [`FileInfo`]. Symlinks are described, not followed.
+pub fn info<P: AsRef<Path>>(file_path: P) -> Result<FileInfo, Error> {
+    let path = file_path.as_ref();
+    let metadata = std::fs::symlink_metadata(path).map_err(|err| fail("read metadata of file", path, err))?;
+
+    let file_type = metadata.file_type();
+    let kind = if file_type.is_symlink() {
+        FileKind::Symlink
+    } else if file_type.is_dir() {
+        FileKind::Directory
+    } else if file_type.is_file() {
+        FileKind::File
+    } else {
+        FileKind::Other
+    };
+
+    #[cfg(unix)]
+    let owner = {
+        use std::os::unix::fs::MetadataExt;
+        let uid = metadata.uid();
+        Some(user_name(uid).unwrap_or_else(|| uid.to_string()))
+    };
+    #[cfg(not(unix))]
+    let owner = None;
+
+    Ok(FileInfo {
+        path: path.to_path_buf(),
+        kind,
+        size: metadata.len(),
+        permissions: Permissions::from_metadata(&metadata),
+        owner,
+        created: metadata.created().ok().map(Into::into),
+        modified: metadata.modified().ok().map(Into::into),
+        accessed: metadata.accessed().ok().map(Into::into),
+    })
+}
+
+/// Removes every directory under `root` that contains no files, directly or in a subdirectory.
+///
+/// `root` itself is kept. With `dry_run`, or while [`set_dry_run`] is enabled, nothing is removed,
+/// and the directories that would be removed are returned instead. Returned paths are ordered deepest first.
+pub fn prune_empty_dirs<P: AsRef<Path>>(root: P, dry_run: bool) -> Result<Vec<PathBuf>, Error> {
+    fn prune(path: &Path, dry_run: bool, removed: &mut Vec<PathBuf>) -> Result<bool, Error> {
+        let mut empty = true;
+        for entry in list_dir(path)? {
+            let file_type = std::fs::symlink_metadata(&entry)
+                .map_err(|err| fail("read metadata of file", &entry, err))?
+                .file_type();
+            if file_type.is_dir() && prune(&entry, dry_run, removed)? {
+                if dry_run {
+                    sinfo!("Would delete empty directory `{}`.", entry.display());
+                } else {
+                    std::fs::remove_dir(&entry).map_err(|err| fail("delete directory", &entry, err))?;
+                }
+                removed.push(entry);
+            } else {
+                empty = false;
+            }
+        }
+
+        Ok(empty)
+    }
+
+    let mut removed = Vec::new();
+    prune(root.as_ref(), dry_run || is_dry_run(), &mut removed)?;
+    Ok(removed)
+}
+
+/// Options for [`write_file_with`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteOptions {
+    atomic: bool,
+    sync: bool,
+}
+
+impl WriteOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Options for critical state files: atomic and synced to disk.
+    pub fn durable() -> Self {
+        Self { atomic: true, sync: true }
+    }
+
+    /// Write to a temporary file next to the target, then rename it over the target,
+    /// so readers never observe a partially written file.
+    pub fn atomic(mut self, atomic: bool) -> Self {
+        self.atomic = atomic;
+        self
+    }
+
+    /// Flush the file and its parent directory to disk before returning,
+    /// so the write survives a crash or power loss.
+    pub fn sync(mut self, sync: bool) -> Self {
+        self.sync = sync;
+        self
+    }
+}
+
+/// Writes `contents` to a file, replacing it if it exists.
+pub fn write_file<P: AsRef<Path>, C: AsRef<[u8]>>(file_path: P, contents: C) -> Result<(), Error> {
+    write_file_with(file_path, contents, WriteOptions::default())
+}
+
+/// Writes `contents` to a file, replacing it if it exists, with the given [`WriteOptions`].
+pub fn write_file_with<P: AsRef<Path>, C: AsRef<[u8]>>(file_path: P, contents: C, options: WriteOptions) -> Result<(), Error> {
+    use std::io::Write;
+
+    let path = file_path.as_ref();
+    if skip_dry_run(|| format!("write {} to `{}`", format_size(contents.as_ref().len() as u64), path.display())) {
+        return Ok(());
+    }
+
+    let target = if options.atomic {
+        let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        path.with_file_name(format!(".{}.tmp-{}", name, std::process::id()))
+    } else {
+        path.to_path_buf()
+    };
+
+    let result = (|| {
+        let mut file = std::fs::File::create(&target)?;
+        file.write_all(contents.as_ref())?;
+        if options.sync {
+            file.sync_all()?;
+        }
+        drop(file);
+
+        if options.atomic {
+            std::fs::rename(&target, path)?;
+        }
+
+        if options.sync {
+            sync_parent_dir(path)?;
+        }
+
+        Ok(())
+    })();
+
+    result.map_err(|err: std::io::Error| {
+        if options.atomic {
+            let _ = std::fs::remove_file(&target);
+        }
+
+        fail("write file", path, err)
+    })
+}
+
+/// Flushes the directory entry of a file to disk. Windows can't open directories, so this is a no-op there.
+fn sync_parent_dir(path: &Path) -> Result<(), std::io::Error> {
+    #[cfg(unix)]
+    {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        std::fs::File::open(parent)?.sync_all()?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = path;
+
+    Ok(())
+}
+
+/// How [`copy_fast`] ended up copying a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyMethod {
+    /// The file was cloned by the filesystem, sharing its blocks with the source until either is modified.
+    Reflink,
+    /// The content was copied byte by byte.
+    Copy,
+}
+
+/// The temporary file a clone is written to before being renamed over `dst`, so `dst` is never left half-written.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+fn reflink_temp_path(dst: &Path) -> PathBuf {
+    let name = dst.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    dst.with_file_name(format!(".{}.tmp-{}", name, std::process::id()))
+}
+
+/// Attempts to clone the file at the filesystem level. Returns false when that is not supported.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn try_reflink(src: &Path, dst: &Path) -> bool {
+    use std::os::fd::AsRawFd;
+
+    let temp = reflink_temp_path(dst);
+    let Ok(source) = std::fs::File::open(src) else {
+        return false;
+    };
+    let Ok(dest) = std::fs::OpenOptions::new().write(true).create_new(true).open(&temp) else {
+        return false;
+    };
+
+    // SAFETY: both descriptors are valid for the duration of the call, as the files are still open.
+    let result = unsafe { libc::ioctl(dest.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) };
+    drop(dest);
+
+    if result != 0 || std::fs::rename(&temp, dst).is_err() {
+        let _ = std::fs::remove_file(&temp);
+        return false;
+    }
+
+    true
+}
+
+#[cfg(target_os = "macos")]
+fn try_reflink(src: &Path, dst: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    // clonefile refuses to overwrite, so clone next to the target and rename it over
+    let temp = reflink_temp_path(dst);
+    let (Ok(src), Ok(temp_c)) = (
+        std::ffi::CString::new(src.as_os_str().as_bytes()),
+        std::ffi::CString::new(temp.as_os_str().as_bytes()),
+    ) else {
+        return false;
+    };
+
+    // SAFETY: both pointers are valid, nul-terminated strings for the duration of the call.
+    if unsafe { libc::clonefile(src.as_ptr(), temp_c.as_ptr(), 0) } != 0 {
+        return false;
+    }
+
+    if std::fs::rename(&temp, dst).is_err() {
+        let _ = std::fs::remove_file(&temp);
+        return false;
+    }
+
+    true
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+fn try_reflink(_src: &Path, _dst: &Path) -> bool {
+    false
+}
+
+/// Returns true if both paths refer to the same file, including through hard links and symlinks.
+#[cfg(unix)]
+fn same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Copies a file, cloning it instead when the filesystem supports it (btrfs, XFS, APFS).
+///
+/// Falls back to a regular copy otherwise, and returns which of the two happened.
+/// Copying a file onto itself, or onto a hard link to itself, is rejected.
+pub fn copy_fast<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> Result<CopyMethod, Error> {
+    let src = src.as_ref();
+    let dst = dst.as_ref();
+    let metadata = file_info(src)?;
+
+    if same_file(src, dst) {
+        let err = std::io::Error::new(std::io::ErrorKind::InvalidInput, "source and destination are the same file");
+        return Err(fail_to("copy", src, dst, err));
+    }
+
+    if skip_dry_run(|| format!("copy `{}` to `{}`", src.display(), dst.display())) {
+        return Ok(CopyMethod::Copy);
+    }
+
+    if try_reflink(src, dst) {
+        let _ = std::fs::set_permissions(dst, metadata.permissions());
+        sdebug!("Cloned `{}` to `{}`.", src.display(), dst.display());
+        return Ok(CopyMethod::Reflink);
+    }
+
+    std::fs::copy(src, dst).map_err(|err| fail_to("copy", src, dst, err))?;
+
+    sdebug!("Copied `{}` to `{}`, as cloning is not supported.", src.display(), dst.display());
+    Ok(CopyMethod::Copy)
+}
+
+/// Reads a JSON file and deserializes it into `T`.
+pub fn read_json<T: serde::de::DeserializeOwned, P: AsRef<Path>>(file_path: P) -> Result<T, Error> {
+    let path = file_path.as_ref();
+    let contents = std::fs::read_to_string(path).map_err(|err| fail("read file", path, err))?;
+
+    serde_json::from_str(&contents).map_err(|err| {
+        fail("parse", path, std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    })
+}
+
+/// Handle to a running [`watch_config`] watcher. The watcher stops when this is dropped.
+pub struct ConfigWatcher {
+    running: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl ConfigWatcher {
+    pub fn stop(&mut self) {
+        self.running.store(false, std::sync::atomic::Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Watches a JSON config file, and calls `callback` with the freshly parsed `T` every time it changes.
+///
+/// The file is parsed once up front, and that first value is passed to `callback` too.
+/// Changes are debounced, so an editor saving in several steps triggers a single reload.
+/// A file that fails to parse is logged and skipped, keeping the last good config in place.
+pub fn watch_config<T, P, F>(file_path: P, mut callback: F) -> Result<ConfigWatcher, Error>
+where
+    T: serde::de::DeserializeOwned,
+    P: AsRef<Path>,
+    F: FnMut(T) + Send + 'static,
+{
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+    const DEBOUNCE: Duration = Duration::from_millis(200);
+
+    let path = file_path.as_ref().to_path_buf();
+    let stamp = |path: &Path| {
+        std::fs::metadata(path)
+            .ok()
+            .map(|metadata| (metadata.modified().ok(), metadata.len()))
+    };
+
+    callback(read_json(&path)?);
+
+    let running = std::sync::Arc::new(AtomicBool::new(true));
+    let thread_running = running.clone();
+    let handle = std::thread::spawn(move || {
+        let mut last = stamp(&path);
+
+        while thread_running.load(Ordering::Relaxed) {
+            std::thread::sleep(POLL_INTERVAL);
+
+            let mut current = stamp(&path);
+            if current == last || current.is_none() {
+                continue;
+            }
+
+            // Wait for the file to settle before reading it
+            loop {
+                std::thread::sleep(DEBOUNCE);
+                let settled = stamp(&path);
+                if settled == current {
+                    break;
+                }
+                current = settled;
+            }
+
+            last = current;
+            sdebug!("Config file `{}` changed, reloading.", path.display());
+            if let Ok(config) = read_json(&path) {
+                callback(config);
+            }
+        }
+    });
+
+    Ok(ConfigWatcher {
+        running,
+        handle: Some(handle),
+    })
+}
+
+/// Space on the volume holding a path, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct VolumeSpace {
+    free: u64,
+    total: u64,
+}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+fn volume_space(path: &Path) -> Result<VolumeSpace, std::io::Error> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+
+    // SAFETY: `stat` is plain old data, so zeroed memory is a valid value for statvfs to fill in.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: the path is a valid nul-terminated string, and `stat` is a valid pointer for the call.
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    // Field widths differ between platforms, hence the casts
+    let fragment = stat.f_frsize as u64;
+    Ok(VolumeSpace {
+        free: stat.f_bavail as u64 * fragment,
+        total: stat.f_blocks as u64 * fragment,
+    })
+}
+
+#[cfg(windows)]
+fn volume_space(path: &Path) -> Result<VolumeSpace, std::io::Error> {
+    use std::os::windows::ffi::OsStrExt;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetDiskFreeSpaceExW(
+            directory: *const u16,
+            free_to_caller: *mut u64,
+            total: *mut u64,
+            total_free: *mut u64,
+        ) -> i32;
+    }
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    let mut free = 0u64;
+    let mut total = 0u64;
+    let mut total_free = 0u64;
+
+    // SAFETY: the path is a valid nul-terminated wide string, and the out pointers are valid for the call.
+    if unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut free, &mut total, &mut total_free) } == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(VolumeSpace { free, total })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn volume_space(_path: &Path) -> Result<VolumeSpace, std::io::Error> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "not supported on this platform"))
+}
+
+/// Returns how many bytes are available to the current user on the volume holding `path`.
+///
+/// Useful to check for enough room before a large download or copy, instead of failing halfway through.
+pub fn free_space<P: AsRef<Path>>(path: P) -> Result<u64, Error> {
+    let path = path.as_ref();
+    volume_space(path)
+        .map(|space| space.free)
+        .map_err(|err| fail("query free space of", path, err))
+}
+
+/// Returns the total capacity in bytes of the volume holding `path`.
+pub fn total_space<P: AsRef<Path>>(path: P) -> Result<u64, Error> {
+    let path = path.as_ref();
+    volume_space(path)
+        .map(|space| space.total)
+        .map_err(|err| fail("query capacity of", path, err))
+}
+
+enum TransactionStep {
+    Write { path: PathBuf, contents: Vec<u8> },
+    Rename { from: PathBuf, to: PathBuf },
+    Delete { path: PathBuf },
+}
+
+/// How to undo a step of a [`Transaction`] that was already applied.
+enum TransactionUndo {
+    /// Delete a file that was created, then restore whatever it replaced.
+    Created { path: PathBuf, backup: Option<PathBuf> },
+    /// Move a renamed path back, then restore whatever it replaced.
+    Renamed { from: PathBuf, to: PathBuf, backup: Option<PathBuf> },
+    /// Restore a deleted path from its backup.
+    Deleted { path: PathBuf, backup: PathBuf },
+}
+
+/// A set of writes, renames and deletes that are applied all together, or not at all.
+///
+/// Nothing touches the filesystem until [`Transaction::commit`]. If any step fails, the steps
+/// applied before it are undone, and replaced or deleted files are restored from backups.
+///
+/// ```rs
+/// fox::disk::Transaction::new()
+///     .write("app/config.json", new_config)
+///     .rename("app/bin.new", "app/bin")
+///     .delete("app/old.lock")
+///     .commit()?;
+/// ```
+#[derive(Default)]
+pub struct Transaction {
+    steps: Vec<TransactionStep>,
+}
+
+impl Transaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stages writing `contents` to a file, replacing it if it exists.
+    pub fn write<P: AsRef<Path>, C: Into<Vec<u8>>>(mut self, path: P, contents: C) -> Self {
+        self.steps.push(TransactionStep::Write {
+            path: path.as_ref().to_path_buf(),
+            contents: contents.into(),
+        });
+        self
+    }
+
+    /// Stages renaming a file or directory, replacing the destination if it exists.
+    pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(mut self, from: P, to: Q) -> Self {
+        self.steps.push(TransactionStep::Rename {
+            from: from.as_ref().to_path_buf(),
+            to: to.as_ref().to_path_buf(),
+        });
+        self
+    }
+
+    /// Stages deleting a file or directory.
+    pub fn delete<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.steps.push(TransactionStep::Delete {
+            path: path.as_ref().to_path_buf(),
+        });
+        self
+    }
+
+    /// Applies every staged step in order, rolling back the applied ones if any step fails.
+    pub fn commit(self) -> Result<(), Error> {
+        if is_dry_run() {
+            for step in &self.steps {
+                match step {
+                    TransactionStep::Write { path, contents } => {
+                        sinfo!("Would write {} to `{}`.", format_size(contents.len() as u64), path.display());
+                    }
+                    TransactionStep::Rename { from, to } => {
+                        sinfo!("Would rename `{}` to `{}`.", from.display(), to.display());
+                    }
+                    TransactionStep::Delete { path } => {
+                        sinfo!("Would delete `{}`.", path.display());
+                    }
+                }
+            }
+
+            return Ok(());
+        }
+
+        let mut applied = Vec::with_capacity(self.steps.len());
+        for (i, step) in self.steps.into_iter().enumerate() {
+            match Self::apply(i, step) {
+                Ok(undo) => applied.push(undo),
+                Err(err) => {
+                    Self::rollback(applied);
+                    return Err(err);
+                }
+            }
+        }
+
+        for undo in applied {
+            let backup = match undo {
+                TransactionUndo::Created { backup, .. } | TransactionUndo::Renamed { backup, .. } => backup,
+                TransactionUndo::Deleted { backup, .. } => Some(backup),
+            };
+
+            if let Some(backup) = backup {
+                let _ = remove_any(&backup);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply(index: usize, step: TransactionStep) -> Result<TransactionUndo, Error> {
+        match step {
+            TransactionStep::Write { path, contents } => {
+                let backup = Self::backup(index, &path)?;
+                std::fs::write(&path, contents).map_err(|err| {
+                    // Put the original back ourselves, as this step won't be in the rollback list
+                    if let Some(backup) = &backup {
+                        let _ = std::fs::rename(backup, &path);
+                    }
+                    fail("write file", &path, err)
+                })?;
+
+                Ok(TransactionUndo::Created { path, backup })
+            }
+            TransactionStep::Rename { from, to } => {
+                let backup = Self::backup(index, &to)?;
+                std::fs::rename(&from, &to).map_err(|err| {
+                    if let Some(backup) = &backup {
+                        let _ = std::fs::rename(backup, &to);
+                    }
+                    fail_to("rename", &from, &to, err)
+                })?;
+
+                Ok(TransactionUndo::Renamed { from, to, backup })
+            }
+            TransactionStep::Delete { path } => match Self::backup(index, &path)? {
+                Some(backup) => Ok(TransactionUndo::Deleted { path, backup }),
+                None => {
+                    let err = std::io::Error::new(std::io::ErrorKind::NotFound, "nothing to delete");
+                    Err(fail("delete", &path, err))
+                }
+            },
+        }
+    }
+
+    /// Moves an existing path out of the way, next to itself, so it can be restored later.
+    fn backup(index: usize, path: &Path) -> Result<Option<PathBuf>, Error> {
+        if std::fs::symlink_metadata(path).is_err() {
+            return Ok(None);
+        }
+
+        let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        let backup = path.with_file_name(format!(".{}.backup-{}-{}", name, std::process::id(), index));
+        std::fs::rename(path, &backup).map_err(|err| fail_to("back up", path, &backup, err))?;
+
+        Ok(Some(backup))
+    }
+
+    fn rollback(applied: Vec<TransactionUndo>) {
+        for undo in applied.into_iter().rev() {
+            let result = match &undo {
+                TransactionUndo::Created { path, backup } => remove_any(path).and_then(|_| match backup {
+                    Some(backup) => std::fs::rename(backup, path),
+                    None => Ok(()),
+                }),
+                TransactionUndo::Renamed { from, to, backup } => std::fs::rename(to, from).and_then(|_| match backup {
+                    Some(backup) => std::fs::rename(backup, to),
+                    None => Ok(()),
+                }),
+                TransactionUndo::Deleted { path, backup } => std::fs::rename(backup, path),
+            };
+
+            if let Err(err) = result {
+                let path = match &undo {
+                    TransactionUndo::Created { path, .. } | TransactionUndo::Deleted { path, .. } => path,
+                    TransactionUndo::Renamed { to, .. } => to,
+                };
+                fail("roll back", path, err);
+            }
+        }
+    }
+}
+
+/// Removes a file, symlink, or directory tree.
+fn remove_any(path: &Path) -> Result<(), std::io::Error> {
+    if std::fs::symlink_metadata(path)?.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+/// Resolves `.` and `..` components of a path lexically, without touching the filesystem.
+///
+/// The path doesn't need to exist, and symlinks are not followed, so `a/link/..` becomes `a`.
+/// Leading `..` components of a relative path are kept, and `..` at the root is dropped.
+pub fn normalize<P: AsRef<Path>>(path: P) -> PathBuf {
+    use std::path::Component;
+
+    let mut result = PathBuf::new();
+    let mut depth = 0;
+
+    for component in path.as_ref().components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => result.push(component),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if depth > 0 {
+                    result.pop();
+                    depth -= 1;
+                } else if !result.has_root() {
+                    result.push("..");
+                }
+            }
+            Component::Normal(part) => {
+                result.push(part);
+                depth += 1;
+            }
+        }
+    }
+
+    if result.as_os_str().is_empty() {
+        result.push(".");
+    }
+
+    result
+}
+
+/// Resolves a path to its absolute form with every symlink followed. The path must exist.
+///
+/// On Windows the verbatim `\\?\` prefix is stripped, so the result displays like a regular path.
+pub fn canonical<P: AsRef<Path>>(path: P) -> Result<PathBuf, Error> {
+    let path = path.as_ref();
+    let canonical = std::fs::canonicalize(path).map_err(|err| fail("resolve", path, err))?;
+
+    Ok(strip_verbatim(canonical))
+}
+
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+    let Some(text) = path.to_str() else {
+        return path;
+    };
+
+    if let Some(rest) = text.strip_prefix(r"\\?\UNC\") {
+        PathBuf::from(format!(r"\\{}", rest))
+    } else if let Some(rest) = text.strip_prefix(r"\\?\") {
+        // Only drive paths are safe to shorten, others may rely on the verbatim form
+        let bytes = rest.as_bytes();
+        if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+            PathBuf::from(rest)
+        } else {
+            path
+        }
+    } else {
+        path
+    }
+}
+
+/// Finds the `n` largest files under a directory, recursively, largest first.
+///
+/// The tree is walked without collecting it, so memory use only depends on `n`.
+/// Symlinks are not followed. Pair the sizes with [`format_size`] for reports.
+pub fn largest_files<P: AsRef<Path>>(root: P, n: usize) -> Result<Vec<(PathBuf, u64)>, Error> {
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+
+    if n == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut largest: BinaryHeap<Reverse<(u64, PathBuf)>> = BinaryHeap::with_capacity(n + 1);
+    let mut pending = vec![root.as_ref().to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = std::fs::read_dir(&dir).map_err(|err| fail("read directory", &dir, err))?;
+        for entry in entries {
+            let entry = entry.map_err(|err| fail("read directory", &dir, err))?;
+            let path = entry.path();
+            let file_type = entry.file_type().map_err(|err| fail("read metadata of file", &path, err))?;
+
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if file_type.is_file() {
+                let size = entry.metadata().map_err(|err| fail("read metadata of file", &path, err))?.len();
+                let smallest_kept = largest.peek().map(|Reverse((size, _))| *size);
+                if largest.len() < n || smallest_kept.is_some_and(|smallest| size > smallest) {
+                    largest.push(Reverse((size, path)));
+                    if largest.len() > n {
+                        largest.pop();
+                    }
+                }
+            }
+        }
+    }
+
+    let mut result: Vec<(PathBuf, u64)> = largest
+        .into_iter()
+        .map(|Reverse((size, path))| (path, size))
+        .collect();
+    result.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    Ok(result)
+}
+
+/// The type of a file's content, as detected by [`detect_type`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentType {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+    Pdf,
+    Zip,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+    SevenZip,
+    Tar,
+    Elf,
+    MachO,
+    Pe,
+    Wasm,
+    Utf8Text,
+    Binary,
+    Empty,
+}
+
+impl ContentType {
+    /// The MIME type of the content.
+    pub fn mime(self) -> &'static str {
+        match self {
+            ContentType::Png => "image/png",
+            ContentType::Jpeg => "image/jpeg",
+            ContentType::Gif => "image/gif",
+            ContentType::Webp => "image/webp",
+            ContentType::Pdf => "application/pdf",
+            ContentType::Zip => "application/zip",
+            ContentType::Gzip => "application/gzip",
+            ContentType::Bzip2 => "application/x-bzip2",
+            ContentType::Xz => "application/x-xz",
+            ContentType::Zstd => "application/zstd",
+            ContentType::SevenZip => "application/x-7z-compressed",
+            ContentType::Tar => "application/x-tar",
+            ContentType::Elf => "application/x-elf",
+            ContentType::MachO => "application/x-mach-binary",
+            ContentType::Pe => "application/vnd.microsoft.portable-executable",
+            ContentType::Wasm => "application/wasm",
+            ContentType::Utf8Text => "text/plain; charset=utf-8",
+            ContentType::Binary | ContentType::Empty => "application/octet-stream",
+        }
+    }
+
+    /// Returns true for text content.
+    pub fn is_text(self) -> bool {
+        self == ContentType::Utf8Text
+    }
+
+    /// Sniffs the type from the first bytes of some content. 512 bytes are enough for every type.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        const SIGNATURES: [(&[u8], ContentType); 14] = [
+            (b"\x89PNG\r\n\x1a\n", ContentType::Png),
+            (b"\xff\xd8\xff", ContentType::Jpeg),
+            (b"GIF87a", ContentType::Gif),
+            (b"GIF89a", ContentType::Gif),
+            (b"%PDF-", ContentType::Pdf),
+            (b"PK\x03\x04", ContentType::Zip),
+            (b"PK\x05\x06", ContentType::Zip),
+            (b"\x1f\x8b", ContentType::Gzip),
+            (b"BZh", ContentType::Bzip2),
+            (b"\xfd7zXZ\x00", ContentType::Xz),
+            (b"\x28\xb5\x2f\xfd", ContentType::Zstd),
+            (b"7z\xbc\xaf\x27\x1c", ContentType::SevenZip),
+            (b"\x7fELF", ContentType::Elf),
+            (b"\x00asm", ContentType::Wasm),
+        ];
+
+        if bytes.is_empty() {
+            return ContentType::Empty;
+        }
+
+        if let Some((_, kind)) = SIGNATURES.iter().find(|(magic, _)| bytes.starts_with(magic)) {
+            return *kind;
+        }
+
+        if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            return ContentType::Webp;
+        }
+
+        let macho = [[0xfe, 0xed, 0xfa, 0xce], [0xfe, 0xed, 0xfa, 0xcf], [0xce, 0xfa, 0xed, 0xfe], [0xcf, 0xfa, 0xed, 0xfe]];
+        if bytes.len() >= 4 && macho.iter().any(|magic| bytes[..4] == *magic) {
+            return ContentType::MachO;
+        }
+
+        if bytes.starts_with(b"MZ") {
+            return ContentType::Pe;
+        }
+
+        if bytes.len() >= 262 && &bytes[257..262] == b"ustar" {
+            return ContentType::Tar;
+        }
+
+        // A multi-byte character may be cut off at the end of the sample
+        let text = match std::str::from_utf8(bytes) {
+            Ok(text) => Some(text),
+            Err(err) if err.error_len().is_none() => std::str::from_utf8(&bytes[..err.valid_up_to()]).ok(),
+            Err(_) => None,
+        };
+
+        match text {
+            Some(text) if !text.chars().any(|c| c.is_control() && !c.is_whitespace() && c != '\x1b') => ContentType::Utf8Text,
+            _ => ContentType::Binary,
+        }
+    }
+}
+
+impl std::fmt::Display for ContentType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ContentType::Png => "png",
+            ContentType::Jpeg => "jpeg",
+            ContentType::Gif => "gif",
+            ContentType::Webp => "webp",
+            ContentType::Pdf => "pdf",
+            ContentType::Zip => "zip",
+            ContentType::Gzip => "gzip",
+            ContentType::Bzip2 => "bzip2",
+            ContentType::Xz => "xz",
+            ContentType::Zstd => "zstd",
+            ContentType::SevenZip => "7z",
+            ContentType::Tar => "tar",
+            ContentType::Elf => "elf",
+            ContentType::MachO => "mach-o",
+            ContentType::Pe => "pe",
+            ContentType::Wasm => "wasm",
+            ContentType::Utf8Text => "utf8-text",
+            ContentType::Binary => "binary",
+            ContentType::Empty => "empty",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Detects the type of a file from its magic bytes, ignoring its extension.
+pub fn detect_type<P: AsRef<Path>>(file_path: P) -> Result<ContentType, Error> {
+    use std::io::Read;
+
+    let path = file_path.as_ref();
+    let file = std::fs::File::open(path).map_err(|err| fail("open file", path, err))?;
+
+    let mut sample = Vec::with_capacity(512);
+    file.take(512).read_to_end(&mut sample).map_err(|err| fail("read file", path, err))?;
+
+    Ok(ContentType::from_bytes(&sample))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safe_join_stays_inside_the_root() {
+        let root = Path::new("/srv/files");
+        assert_eq!(safe_join(root, "a/b.txt").unwrap(), root.join("a/b.txt"));
+        assert_eq!(safe_join(root, "./a/../b.txt").unwrap(), root.join("b.txt"));
+        assert_eq!(safe_join(root, "").unwrap(), root);
+        assert_eq!(safe_join(root, "a/..").unwrap(), root);
+    }
+
+    #[test]
+    fn safe_join_rejects_escapes() {
+        for untrusted in ["..", "../etc/passwd", "a/../../b", "a/b/../../../c"] {
+            let err = safe_join("/srv/files", untrusted).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput, "{}", untrusted);
+            assert_eq!(err.io_error().to_string(), "path escapes the root", "{}", untrusted);
+        }
+
+        let err = safe_join("/srv/files", "/etc/passwd").unwrap_err();
+        assert_eq!(err.io_error().to_string(), "path is absolute");
+    }
+
+    #[test]
+    fn transactions_leave_nothing_behind_unless_committed() {
+        let dir = std::env::temp_dir().join(format!("fox-transaction-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let (existing, created) = (dir.join("existing.txt"), dir.join("created.txt"));
+        std::fs::write(&existing, "old").unwrap();
+
+        drop(Transaction::new().write(&existing, "new").write(&created, "new"));
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "old");
+        assert!(!created.exists());
+
+        // The delete fails, so the writes before it are rolled back
+        let result = Transaction::new().write(&existing, "new").write(&created, "new").delete(dir.join("missing.txt")).commit();
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "old");
+        assert!(!created.exists());
+        assert_eq!(list_dir(&dir).unwrap(), vec![existing.clone()]);
+
+        Transaction::new().write(&existing, "new").write(&created, "new").commit().unwrap();
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "new");
+        assert_eq!(std::fs::read_to_string(&created).unwrap(), "new");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
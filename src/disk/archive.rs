@@ -0,0 +1,293 @@
+//! ZIP and tar.gz archive creation and extraction.
+
+use super::{fail, fail_to, skip_dry_run, Error};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+fn escapes_destination() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, "entry escapes the destination")
+}
+
+/// Packs the content of a directory into a ZIP archive.
+pub fn zip<P: AsRef<Path>, Q: AsRef<Path>>(dir: P, archive: Q) -> Result<(), Error> {
+    zip_with_progress(dir, archive, |_, _| {})
+}
+
+/// Packs the content of a directory into a ZIP archive.
+///
+/// `progress` is called with the amount of entries written so far and the total amount of entries.
+pub fn zip_with_progress<P, Q, F>(dir: P, archive: Q, mut progress: F) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    F: FnMut(u64, u64),
+{
+    let dir = dir.as_ref();
+    let archive = archive.as_ref();
+    let entries = super::list_dir_all_relative(dir)?;
+    let total = entries.len() as u64;
+    if skip_dry_run(|| format!("pack {} entries of `{}` into `{}`", total, dir.display(), archive.display())) {
+        return Ok(());
+    }
+
+    let file = File::create(archive).map_err(|err| fail("create archive", archive, err))?;
+
+    let mut writer = zip::ZipWriter::new(BufWriter::new(file));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    for (i, entry) in entries.iter().enumerate() {
+        let full_path = dir.join(entry);
+        // ZIP entries always use forward slashes, regardless of platform
+        let name = entry
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let result = if full_path.is_dir() {
+            writer.add_directory(name, options).map_err(std::io::Error::from)
+        } else {
+            let mut entry_options = options;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = super::file_info(&full_path)?.permissions().mode();
+                entry_options = entry_options.unix_permissions(mode);
+            }
+
+            writer.start_file(name, entry_options)
+                .map_err(std::io::Error::from)
+                .and_then(|_| {
+                    let mut source = BufReader::new(File::open(&full_path)?);
+                    std::io::copy(&mut source, &mut writer).map(|_| ())
+                })
+        };
+
+        result.map_err(|err| fail_to("add", &full_path, archive, err))?;
+
+        progress(i as u64 + 1, total);
+    }
+
+    writer.finish().map_err(|err| fail("finish archive", archive, err.into()))?;
+
+    Ok(())
+}
+
+/// Extracts a ZIP archive into a directory, creating it if needed.
+///
+/// Entries that would end up outside of `dest` are rejected, instead of being written ("zip slip").
+/// Symlinks are recreated on unix if they point inside of `dest`, and rejected otherwise.
+pub fn unzip<P: AsRef<Path>, Q: AsRef<Path>>(archive: P, dest: Q) -> Result<(), Error> {
+    unzip_with_progress(archive, dest, |_, _| {})
+}
+
+/// Extracts a ZIP archive into a directory, creating it if needed.
+///
+/// `progress` is called with the amount of entries extracted so far and the total amount of entries.
+pub fn unzip_with_progress<P, Q, F>(archive: P, dest: Q, mut progress: F) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    F: FnMut(u64, u64),
+{
+    let archive = archive.as_ref();
+    let dest = dest.as_ref();
+
+    let file = File::open(archive).map_err(|err| fail("open archive", archive, err))?;
+    let mut reader = zip::ZipArchive::new(BufReader::new(file))
+        .map_err(|err| fail("read archive", archive, err.into()))?;
+    if skip_dry_run(|| format!("extract {} entries of `{}` into `{}`", reader.len(), archive.display(), dest.display())) {
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(dest).map_err(|err| fail("create directory", dest, err))?;
+
+    let total = reader.len() as u64;
+    for i in 0..reader.len() {
+        let mut entry = reader.by_index(i).map_err(|err| fail("read archive", archive, err.into()))?;
+        let name = String::from_utf8_lossy(entry.name_raw()).into_owned();
+
+        let Some(relative) = entry.enclosed_name() else {
+            return Err(fail_to("extract", name, dest, escapes_destination()));
+        };
+
+        let out_path = dest.join(relative);
+        if entry.is_symlink() {
+            let mut target = String::new();
+            std::io::Read::read_to_string(&mut entry, &mut target)
+                .and_then(|_| extract_symlink(dest, &out_path, Path::new(&target)))
+                .map_err(|err| fail_to("extract", name, &out_path, err))?;
+
+            progress(i as u64 + 1, total);
+            continue;
+        }
+
+        let result = if entry.is_dir() {
+            std::fs::create_dir_all(&out_path)
+        } else {
+            let parent_result = match out_path.parent() {
+                Some(parent) => std::fs::create_dir_all(parent),
+                None => Ok(()),
+            };
+
+            parent_result
+                .and_then(|_| File::create(&out_path))
+                .and_then(|out| std::io::copy(&mut entry, &mut BufWriter::new(out)).map(|_| ()))
+        };
+
+        result.map_err(|err| fail_to("extract", name, &out_path, err))?;
+
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = std::fs::set_permissions(&out_path, std::fs::Permissions::from_mode(mode));
+        }
+
+        progress(i as u64 + 1, total);
+    }
+
+    Ok(())
+}
+
+/// Creates the symlink `link` pointing to `target`, if it resolves to somewhere inside of `dest`.
+#[cfg(unix)]
+fn extract_symlink(dest: &Path, link: &Path, target: &Path) -> std::io::Result<()> {
+    use std::path::Component;
+
+    // Only leading `..` components are allowed, since `..` after a symlink would climb from wherever it points
+    let mut climbing = true;
+    for component in target.components() {
+        match component {
+            Component::ParentDir if climbing => {}
+            Component::Normal(_) | Component::CurDir => climbing = false,
+            _ => return Err(escapes_destination()),
+        }
+    }
+
+    let parent = link.parent().unwrap_or(dest);
+    std::fs::create_dir_all(parent)?;
+
+    // Every symlink extracted so far points inside of `dest`, so resolving the parent is enough
+    let resolved = super::normalize(std::fs::canonicalize(parent)?.join(target));
+    if !resolved.starts_with(std::fs::canonicalize(dest)?) {
+        return Err(escapes_destination());
+    }
+
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn extract_symlink(_dest: &Path, _link: &Path, _target: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "symlinks can't be extracted on this platform"))
+}
+
+/// Packs the content of a directory into a gzip compressed tarball.
+pub fn tar_gz<P: AsRef<Path>, Q: AsRef<Path>>(dir: P, archive: Q) -> Result<(), Error> {
+    tar_gz_with_progress(dir, archive, |_, _| {})
+}
+
+/// Packs the content of a directory into a gzip compressed tarball.
+///
+/// `progress` is called with the amount of entries written so far and the total amount of entries.
+pub fn tar_gz_with_progress<P, Q, F>(dir: P, archive: Q, mut progress: F) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    F: FnMut(u64, u64),
+{
+    let dir = dir.as_ref();
+    let archive = archive.as_ref();
+    let entries = super::list_dir_all_relative(dir)?;
+    let total = entries.len() as u64;
+    if skip_dry_run(|| format!("pack {} entries of `{}` into `{}`", total, dir.display(), archive.display())) {
+        return Ok(());
+    }
+
+    let file = File::create(archive).map_err(|err| fail("create archive", archive, err))?;
+
+    let encoder = flate2::write::GzEncoder::new(BufWriter::new(file), flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    builder.follow_symlinks(false);
+
+    for (i, entry) in entries.iter().enumerate() {
+        let full_path = dir.join(entry);
+        let result = if full_path.is_dir() && !full_path.is_symlink() {
+            builder.append_dir(entry, &full_path)
+        } else {
+            builder.append_path_with_name(&full_path, entry)
+        };
+
+        result.map_err(|err| fail_to("add", &full_path, archive, err))?;
+
+        progress(i as u64 + 1, total);
+    }
+
+    builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .map_err(|err| fail("finish archive", archive, err))?;
+
+    Ok(())
+}
+
+/// Extracts a gzip compressed tarball into a directory, creating it if needed.
+///
+/// Entries that would end up outside of `dest` are rejected, instead of being written.
+pub fn untar_gz<P: AsRef<Path>, Q: AsRef<Path>>(archive: P, dest: Q) -> Result<(), Error> {
+    untar_gz_with_progress(archive, dest, |_, _| {})
+}
+
+/// Extracts a gzip compressed tarball into a directory, creating it if needed.
+///
+/// Tarballs don't know their entry count up front, so `progress` is called with the amount
+/// of compressed bytes read so far and the size of the archive.
+pub fn untar_gz_with_progress<P, Q, F>(archive: P, dest: Q, mut progress: F) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    F: FnMut(u64, u64),
+{
+    let archive = archive.as_ref();
+    let dest = dest.as_ref();
+
+    let file = File::open(archive).map_err(|err| fail("open archive", archive, err))?;
+    let total = super::file_info(archive)?.len();
+    let read = std::rc::Rc::new(std::cell::Cell::new(0u64));
+    let counter = CountingReader { inner: BufReader::new(file), read: read.clone() };
+    let mut reader = tar::Archive::new(flate2::read::GzDecoder::new(counter));
+    if skip_dry_run(|| format!("extract `{}` into `{}`", archive.display(), dest.display())) {
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(dest).map_err(|err| fail("create directory", dest, err))?;
+
+    let entries = reader.entries().map_err(|err| fail("read archive", archive, err))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|err| fail("read archive", archive, err))?;
+
+        let name = entry.path().map(|p| p.display().to_string()).unwrap_or_default();
+        let unpacked = entry.unpack_in(dest).map_err(|err| fail_to("extract", &name, dest, err))?;
+        if !unpacked {
+            return Err(fail_to("extract", name, dest, escapes_destination()));
+        }
+
+        progress(read.get(), total);
+    }
+
+    Ok(())
+}
+
+struct CountingReader<R> {
+    inner: R,
+    read: std::rc::Rc<std::cell::Cell<u64>>,
+}
+
+impl<R: std::io::Read> std::io::Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read.set(self.read.get() + n as u64);
+        Ok(n)
+    }
+}
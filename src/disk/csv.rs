@@ -0,0 +1,67 @@
+//! Serde-based CSV reading and writing.
+
+use super::{fail, skip_dry_run, Error};
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::Path;
+
+/// Describes where in the file a CSV error happened, using the header names when known.
+fn describe_error(err: &::csv::Error, headers: Option<&::csv::StringRecord>) -> String {
+    match err.kind() {
+        ::csv::ErrorKind::Deserialize { pos, err: inner } => {
+            let column = inner.field().map(|field| {
+                headers
+                    .and_then(|headers| headers.get(field as usize))
+                    .map(|name| format!("column `{}`", name))
+                    .unwrap_or_else(|| format!("column {}", field + 1))
+            });
+
+            match (pos, column) {
+                (Some(pos), Some(column)) => format!("line {}, {}: {}", pos.line(), column, inner.kind()),
+                (Some(pos), None) => format!("line {}: {}", pos.line(), inner.kind()),
+                (None, Some(column)) => format!("{}: {}", column, inner.kind()),
+                (None, None) => inner.kind().to_string(),
+            }
+        }
+        _ => match err.position() {
+            Some(pos) => format!("line {}: {}", pos.line(), err),
+            None => err.to_string(),
+        },
+    }
+}
+
+/// Reads every row of a CSV file with a header line, deserializing each into `T`.
+pub fn read_csv<T: DeserializeOwned, P: AsRef<Path>>(file_path: P) -> Result<Vec<T>, Error> {
+    let path = file_path.as_ref();
+    let mut reader = ::csv::Reader::from_path(path).map_err(|err| fail("open CSV file", path, err.into()))?;
+
+    let headers = reader.headers().ok().cloned();
+    let mut rows = Vec::new();
+    for row in reader.deserialize() {
+        let row = row.map_err(|err| {
+            let description = describe_error(&err, headers.as_ref());
+            fail("read CSV file", path, std::io::Error::new(std::io::ErrorKind::InvalidData, description))
+        })?;
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+/// Writes rows to a CSV file, with a header line derived from the fields of `T`.
+pub fn write_csv<T: Serialize, P: AsRef<Path>>(file_path: P, rows: &[T]) -> Result<(), Error> {
+    let path = file_path.as_ref();
+    if skip_dry_run(|| format!("write {} rows to `{}`", rows.len(), path.display())) {
+        return Ok(());
+    }
+
+    let mut writer = ::csv::Writer::from_path(path).map_err(|err| fail("create CSV file", path, err.into()))?;
+
+    for (i, row) in rows.iter().enumerate() {
+        writer.serialize(row).map_err(|err| {
+            let description = format!("row {}: {}", i + 1, err);
+            fail("write CSV file", path, std::io::Error::new(std::io::ErrorKind::InvalidData, description))
+        })?;
+    }
+
+    writer.flush().map_err(|err| fail("write CSV file", path, err))
+}
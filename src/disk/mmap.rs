@@ -0,0 +1,46 @@
+//! Read-only memory-mapped file access.
+
+use super::{fail, Error};
+use std::path::Path;
+
+/// A read-only view of a file mapped into memory.
+///
+/// Dereferences to `[u8]`, so it can be sliced and searched like a regular buffer.
+pub struct MappedFile {
+    map: memmap2::Mmap,
+}
+
+impl MappedFile {
+    /// Returns the mapped bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.map
+    }
+}
+
+impl std::ops::Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.map
+    }
+}
+
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] {
+        &self.map
+    }
+}
+
+/// Maps a file into memory, read-only.
+///
+/// The mapping reflects the file on disk, so it must not be truncated by another process while mapped.
+pub fn mmap<P: AsRef<Path>>(file_path: P) -> Result<MappedFile, Error> {
+    let path = file_path.as_ref();
+    let file = std::fs::File::open(path).map_err(|err| fail("open file", path, err))?;
+
+    // SAFETY: the map is read-only, and we never hand out anything but shared slices of it.
+    // Concurrent truncation of the file is the caller's responsibility, as documented above.
+    let map = unsafe { memmap2::Mmap::map(&file) }.map_err(|err| fail("map", path, err))?;
+
+    Ok(MappedFile { map })
+}
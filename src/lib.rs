@@ -4,6 +4,11 @@ pub mod log;
 pub mod disk;
 pub mod snips;
 pub mod discord;
+pub mod net;
 
-#[deprecated = "use clap instead"]
+// The test harness refers to every test, which would trip the lint in the crate's own tests
+#[cfg_attr(not(test), deprecated = "use clap instead")]
 pub mod cli;
+
+#[cfg(feature = "derive")]
+pub use fox_derive::FoxArgs;
@@ -229,6 +229,7 @@ pub fn set_logging_level(level: LogLevel) {
     LEVEL.store(level.as_u8(), Ordering::SeqCst);
 }
 
+#[allow(clippy::result_unit_err)]
 pub fn set_logging_level_from_str(level: &str) -> Result<(), ()> {
     let level: LogLevel = level.parse()?;
     set_logging_level(level);
@@ -260,9 +261,9 @@ pub fn get_caller_info() -> String {
         buffer.clear();
 
         let short_file = file
-            .split('/')
-            .last()
-            .or_else(|| file.split('\\').last())
+            .rsplit('/')
+            .next()
+            .or_else(|| file.rsplit('\\').next())
             .unwrap_or(file);
 
         use std::fmt::Write;
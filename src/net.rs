@@ -0,0 +1,717 @@
+//! Small networking helpers: HTTP requests, URLs, TCP and UDP, DNS, and reachability checks.
+
+use crate::{sdebug, serror, sinfo, swarn};
+use crate as fox;
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "async")]
+pub mod r#async;
+pub mod tcp;
+pub mod test;
+pub mod udp;
+
+mod cache;
+pub use cache::*;
+
+mod cert;
+pub use cert::*;
+
+mod client;
+pub use client::*;
+
+mod cookies;
+
+mod graphql;
+pub use graphql::*;
+
+mod http;
+pub use http::*;
+
+mod download;
+pub use download::*;
+
+mod mdns;
+pub use mdns::*;
+
+mod multipart;
+pub use multipart::*;
+
+mod paginate;
+pub use paginate::*;
+
+mod parallel;
+pub use parallel::*;
+
+mod proxy;
+
+mod rate_limit;
+pub use rate_limit::*;
+
+mod response;
+pub use response::*;
+
+mod rest;
+pub use rest::*;
+
+mod status;
+pub use status::*;
+
+mod tls;
+pub use tls::*;
+
+mod transport;
+pub use transport::*;
+
+/// An error returned when parsing a [`Url`] fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlError {
+    input: String,
+    reason: String,
+}
+
+impl UrlError {
+    fn new<R: ToString>(input: &str, reason: R) -> Self {
+        UrlError { input: input.to_string(), reason: reason.to_string() }
+    }
+
+    /// The string that failed to parse.
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+}
+
+impl std::fmt::Display for UrlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid URL `{}`: {}", self.input, self.reason)
+    }
+}
+
+impl std::error::Error for UrlError {}
+
+/// A parsed URL that can be extended with path segments and query parameters, built on
+/// [url](https://docs.rs/url).
+///
+/// ```rs
+/// let url = Url::parse("https://api.example.com/v1")?
+///     .join("users")
+///     .join("42")
+///     .query("fields", "name,email");
+///
+/// assert_eq!(url.to_string(), "https://api.example.com/v1/users/42?fields=name%2Cemail");
+/// assert_eq!(url.port(), Some(443));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Url(url::Url);
+
+impl Url {
+    /// Parses an absolute URL such as `https://user@host:8080/path?key=value#section`.
+    pub fn parse(input: &str) -> Result<Url, UrlError> {
+        let url = url::Url::parse(input.trim()).map_err(|err| UrlError::new(input, err))?;
+        if url.host_str().is_none_or(|host| host.is_empty()) {
+            return Err(UrlError::new(input, "missing host"));
+        }
+        Ok(Url(url))
+    }
+
+    /// The URL as a string, percent-encoded where needed.
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// The scheme, lowercased, e.g. `https`.
+    pub fn scheme(&self) -> &str {
+        self.0.scheme()
+    }
+
+    /// The host without brackets, lowercased, e.g. `example.com` or `::1`.
+    pub fn host(&self) -> &str {
+        let host = self.0.host_str().unwrap_or_default();
+        host.strip_prefix('[').and_then(|host| host.strip_suffix(']')).unwrap_or(host)
+    }
+
+    /// The explicit port, or the default port for well-known schemes.
+    pub fn port(&self) -> Option<u16> {
+        self.0.port_or_known_default()
+    }
+
+    /// The `user[:password]` part before the host, if any.
+    pub fn userinfo(&self) -> Option<&str> {
+        Some(&self.0[url::Position::BeforeUsername..url::Position::AfterPassword]).filter(|userinfo| !userinfo.is_empty())
+    }
+
+    /// The path, as it appears in the URL. Always starts with `/`.
+    pub fn path(&self) -> &str {
+        self.0.path()
+    }
+
+    /// The path followed by the query string, as sent in an HTTP request line.
+    pub fn path_and_query(&self) -> &str {
+        &self.0[url::Position::BeforePath..url::Position::AfterQuery]
+    }
+
+    /// The decoded query parameters, in order.
+    pub fn query_pairs(&self) -> Vec<(String, String)> {
+        self.0.query_pairs().map(|(key, value)| (key.into_owned(), value.into_owned())).collect()
+    }
+
+    /// Returns the decoded value of the first query parameter named `key`.
+    pub fn query_value(&self, key: &str) -> Option<String> {
+        self.0.query_pairs().find(|(k, _)| k == key).map(|(_, value)| value.into_owned())
+    }
+
+    /// The fragment after `#`, as it appears in the URL.
+    pub fn fragment(&self) -> Option<&str> {
+        self.0.fragment()
+    }
+
+    /// The `host:port` pair to connect to, with IPv6 hosts in brackets.
+    pub fn host_port(&self) -> Option<String> {
+        self.port().map(|port| format!("{}:{}", self.0.host_str().unwrap_or_default(), port))
+    }
+
+    /// Returns true if both URLs have the same scheme, host and port.
+    pub fn same_origin(&self, other: &Url) -> bool {
+        self.0.origin() == other.0.origin()
+    }
+
+    /// Resolves `reference` against this URL, the way a browser follows a link or a `Location` header.
+    pub fn resolve(&self, reference: &str) -> Result<Url, UrlError> {
+        self.0.join(reference).map(Url).map_err(|err| UrlError::new(reference, err))
+    }
+
+    /// Appends a path segment, percent-encoding it. Slashes inside the segment separate further segments.
+    pub fn join<T: AsRef<str>>(mut self, segment: T) -> Self {
+        let segment = segment.as_ref().trim_matches('/');
+        if segment.is_empty() {
+            return self;
+        }
+
+        if let Ok(mut segments) = self.0.path_segments_mut() {
+            segments.pop_if_empty().extend(segment.split('/'));
+        }
+        self
+    }
+
+    /// Appends a query parameter, encoding the key and value.
+    pub fn query<K: AsRef<str>, V: ToString>(mut self, key: K, value: V) -> Self {
+        self.0.query_pairs_mut().append_pair(key.as_ref(), &value.to_string());
+        self
+    }
+
+    /// Replaces the query parameters named `key` with one set to `value`, keeping the others in order.
+    pub fn set_query<K: AsRef<str>, V: ToString>(mut self, key: K, value: V) -> Self {
+        let key = key.as_ref();
+        let others: Vec<(String, String)> = self.query_pairs().into_iter().filter(|(k, _)| k != key).collect();
+        self.0.query_pairs_mut().clear().extend_pairs(others).append_pair(key, &value.to_string());
+        self
+    }
+
+    /// Sets the fragment after `#`, percent-encoding it where needed.
+    pub fn with_fragment<T: AsRef<str>>(mut self, fragment: T) -> Self {
+        self.0.set_fragment(Some(fragment.as_ref()));
+        self
+    }
+}
+
+impl std::str::FromStr for Url {
+    type Err = UrlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Url::parse(s)
+    }
+}
+
+impl std::fmt::Display for Url {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl AsRef<str> for Url {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+/// Everything but unreserved characters (`A-Z a-z 0-9 - . _ ~`), which never need encoding.
+const RESERVED: &percent_encoding::AsciiSet = &percent_encoding::NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+/// Percent-encodes everything except unreserved characters (`A-Z a-z 0-9 - . _ ~`).
+pub fn encode(text: &str) -> String {
+    percent_encoding::utf8_percent_encode(text, RESERVED).to_string()
+}
+
+/// Decodes `%XX` escapes. Invalid escapes are kept as-is and invalid UTF-8 is replaced.
+pub fn decode(text: &str) -> String {
+    percent_encoding::percent_decode_str(text).decode_utf8_lossy().into_owned()
+}
+
+/// Splits `host`, `host:port`, `[v6]:port` or a full URL into a host and an optional port.
+fn split_host_port(target: &str) -> (String, Option<u16>) {
+    if target.contains("://") {
+        if let Ok(url) = Url::parse(target) {
+            return (url.host().to_string(), url.port());
+        }
+    }
+
+    if let Some(bracketed) = target.strip_prefix('[') {
+        if let Some((host, after)) = bracketed.split_once(']') {
+            return (host.to_string(), after.strip_prefix(':').and_then(|port| port.parse().ok()));
+        }
+    }
+
+    match target.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => match port.parse() {
+            Ok(port) => (host.to_string(), Some(port)),
+            Err(_) => (target.to_string(), None),
+        },
+        _ => (target.to_string(), None),
+    }
+}
+
+fn resolve_addrs(host: &str, port: u16) -> Vec<SocketAddr> {
+    match (host, port).to_socket_addrs() {
+        Ok(addrs) => addrs.collect(),
+        Err(err) => {
+            sdebug!("Could not resolve `{}`: {}", host, err);
+            Vec::new()
+        }
+    }
+}
+
+/// Probes a host by opening a TCP connection, returning the connect latency if it answered in time.
+///
+/// The target can be `host`, `host:port` or a URL. Without a port, 443 and then 80 are tried.
+/// A refused connection still counts as reachable, since the host had to answer to refuse it.
+///
+/// ```rs
+/// match net::is_reachable("example.com", Duration::from_secs(2)) {
+///     Some(latency) => info!("Up ({}ms)", latency.as_millis()),
+///     None => warn!("Down"),
+/// }
+/// ```
+pub fn is_reachable<H: AsRef<str>>(host: H, timeout: Duration) -> Option<Duration> {
+    let (host, port) = split_host_port(host.as_ref());
+    let ports = match port {
+        Some(port) => vec![port],
+        None => vec![443, 80],
+    };
+
+    let deadline = Instant::now() + timeout;
+    let addrs = resolve_addrs(&host, 0);
+
+    for port in ports {
+        for mut addr in addrs.iter().copied() {
+            addr.set_port(port);
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return None;
+            }
+
+            let start = Instant::now();
+            match TcpStream::connect_timeout(&addr, remaining) {
+                Ok(_) => return Some(start.elapsed()),
+                Err(err) if err.kind() == std::io::ErrorKind::ConnectionRefused => return Some(start.elapsed()),
+                Err(err) => sdebug!("No answer from {}: {}", addr, err),
+            }
+        }
+    }
+
+    None
+}
+
+/// Returns true if something accepts TCP connections on `host:port` within `timeout`.
+pub fn port_open<H: AsRef<str>>(host: H, port: u16, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+
+    for addr in resolve_addrs(host.as_ref(), port) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+
+        if TcpStream::connect_timeout(&addr, remaining).is_ok() {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Polls `host:port` until it accepts connections, giving up after `timeout`. Returns true once it's open.
+///
+/// ```rs
+/// if !net::wait_for_port("localhost", 5432, Duration::from_secs(30)) {
+///     critical!("Database never came up");
+/// }
+/// ```
+pub fn wait_for_port<H: AsRef<str>>(host: H, port: u16, timeout: Duration) -> bool {
+    let host = host.as_ref();
+    let start = Instant::now();
+    let deadline = start + timeout;
+    let mut attempt = 1;
+
+    sinfo!("Waiting for {}:{} to accept connections...", host, port);
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if port_open(host, port, remaining.min(Duration::from_secs(1))) {
+            sinfo!("{}:{} is up after {:.1}s.", host, port, start.elapsed().as_secs_f64());
+            return true;
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            swarn!("Gave up waiting for {}:{} after {} attempts.", host, port, attempt);
+            return false;
+        }
+
+        sdebug!("{}:{} is not open yet (attempt {}).", host, port, attempt);
+        attempt += 1;
+        std::thread::sleep(remaining.min(Duration::from_millis(250)));
+    }
+}
+
+/// Resolves a hostname to all of its IPv4 and IPv6 addresses, without duplicates.
+///
+/// IP address literals resolve to themselves.
+pub fn resolve<H: AsRef<str>>(host: H) -> Result<Vec<IpAddr>, std::io::Error> {
+    let host = host.as_ref();
+
+    match (host, 0).to_socket_addrs() {
+        Ok(addrs) => {
+            let mut ips: Vec<IpAddr> = Vec::new();
+            for addr in addrs {
+                if !ips.contains(&addr.ip()) {
+                    ips.push(addr.ip());
+                }
+            }
+            Ok(ips)
+        }
+        Err(err) => {
+            serror!("Failed to resolve `{}`: {}", host, err);
+            Err(err)
+        }
+    }
+}
+
+/// Looks up the hostname an IP address points back to, using the system resolver.
+pub fn reverse_lookup(ip: IpAddr) -> Result<String, std::io::Error> {
+    name_info(ip).inspect_err(|err| serror!("Failed to reverse lookup `{}`: {}", ip, err))
+}
+
+#[cfg(unix)]
+fn name_info(ip: IpAddr) -> Result<String, std::io::Error> {
+    // SAFETY: sockaddr_storage is plain old data, so zeroed memory is a valid value.
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+
+    let len = match ip {
+        IpAddr::V4(v4) => {
+            // SAFETY: sockaddr_storage is large enough and suitably aligned for any socket address.
+            let addr = unsafe { &mut *(&mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in) };
+            addr.sin_family = libc::AF_INET as libc::sa_family_t;
+            addr.sin_addr.s_addr = u32::from_ne_bytes(v4.octets());
+            std::mem::size_of::<libc::sockaddr_in>()
+        }
+        IpAddr::V6(v6) => {
+            // SAFETY: as above.
+            let addr = unsafe { &mut *(&mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in6) };
+            addr.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            addr.sin6_addr.s6_addr = v6.octets();
+            std::mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "dragonfly"))]
+    {
+        storage.ss_len = len as u8;
+    }
+
+    let mut host = [0 as libc::c_char; 1025];
+
+    // SAFETY: the address is initialized for `len` bytes and `host` is a writable buffer of the given size.
+    let result = unsafe {
+        libc::getnameinfo(
+            &storage as *const libc::sockaddr_storage as *const libc::sockaddr,
+            len as libc::socklen_t,
+            host.as_mut_ptr(),
+            host.len() as libc::socklen_t,
+            std::ptr::null_mut(),
+            0,
+            libc::NI_NAMEREQD,
+        )
+    };
+
+    if result != 0 {
+        // SAFETY: gai_strerror returns a pointer to a static nul-terminated message.
+        let message = unsafe { std::ffi::CStr::from_ptr(libc::gai_strerror(result)) };
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, message.to_string_lossy()));
+    }
+
+    // SAFETY: getnameinfo nul-terminates the name on success.
+    let name = unsafe { std::ffi::CStr::from_ptr(host.as_ptr()) };
+    Ok(name.to_string_lossy().into_owned())
+}
+
+#[cfg(windows)]
+fn name_info(ip: IpAddr) -> Result<String, std::io::Error> {
+    #[repr(C)]
+    struct SockaddrIn {
+        family: u16,
+        port: u16,
+        addr: [u8; 4],
+        zero: [u8; 8],
+    }
+
+    #[repr(C)]
+    struct SockaddrIn6 {
+        family: u16,
+        port: u16,
+        flowinfo: u32,
+        addr: [u8; 16],
+        scope_id: u32,
+    }
+
+    #[link(name = "ws2_32")]
+    extern "system" {
+        fn getnameinfo(addr: *const u8, addr_len: i32, host: *mut u8, host_len: u32, serv: *mut u8, serv_len: u32, flags: i32) -> i32;
+    }
+
+    const NI_NAMEREQD: i32 = 0x04;
+
+    // Winsock has to be initialized first, which the standard library does on its first lookup
+    let _ = ("localhost", 0).to_socket_addrs();
+
+    let v4;
+    let v6;
+    let (addr, len) = match ip {
+        IpAddr::V4(ip) => {
+            v4 = SockaddrIn { family: 2, port: 0, addr: ip.octets(), zero: [0; 8] };
+            (&v4 as *const SockaddrIn as *const u8, std::mem::size_of::<SockaddrIn>())
+        }
+        IpAddr::V6(ip) => {
+            v6 = SockaddrIn6 { family: 23, port: 0, flowinfo: 0, addr: ip.octets(), scope_id: 0 };
+            (&v6 as *const SockaddrIn6 as *const u8, std::mem::size_of::<SockaddrIn6>())
+        }
+    };
+
+    let mut host = [0u8; 1025];
+
+    // SAFETY: the address is valid for `len` bytes and `host` is a writable buffer of the given size.
+    let result = unsafe { getnameinfo(addr, len as i32, host.as_mut_ptr(), host.len() as u32, std::ptr::null_mut(), 0, NI_NAMEREQD) };
+    if result != 0 {
+        return Err(std::io::Error::from_raw_os_error(result));
+    }
+
+    let end = host.iter().position(|&b| b == 0).unwrap_or(host.len());
+    Ok(String::from_utf8_lossy(&host[..end]).into_owned())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn name_info(_ip: IpAddr) -> Result<String, std::io::Error> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "not supported on this platform"))
+}
+
+/// Returns the address of the interface used for outbound traffic.
+///
+/// No packets are sent: a UDP socket is connected to a public address and asked which local address it picked.
+pub fn local_ip() -> Result<IpAddr, std::io::Error> {
+    let probe = |bind: &str, target: &str| -> Result<IpAddr, std::io::Error> {
+        let socket = UdpSocket::bind(bind)?;
+        socket.connect(target)?;
+        Ok(socket.local_addr()?.ip())
+    };
+
+    probe("0.0.0.0:0", "8.8.8.8:80")
+        .or_else(|_| probe("[::]:0", "[2001:4860:4860::8888]:80"))
+        .inspect_err(|err| serror!("Failed to determine local IP: {}", err))
+}
+
+/// The services [`public_ip`] asks, in order. Each one answers with the caller's IP as text.
+pub const PUBLIC_IP_SERVICES: [&str; 3] = [
+    "https://api.ipify.org",
+    "https://icanhazip.com",
+    "https://ifconfig.me/ip",
+];
+
+/// Returns the public IP this machine's traffic appears to come from, asking [`PUBLIC_IP_SERVICES`].
+pub fn public_ip() -> Result<IpAddr, std::io::Error> {
+    public_ip_from(&PUBLIC_IP_SERVICES)
+}
+
+/// Returns the public IP as reported by the first of `services` that answers with a valid address.
+///
+/// Services are URLs that respond with the IP as plain text.
+pub fn public_ip_from<S: AsRef<str>>(services: &[S]) -> Result<IpAddr, std::io::Error> {
+    let mut last_error = std::io::Error::new(std::io::ErrorKind::InvalidInput, "no services given");
+
+    for service in services {
+        let service = service.as_ref();
+        let body = request(Method::Get, service)
+            .timeout(Duration::from_secs(5))
+            .send()
+            .and_then(|response| response.text())
+            .map_err(std::io::Error::from);
+
+        match body.and_then(|body| {
+            body.trim().parse::<IpAddr>().map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "response is not an IP address"))
+        }) {
+            Ok(ip) => return Ok(ip),
+            Err(err) => {
+                sdebug!("Public IP service {} failed: {}", service, err);
+                last_error = err;
+            }
+        }
+    }
+
+    serror!("Failed to determine public IP: {}", last_error);
+    Err(last_error)
+}
+
+/// The result of a [`speedtest`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpeedTest {
+    /// The average time from connecting to receiving the response headers.
+    pub latency: Duration,
+    /// How many body bytes were downloaded.
+    pub bytes: u64,
+    /// How long the downloads took.
+    pub elapsed: Duration,
+    /// How many requests were made.
+    pub requests: u32,
+}
+
+impl SpeedTest {
+    /// The download throughput in bytes per second.
+    pub fn bytes_per_second(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            secs if secs > 0.0 => self.bytes as f64 / secs,
+            _ => 0.0,
+        }
+    }
+
+    /// The download throughput in megabits per second.
+    pub fn megabits_per_second(&self) -> f64 {
+        self.bytes_per_second() * 8.0 / 1_000_000.0
+    }
+}
+
+impl std::fmt::Display for SpeedTest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.2} Mbit/s ({} in {:.1}s), {}ms latency",
+            self.megabits_per_second(),
+            crate::disk::format_size(self.bytes),
+            self.elapsed.as_secs_f64(),
+            self.latency.as_millis(),
+        )
+    }
+}
+
+/// Measures download throughput and request latency against `url` by downloading it repeatedly for about `duration`.
+///
+/// Point it at a large file for meaningful throughput numbers.
+///
+/// ```rs
+/// let result = net::speedtest("http://speed.example.com/100MB.bin", Duration::from_secs(10))?;
+/// info!("{}", result);
+/// ```
+pub fn speedtest<U: AsRef<str>>(url: U, duration: Duration) -> Result<SpeedTest, std::io::Error> {
+    use std::io::Read;
+
+    let url = url.as_ref();
+    let start = Instant::now();
+    let deadline = start + duration;
+    let mut result = SpeedTest { latency: Duration::ZERO, bytes: 0, elapsed: Duration::ZERO, requests: 0 };
+    let mut total_latency = Duration::ZERO;
+    let mut chunk = vec![0u8; 64 * 1024];
+
+    while result.requests == 0 || Instant::now() < deadline {
+        let request_start = Instant::now();
+        // Compression would measure how well the file compresses rather than the connection
+        let response = request(Method::Get, url)
+            .header("Accept-Encoding", "identity")
+            .timeout(duration + Duration::from_secs(10))
+            .send()
+            .inspect_err(|err| serror!("Speed test against {} failed: {}", url, err))?;
+        total_latency += request_start.elapsed();
+        result.requests += 1;
+
+        let mut body = response.into_reader();
+        while Instant::now() < deadline {
+            match body.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(read) => result.bytes += read as u64,
+                Err(err) => {
+                    serror!("Speed test against {} failed: {}", url, err);
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    result.elapsed = start.elapsed();
+    result.latency = total_latency / result.requests;
+    sdebug!("Speed test against {}: {}", url, result);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urls_build_with_encoded_segments_and_queries() {
+        let url = Url::parse("https://api.example.com/v1/").unwrap().join("users/42").join("a b").query("fields", "name,email");
+        assert_eq!(url.as_str(), "https://api.example.com/v1/users/42/a%20b?fields=name%2Cemail");
+        assert_eq!(url.path_and_query(), "/v1/users/42/a%20b?fields=name%2Cemail");
+        assert_eq!(url.query_value("fields").as_deref(), Some("name,email"));
+        assert_eq!(url.port(), Some(443));
+
+        let url = url.set_query("fields", "id").query("page", 2);
+        assert_eq!(url.query_pairs(), vec![("fields".to_string(), "id".to_string()), ("page".to_string(), "2".to_string())]);
+    }
+
+    #[test]
+    fn urls_keep_fragments_as_written() {
+        let url = Url::parse("http://user:pw@example.com:8080/docs#a%2Fb").unwrap();
+        assert_eq!(url.fragment(), Some("a%2Fb"));
+        assert_eq!(url.to_string(), "http://user:pw@example.com:8080/docs#a%2Fb");
+        assert_eq!(url.userinfo(), Some("user:pw"));
+        assert_eq!(url.host_port().as_deref(), Some("example.com:8080"));
+    }
+
+    #[test]
+    fn urls_handle_ipv6_hosts() {
+        let url = Url::parse("http://[::1]:3000/").unwrap();
+        assert_eq!(url.host(), "::1");
+        assert_eq!(url.host_port().as_deref(), Some("[::1]:3000"));
+        assert_eq!(split_host_port("http://[::1]:3000/"), ("::1".to_string(), Some(3000)));
+    }
+
+    #[test]
+    fn urls_resolve_references() {
+        let url = Url::parse("https://example.com/a/b?page=1").unwrap();
+        assert_eq!(url.resolve("c").unwrap().as_str(), "https://example.com/a/c");
+        assert_eq!(url.resolve("/d?page=2").unwrap().as_str(), "https://example.com/d?page=2");
+        assert!(url.same_origin(&url.resolve("//example.com/e").unwrap()));
+        assert!(!url.same_origin(&url.resolve("http://example.com/").unwrap()));
+    }
+
+    #[test]
+    fn urls_without_a_host_are_rejected() {
+        let err = Url::parse("mailto:someone@example.com").unwrap_err();
+        assert_eq!(err.input(), "mailto:someone@example.com");
+        assert!(err.to_string().contains("missing host"));
+        assert!(Url::parse("not a url").is_err());
+    }
+
+    #[test]
+    fn text_round_trips_through_percent_encoding() {
+        assert_eq!(encode("a b/c~ü"), "a%20b%2Fc~%C3%BC");
+        assert_eq!(decode("a%20b%2Fc~%C3%BC"), "a b/c~ü");
+        assert_eq!(decode("100%"), "100%");
+    }
+}
@@ -0,0 +1,333 @@
+//! Async versions of the HTTP functions, behind the `async` feature, built on
+//! [reqwest](https://docs.rs/reqwest) and [tokio](https://docs.rs/tokio).
+//!
+//! Requests use non-blocking I/O, so many can be in flight on a few threads. They have to run inside
+//! a tokio runtime. Redirects, timeouts, proxies, the headers of a [`Client`](super::Client) and
+//! [`Tls`] settings apply as for blocking requests; cookie jars, caches, rate limiters, retry
+//! policies and [`set_transport_for_tests`](super::set_transport_for_tests) don't.
+//!
+//! ```rs
+//! let response = net::r#async::get("https://example.com").await?;
+//! let written = net::r#async::download("https://example.com/file.zip", "file.zip").await?;
+//! ```
+
+use super::{Body, DownloadOptions, Error, Method, RequestBuilder, StatusCode, Tls, TransportRequest, DEFAULT_TIMEOUT};
+use crate::sdebug;
+use crate as fox;
+use std::path::Path;
+use std::sync::Mutex;
+use tokio::io::AsyncWriteExt;
+
+/// A response to an async request, whose body is read as it arrives.
+#[derive(Debug)]
+pub struct Response {
+    inner: reqwest::Response,
+}
+
+impl Response {
+    /// The status the server answered with.
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from(self.inner.status().as_u16())
+    }
+
+    /// Returns true if the status is 2xx.
+    pub fn ok(&self) -> bool {
+        self.status().is_success()
+    }
+
+    /// The URL the response came from, after redirects.
+    pub fn url(&self) -> &str {
+        self.inner.url().as_str()
+    }
+
+    /// The value of the first header called `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.inner.headers().get(name).and_then(|value| value.to_str().ok())
+    }
+
+    /// The size of the body, if the server sent it and the body isn't compressed.
+    pub fn content_length(&self) -> Option<u64> {
+        self.inner.content_length()
+    }
+
+    /// The next chunk of the body, or None once it has been read.
+    pub async fn chunk(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let url = self.url().to_string();
+        let chunk = self.inner.chunk().await.map_err(|err| error(&url, err))?;
+        Ok(chunk.map(|chunk| chunk.to_vec()))
+    }
+
+    /// Reads the whole body.
+    pub async fn bytes(self) -> Result<Vec<u8>, Error> {
+        let url = self.url().to_string();
+        let bytes = self.inner.bytes().await.map_err(|err| error(&url, err))?;
+        Ok(bytes.to_vec())
+    }
+
+    /// Reads the body as UTF-8 text.
+    pub async fn text(self) -> Result<String, Error> {
+        let bytes = self.bytes().await?;
+        String::from_utf8(bytes).map_err(|err| Error::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, err)))
+    }
+
+    /// Deserializes the body as JSON. If it doesn't fit `T`, the error shows a snippet of the body.
+    pub async fn json<T: serde::de::DeserializeOwned>(self) -> Result<T, Error> {
+        let url = self.url().to_string();
+        let text = self.text().await?;
+        super::http::parse_json(&url, &text)
+    }
+
+    /// The response as a blocking [`Response`](super::Response), with the body read into memory.
+    async fn buffered(self) -> Result<super::Response, Error> {
+        let (status, url) = (self.status(), self.url().to_string());
+        let headers = self
+            .inner
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        Ok(super::Response::from_parts(status, url, headers, self.bytes().await?))
+    }
+}
+
+/// Converts a reqwest error into an [`Error`].
+fn error(url: &str, err: reqwest::Error) -> Error {
+    let url = err.url().map(|url| url.to_string()).unwrap_or_else(|| url.to_string());
+    let mut reason = err.to_string();
+    let mut source = std::error::Error::source(&err);
+    while let Some(cause) = source {
+        reason = format!("{}: {}", reason, cause);
+        source = cause.source();
+    }
+
+    if err.is_timeout() {
+        Error::Timeout { url }
+    } else if err.is_builder() {
+        Error::InvalidUrl { url, reason }
+    } else if err.is_redirect() || err.is_decode() {
+        Error::Protocol { url, reason }
+    } else {
+        Error::Connection { url, reason }
+    }
+}
+
+/// The client for connecting through `proxy` with the `tls` settings. Clients keep connections open, so they are shared.
+fn client(proxy: Option<&str>, tls: Option<&Tls>) -> Result<reqwest::Client, Error> {
+    type Key = (Option<String>, Option<Tls>);
+    static CLIENTS: Mutex<Vec<(Key, reqwest::Client)>> = Mutex::new(Vec::new());
+
+    let mut clients = CLIENTS.lock().unwrap();
+    if let Some((_, client)) = clients.iter().find(|((key_proxy, key_tls), _)| key_proxy.as_deref() == proxy && key_tls.as_ref() == tls) {
+        return Ok(client.clone());
+    }
+
+    // The proxy was already picked from the environment, like for blocking requests
+    let mut builder = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::limited(super::http::MAX_REDIRECTS))
+        .timeout(DEFAULT_TIMEOUT)
+        .user_agent(concat!("fox/", env!("CARGO_PKG_VERSION")))
+        .no_proxy();
+    if let Some(proxy) = proxy {
+        let parsed = reqwest::Proxy::all(proxy).map_err(|err| Error::InvalidUrl { url: proxy.to_string(), reason: format!("bad proxy: {}", err) })?;
+        builder = builder.proxy(parsed);
+    }
+    if let Some(tls) = tls {
+        builder = builder.use_preconfigured_tls((*tls.config()).clone());
+    }
+
+    let client = builder.build().map_err(|err| Error::Tls { reason: err.to_string() })?;
+    clients.push(((proxy.map(str::to_string), tls.cloned()), client.clone()));
+    Ok(client)
+}
+
+/// Sends a request built with [`request`](super::request).
+///
+/// Fails with [`Error::Status`] if the server answers with a 4xx or 5xx status.
+pub async fn send(request: RequestBuilder) -> Result<Response, Error> {
+    let request = request.to_transport()?;
+    let call = build(&request).await?;
+
+    let response = Response { inner: call.send().await.map_err(|err| error(&request.url, err))? };
+    sdebug!("{} {} -> {}", request.method, request.url, response.status());
+    match response.status().as_u16() >= 400 {
+        true => Err(Error::Status(response.buffered().await?)),
+        false => Ok(response),
+    }
+}
+
+/// The reqwest request for `request`. Forms are read into memory on a blocking thread, since their files are read with blocking I/O.
+async fn build(request: &TransportRequest) -> Result<reqwest::RequestBuilder, Error> {
+    let method = match request.method {
+        Method::Get => reqwest::Method::GET,
+        Method::Post => reqwest::Method::POST,
+        Method::Put => reqwest::Method::PUT,
+        Method::Patch => reqwest::Method::PATCH,
+        Method::Delete => reqwest::Method::DELETE,
+        Method::Head => reqwest::Method::HEAD,
+        Method::Options => reqwest::Method::OPTIONS,
+    };
+
+    let mut call = client(request.proxy.as_deref(), request.tls.as_ref())?.request(method, &request.url);
+    for (name, value) in &request.headers {
+        call = call.header(name, value);
+    }
+    if let Some(timeout) = request.timeout {
+        call = call.timeout(timeout);
+    }
+
+    Ok(match &request.body {
+        Body::Empty => call,
+        Body::Multipart(form) => {
+            let form = form.clone();
+            let bytes = tokio::task::spawn_blocking(move || -> Result<Vec<u8>, Error> {
+                let (mut reader, length) = form.reader()?;
+                let mut bytes = Vec::with_capacity(length as usize);
+                std::io::Read::read_to_end(&mut reader, &mut bytes)?;
+                Ok(bytes)
+            })
+            .await
+            .map_err(std::io::Error::other)??;
+            call.body(bytes)
+        }
+        body => call.body(body.to_bytes()),
+    })
+}
+
+/// Sends a GET request, like [`try_get`](super::try_get).
+pub async fn get<U: Into<String>>(url: U) -> Result<Response, Error> {
+    send(super::request(Method::Get, url)).await
+}
+
+/// Sends a POST request with `body`, like [`try_post`](super::try_post).
+pub async fn post<U: Into<String>, B: Into<Body>>(url: U, body: B) -> Result<Response, Error> {
+    send(super::request(Method::Post, url).body(body)).await
+}
+
+/// Fetches `url` and deserializes the JSON it answers with, like [`get_json`](super::get_json).
+pub async fn get_json<T: serde::de::DeserializeOwned, U: Into<String>>(url: U) -> Result<T, Error> {
+    send(super::request(Method::Get, url).header("Accept", "application/json")).await?.json().await
+}
+
+/// Downloads `url` to `dest`, like [`download`](super::download).
+pub async fn download<U: Into<String>, P: AsRef<Path>>(url: U, dest: P) -> Result<u64, Error> {
+    download_with(url, dest, DownloadOptions::default()).await
+}
+
+/// Downloads `url` to `dest` with the given options, like [`download_with`](super::download_with).
+///
+/// The body is streamed into a `.part` file next to `dest`, which replaces `dest` once complete.
+/// Unlike blocking downloads, interrupted ones are not resumed.
+pub async fn download_with<U: Into<String>, P: AsRef<Path>>(url: U, dest: P, mut options: DownloadOptions) -> Result<u64, Error> {
+    use crate::disk::{create_dir_all, fail, move_file, remove_temp_file};
+
+    let url = url.into();
+    let dest = dest.as_ref();
+    if crate::disk::skip_dry_run(|| format!("download {} to `{}`", url, dest.display())) {
+        return Ok(0);
+    }
+
+    let part = super::download::part_path(dest);
+    // A blocking download left behind would otherwise resume from the bytes written here
+    let _ = remove_temp_file(&super::download::etag_path(&part));
+
+    let mut response = send(super::request(Method::Get, url.as_str())).await?;
+    let total = response.content_length();
+
+    if let Some(parent) = dest.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        create_dir_all(parent)?;
+    }
+
+    let mut bar = match (options.progress_bar, total) {
+        (true, Some(total)) => Some(crate::snips::ProgressBar::new(total).bytes()),
+        _ => None,
+    };
+
+    let result = async {
+        let mut hasher = ring::digest::Context::new(&ring::digest::SHA256);
+        let mut file = tokio::fs::File::create(&part).await.map_err(|err| fail("create file", &part, err))?;
+        let mut written = 0u64;
+
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk).await.map_err(|err| fail("write file", &part, err))?;
+            hasher.update(&chunk);
+            written += chunk.len() as u64;
+
+            if let Some(bar) = &mut bar {
+                bar.set_position(written);
+            }
+            if let Some(progress) = &mut options.progress {
+                progress(written, total);
+            }
+        }
+
+        file.sync_all().await.map_err(|err| fail("write file", &part, err))?;
+
+        if let Some(expected) = &options.sha256 {
+            let actual = super::download::hex(hasher.finish().as_ref());
+            if &actual != expected {
+                return Err(Error::Checksum { url: url.clone(), expected: expected.clone(), actual });
+            }
+        }
+
+        Ok(written)
+    }
+    .await;
+
+    if let Some(bar) = &mut bar {
+        bar.finish();
+    }
+
+    match result {
+        Ok(written) => {
+            move_file(&part, dest)?;
+            sdebug!("Downloaded {} to `{}` ({}).", url, dest.display(), crate::disk::format_size(written));
+            Ok(written)
+        }
+        Err(err) => {
+            let _ = remove_temp_file(&part);
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::test::Server;
+
+    #[tokio::test]
+    async fn requests_run_concurrently_on_one_thread() {
+        let server = Server::start().unwrap();
+        server.when("GET", "/a").respond(200, "a");
+        server.when("POST", "/b").respond(201, "b");
+        server.when("GET", "/json").respond(200, r#"{"name":"fox"}"#);
+
+        let (a, b) = tokio::join!(get(format!("{}/a", server.url())), post(format!("{}/b", server.url()), "body"));
+        assert_eq!(a.unwrap().text().await.unwrap(), "a");
+        assert_eq!(b.unwrap().status(), 201);
+        assert!(server.requests().iter().any(|request| request.body_text() == "body"));
+
+        let json: serde_json::Value = get_json(format!("{}/json", server.url())).await.unwrap();
+        assert_eq!(json["name"], "fox");
+
+        let err = get(format!("{}/missing", server.url())).await.unwrap_err();
+        assert_eq!(err.status(), Some(StatusCode::NotFound));
+    }
+
+    #[tokio::test]
+    async fn downloads_are_streamed_to_disk() {
+        let server = Server::start().unwrap();
+        server.when("GET", "/file").respond(200, "test");
+
+        let dir = std::env::temp_dir().join(format!("fox-async-download-{}", std::process::id()));
+        let dest = dir.join("file.txt");
+        let options = DownloadOptions::new().sha256("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08");
+        assert_eq!(download_with(format!("{}/file", server.url()), &dest, options).await.unwrap(), 4);
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), "test");
+
+        let err = download_with(format!("{}/file", server.url()), &dest, DownloadOptions::new().sha256("00")).await.unwrap_err();
+        assert!(matches!(err, Error::Checksum { .. }));
+        assert!(!super::super::download::part_path(&dest).exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,321 @@
+//! A disk cache for GET responses, revalidated with conditional requests.
+
+use super::{Error, Response, StatusCode};
+use crate::swarn;
+use crate as fox;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+/// A cache for GET responses, kept in a directory.
+///
+/// Responses with an `ETag` or `Last-Modified` header are stored, and sending the same request again
+/// asks the server whether they changed, with `If-None-Match` or `If-Modified-Since`. When the server
+/// answers 304 Not Modified, the stored body is returned as a 200 response, so repeated fetches cost
+/// a round trip but no download.
+///
+/// Bodies are written to the cache while they are read, so large ones are never held in memory, and
+/// only responses read to the end are stored. Requests with a `Range` header skip the cache.
+///
+/// Entries are keyed by URL only, so don't share a cache between credentials that see different data.
+/// Responses with `Cache-Control: no-store` are never stored.
+///
+/// ```rs
+/// net::set_cache(Some(Cache::for_app("release-bot")?));
+///
+/// // Only downloaded again when the release changed
+/// let release: Release = net::get_json("https://api.github.com/repos/rust-lang/rust/releases/latest")?;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+/// What is stored next to a cached body.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Entry {
+    url: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    size: u64,
+}
+
+impl Entry {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+}
+
+/// A response loaded from a [`Cache`].
+#[derive(Debug)]
+pub(super) struct Cached {
+    entry: Entry,
+    body: std::fs::File,
+}
+
+impl Cached {
+    /// The headers to send to ask whether the response changed.
+    pub(super) fn conditions(&self) -> Vec<(String, String)> {
+        let mut conditions = Vec::new();
+        if let Some(etag) = self.entry.header("ETag") {
+            conditions.push(("If-None-Match".to_string(), etag.to_string()));
+        }
+        if let Some(modified) = self.entry.header("Last-Modified") {
+            conditions.push(("If-Modified-Since".to_string(), modified.to_string()));
+        }
+        conditions
+    }
+
+    /// The stored response, as if `url` had just answered with it.
+    pub(super) fn into_response(self, url: &str) -> Response {
+        Response::encoded(StatusCode::from(self.entry.status), url.to_string(), self.entry.headers, self.body)
+    }
+}
+
+impl Cache {
+    /// A cache in `dir`, which is created when the first response is stored.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Cache { dir: dir.as_ref().to_path_buf() }
+    }
+
+    /// A cache in the `http` folder of the [cache directory](crate::disk::cache_dir) of `app`.
+    pub fn for_app(app: &str) -> Result<Self, Error> {
+        Ok(Cache::new(crate::disk::cache_dir(app, true)?.join("http")))
+    }
+
+    /// The directory the cache is kept in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Deletes every stored response.
+    pub fn clear(&self) -> Result<(), Error> {
+        if self.dir.exists() {
+            crate::disk::delete_dir(&self.dir)?;
+        }
+        Ok(())
+    }
+
+    /// The paths of the entry and body for `url`.
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let digest = ring::digest::digest(&ring::digest::SHA256, url.as_bytes());
+        let key = super::download::hex(digest.as_ref());
+        (self.dir.join(format!("{}.json", key)), self.dir.join(format!("{}.body", key)))
+    }
+
+    /// The stored response for `url`. Missing, unreadable and half-written entries count as not cached.
+    pub(super) fn load(&self, url: &str) -> Option<Cached> {
+        let (entry_path, body_path) = self.paths(url);
+        let entry: Entry = serde_json::from_slice(&std::fs::read(entry_path).ok()?).ok()?;
+        let body = std::fs::File::open(body_path).ok()?;
+
+        match entry.url == url && body.metadata().ok()?.len() == entry.size {
+            true => Some(Cached { entry, body }),
+            false => None,
+        }
+    }
+
+    /// Returns true if a response with `status` and `headers` can be revalidated later, and may be stored.
+    pub(super) fn accepts(status: StatusCode, headers: &[(String, String)]) -> bool {
+        let header = |name: &str| headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str());
+        let no_store = header("Cache-Control").is_some_and(|value| value.to_ascii_lowercase().contains("no-store"));
+        status == 200 && !no_store && (header("ETag").is_some() || header("Last-Modified").is_some())
+    }
+
+    /// Returns `response` with its body written to the cache as it is read. The entry for `url` is
+    /// only stored once the body was read to the end. Failing to write only logs a warning, since the
+    /// response is still good.
+    pub(super) fn store(&self, url: &str, response: Response) -> Response {
+        if crate::disk::is_dry_run() {
+            return response;
+        }
+
+        let (entry_path, body_path) = self.paths(url);
+        let entry = Entry {
+            url: url.to_string(),
+            status: response.status().as_u16(),
+            // Replaying cookies would be wrong, the client's jar already has them.
+            // The body is stored as it was sent, so its encoding and length still apply.
+            headers: response.headers().iter().filter(|(name, _)| !name.eq_ignore_ascii_case("set-cookie")).cloned().collect(),
+            size: 0,
+        };
+
+        static PARTS: AtomicU64 = AtomicU64::new(0);
+        let name = body_path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        let part = body_path.with_file_name(format!(".{}.part-{}-{}", name, std::process::id(), PARTS.fetch_add(1, Ordering::Relaxed)));
+
+        let file = std::fs::create_dir_all(&self.dir)
+            .map_err(|err| crate::disk::Error::new("create directory", &self.dir, err))
+            .and_then(|_| std::fs::File::create(&part).map_err(|err| crate::disk::Error::new("create file", &part, err)));
+        let file = match file {
+            Ok(file) => file,
+            Err(err) => {
+                swarn!("Could not cache the response from {}: {}", url, err);
+                return response;
+            }
+        };
+
+        let expected = response.header("Content-Length").and_then(|length| length.trim().parse().ok());
+        response.map_body(|body| Box::new(Recorder { body, file: Some(file), part, body_path, entry_path, entry, expected }))
+    }
+}
+
+/// Copies a body into a [`Cache`] as it is read.
+struct Recorder {
+    body: Box<dyn Read + Send + Sync>,
+    /// The `.part` file the body goes to. None once the entry was stored or writing failed.
+    file: Option<std::fs::File>,
+    part: PathBuf,
+    body_path: PathBuf,
+    entry_path: PathBuf,
+    entry: Entry,
+    /// The `Content-Length`, since decoders may stop reading once their stream ends.
+    expected: Option<u64>,
+}
+
+impl Recorder {
+    fn abandon(&mut self, err: crate::disk::Error) {
+        swarn!("Could not cache the response from {}: {}", self.entry.url, err);
+        self.file = None;
+        let _ = std::fs::remove_file(&self.part);
+    }
+
+    fn finish(&mut self) {
+        let result = (|| -> Result<(), Error> {
+            drop(self.file.take());
+            // The body goes first, so an entry never points at a body that isn't there yet
+            std::fs::rename(&self.part, &self.body_path).map_err(|err| crate::disk::Error::new("move file", &self.part, err).with_target(&self.body_path))?;
+            let entry = serde_json::to_vec(&self.entry).map_err(std::io::Error::other)?;
+            crate::disk::write_file_with(&self.entry_path, entry, crate::disk::WriteOptions::new().atomic(true))?;
+            Ok(())
+        })();
+
+        if let Err(err) = result {
+            swarn!("Could not cache the response from {}: {}", self.entry.url, err);
+            let _ = std::fs::remove_file(&self.part);
+        }
+    }
+}
+
+impl Read for Recorder {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.body.read(buf)?;
+        if let Some(file) = &mut self.file {
+            match file.write_all(&buf[..read]) {
+                Ok(()) => self.entry.size += read as u64,
+                Err(err) => self.abandon(crate::disk::Error::new("write file", &self.part, err)),
+            }
+        }
+
+        if self.file.is_some() && (read == 0 || self.expected == Some(self.entry.size)) {
+            self.finish();
+        }
+        Ok(read)
+    }
+}
+
+impl Drop for Recorder {
+    /// A body that wasn't read to the end is not stored.
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = std::fs::remove_file(&self.part);
+        }
+    }
+}
+
+static CACHE: RwLock<Option<Cache>> = RwLock::new(None);
+
+/// Sets the cache for every GET request that doesn't set its own with
+/// [`RequestBuilder::cache`](super::RequestBuilder::cache). Nothing is cached by default.
+pub fn set_cache(cache: Option<Cache>) {
+    *CACHE.write().unwrap() = cache;
+}
+
+pub(super) fn global_cache() -> Option<Cache> {
+    CACHE.read().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::{test::Server, Client};
+
+    fn temp_cache(name: &str) -> Cache {
+        let dir = std::env::temp_dir().join(format!("fox-cache-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        Cache::new(dir)
+    }
+
+    #[test]
+    fn not_modified_serves_the_cached_body() {
+        let server = Server::start().unwrap();
+        server.when("GET", "/data").header("ETag", "\"v1\"").header("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT").respond(200, "payload");
+
+        let cache = temp_cache("revalidate");
+        let client = Client::new(server.url()).cache(cache.clone());
+        assert_eq!(client.get("/data").unwrap().text().unwrap(), "payload");
+        assert_eq!(server.requests()[0].header("If-None-Match"), None);
+
+        server.when("GET", "/data").respond(304, "");
+        let response = client.get("/data").unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.header("ETag"), Some("\"v1\""));
+        assert_eq!(response.text().unwrap(), "payload");
+
+        let requests = server.requests();
+        assert_eq!(requests[1].header("If-None-Match"), Some("\"v1\""));
+        assert_eq!(requests[1].header("If-Modified-Since"), Some("Wed, 21 Oct 2015 07:28:00 GMT"));
+
+        cache.clear().unwrap();
+        assert!(cache.load(&format!("{}/data", server.url())).is_none());
+    }
+
+    #[test]
+    fn only_revalidatable_responses_are_stored() {
+        let server = Server::start().unwrap();
+        server.when("GET", "/plain").respond(200, "no validators");
+        server.when("GET", "/secret").header("ETag", "\"s\"").header("Cache-Control", "private, no-store").respond(200, "secret");
+        server.when("POST", "/data").header("ETag", "\"p\"").respond(200, "posted");
+
+        let cache = temp_cache("skip");
+        let client = Client::new(server.url()).cache(cache.clone());
+        client.get("/plain").unwrap();
+        client.get("/secret").unwrap();
+        client.post("/data", "x").unwrap();
+
+        for path in ["/plain", "/secret", "/data"] {
+            assert!(cache.load(&format!("{}{}", server.url(), path)).is_none(), "{} was cached", path);
+        }
+        assert!(!cache.dir().exists());
+    }
+
+    #[test]
+    fn bodies_are_stored_once_read_to_the_end() {
+        use std::io::Write;
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(b"payload").unwrap();
+        let server = Server::start().unwrap();
+        server.when("GET", "/data").header("ETag", "\"v1\"").header("Content-Encoding", "gzip").respond(200, gzip.finish().unwrap());
+        server.when("GET", "/range").header("ETag", "\"r\"").respond(200, "part");
+
+        let cache = temp_cache("streamed");
+        let client = Client::new(server.url()).cache(cache.clone());
+        let url = format!("{}/data", server.url());
+        drop(client.get("/data").unwrap());
+        assert!(cache.load(&url).is_none());
+
+        let response = client.get("/data").unwrap();
+        assert!(cache.load(&url).is_none());
+        assert_eq!(response.text().unwrap(), "payload");
+        assert!(cache.load(&url).is_some());
+
+        server.when("GET", "/data").respond(304, "");
+        assert_eq!(client.get("/data").unwrap().text().unwrap(), "payload");
+
+        client.request(crate::net::Method::Get, "/range").header("Range", "bytes=0-3").send().unwrap().bytes().unwrap();
+        assert!(cache.load(&format!("{}/range", server.url())).is_none());
+        let _ = cache.clear();
+    }
+}
@@ -0,0 +1,377 @@
+//! Inspecting the certificates of TLS servers, for expiry checks and diagnostics.
+//!
+//! The handshake is done by rustls; the certificate it receives is decoded here, reading only the
+//! few fields of the X.509 DER that [`CertInfo`] exposes.
+
+use crate::{sdebug, serror};
+use crate as fox;
+use chrono::{DateTime, NaiveDate, Utc};
+use super::tls::AcceptAnyCertificate;
+use rustls::pki_types::ServerName;
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Details of the certificate a TLS server presents, as returned by [`cert_info`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertInfo {
+    /// The subject's distinguished name, e.g. `CN=example.com, O=Example`.
+    pub subject: String,
+    /// The issuer's distinguished name.
+    pub issuer: String,
+    /// The DNS names and IP addresses from the Subject Alternative Name extension.
+    pub sans: Vec<String>,
+    /// The serial number in hex.
+    pub serial: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+}
+
+impl CertInfo {
+    /// Whole days left until the certificate expires. Negative once it has expired.
+    pub fn days_until_expiry(&self) -> i64 {
+        (self.not_after - Utc::now()).num_days()
+    }
+
+    /// Returns true if the certificate is past its expiry date.
+    pub fn is_expired(&self) -> bool {
+        Utc::now() > self.not_after
+    }
+
+    /// Returns true if the current time is within the certificate's validity period.
+    pub fn is_valid_now(&self) -> bool {
+        let now = Utc::now();
+        self.not_before <= now && now <= self.not_after
+    }
+}
+
+impl std::fmt::Display for CertInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let dim = fox::log::dim;
+        writeln!(f, "{}    {}", dim("Subject"), self.subject)?;
+        writeln!(f, "{}     {}", dim("Issuer"), self.issuer)?;
+        writeln!(f, "{}       {}", dim("SANs"), self.sans.join(", "))?;
+        writeln!(f, "{}     {}", dim("Serial"), self.serial)?;
+        writeln!(f, "{} {}", dim("Not before"), self.not_before.format("%Y-%m-%d %H:%M:%S UTC"))?;
+        write!(f, "{}  {} ({} days left)", dim("Not after"), self.not_after.format("%Y-%m-%d %H:%M:%S UTC"), self.days_until_expiry())
+    }
+}
+
+/// Fetches and decodes the certificate presented by a TLS server, without verifying it.
+///
+/// The target can be `host`, `host:port` or a URL, with port 443 by default. The handshake is done with
+/// rustls, over TLS 1.3 or 1.2, and the connection is closed once it completes.
+///
+/// ```rs
+/// let cert = net::cert_info("example.com")?;
+/// if cert.days_until_expiry() < 14 {
+///     warn!("Certificate for {} expires on {}", cert.subject, cert.not_after);
+/// }
+/// ```
+pub fn cert_info<H: AsRef<str>>(host: H) -> Result<CertInfo, std::io::Error> {
+    let target = host.as_ref();
+    let (host, port) = super::split_host_port(target);
+    let port = port.unwrap_or(443);
+
+    fetch_certificate(&host, port)
+        .and_then(|der| parse_certificate(&der).ok_or_else(|| invalid("could not decode the certificate")))
+        .inspect(|cert| sdebug!("{} presented a certificate for {}, valid until {}.", target, cert.subject, cert.not_after))
+        .inspect_err(|err| serror!("Failed to get certificate of {}: {}", target, err))
+}
+
+fn invalid(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Performs a TLS handshake with `host` and returns the DER of the server's leaf certificate.
+fn fetch_certificate(host: &str, port: u16) -> Result<Vec<u8>, std::io::Error> {
+    let timeout = Duration::from_secs(10);
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = rustls::ClientConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()
+        .map_err(std::io::Error::other)?
+        .dangerous()
+        // The certificate is only looked at, so an expired or self-signed one is fine
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate(provider)))
+        .with_no_client_auth();
+
+    let name = ServerName::try_from(host.to_string()).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+    let mut connection = rustls::ClientConnection::new(Arc::new(config), name).map_err(std::io::Error::other)?;
+
+    let mut stream = connect(host, port, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    while connection.is_handshaking() {
+        connection.complete_io(&mut stream)?;
+    }
+
+    let der = connection.peer_certificates().and_then(|chain| chain.first()).map(|cert| cert.to_vec());
+    connection.send_close_notify();
+    let _ = connection.complete_io(&mut stream);
+    der.ok_or_else(|| invalid("server sent no certificate"))
+}
+
+/// Connects to `host`, trying each of its addresses in turn, e.g. IPv4 after an unreachable IPv6 one.
+fn connect(host: &str, port: u16, timeout: Duration) -> Result<TcpStream, std::io::Error> {
+    let mut last_error = std::io::Error::new(std::io::ErrorKind::NotFound, "host has no addresses");
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => {
+                sdebug!("Could not connect to {}: {}", addr, err);
+                last_error = err;
+            }
+        }
+    }
+    Err(last_error)
+}
+
+/// Reads one DER element at `pos`, returning its tag, contents and the position after it.
+fn read_tlv(data: &[u8], pos: usize) -> Option<(u8, &[u8], usize)> {
+    let tag = *data.get(pos)?;
+    let first = *data.get(pos + 1)? as usize;
+
+    let (length, start) = if first & 0x80 == 0 {
+        (first, pos + 2)
+    } else {
+        let count = first & 0x7f;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let bytes = data.get(pos + 2..pos + 2 + count)?;
+        (bytes.iter().fold(0usize, |length, &byte| (length << 8) | byte as usize), pos + 2 + count)
+    };
+
+    let contents = data.get(start..start.checked_add(length)?)?;
+    Some((tag, contents, start + length))
+}
+
+/// Splits the contents of a DER constructed element into its children.
+fn children(data: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let mut items = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let (tag, contents, next) = read_tlv(data, pos)?;
+        items.push((tag, contents));
+        pos = next;
+    }
+    Some(items)
+}
+
+fn parse_certificate(der: &[u8]) -> Option<CertInfo> {
+    const SEQUENCE: u8 = 0x30;
+
+    let (_, certificate, _) = read_tlv(der, 0)?;
+    let (tag, tbs, _) = read_tlv(certificate, 0)?;
+    if tag != SEQUENCE {
+        return None;
+    }
+
+    let mut fields = children(tbs)?.into_iter().peekable();
+
+    // The version is an optional explicit [0] tag
+    if fields.peek()?.0 == 0xa0 {
+        fields.next();
+    }
+
+    let (_, serial) = fields.next()?;
+    let _signature = fields.next()?;
+    let (_, issuer) = fields.next()?;
+    let (_, validity) = fields.next()?;
+    let (_, subject) = fields.next()?;
+
+    let validity = children(validity)?;
+    let not_before = parse_time(validity.first()?)?;
+    let not_after = parse_time(validity.get(1)?)?;
+
+    let mut sans = Vec::new();
+    for (tag, contents) in fields {
+        // Extensions are in an explicit [3] tag
+        if tag == 0xa3 {
+            let (_, extensions, _) = read_tlv(contents, 0)?;
+            sans = parse_sans(extensions).unwrap_or_default();
+        }
+    }
+
+    let serial = serial.iter().skip_while(|&&byte| byte == 0).map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(":");
+
+    Some(CertInfo {
+        subject: parse_name(subject)?,
+        issuer: parse_name(issuer)?,
+        sans,
+        serial,
+        not_before,
+        not_after,
+    })
+}
+
+fn parse_name(name: &[u8]) -> Option<String> {
+    let mut parts = Vec::new();
+
+    for (_, set) in children(name)? {
+        for (_, attribute) in children(set)? {
+            let attribute = children(attribute)?;
+            let (_, oid) = attribute.first()?;
+            let (_, value) = attribute.get(1)?;
+
+            let key = match oid {
+                [0x55, 0x04, 0x03] => "CN".to_string(),
+                [0x55, 0x04, 0x06] => "C".to_string(),
+                [0x55, 0x04, 0x07] => "L".to_string(),
+                [0x55, 0x04, 0x08] => "ST".to_string(),
+                [0x55, 0x04, 0x0a] => "O".to_string(),
+                [0x55, 0x04, 0x0b] => "OU".to_string(),
+                _ => format_oid(oid),
+            };
+
+            parts.push(format!("{}={}", key, String::from_utf8_lossy(value)));
+        }
+    }
+
+    Some(parts.join(", "))
+}
+
+fn format_oid(oid: &[u8]) -> String {
+    let mut arcs: Vec<u64> = Vec::new();
+    let mut value = 0u64;
+
+    for &byte in oid {
+        value = (value << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            arcs.push(value);
+            value = 0;
+        }
+    }
+
+    // The first encoded value packs the first two arcs together
+    if let Some(&first) = arcs.first() {
+        let top = (first / 40).min(2);
+        arcs.splice(0..1, [top, first - top * 40]);
+    }
+
+    arcs.iter().map(|arc| arc.to_string()).collect::<Vec<_>>().join(".")
+}
+
+fn parse_sans(extensions: &[u8]) -> Option<Vec<String>> {
+    const SUBJECT_ALT_NAME: [u8; 3] = [0x55, 0x1d, 0x11];
+
+    for (_, extension) in children(extensions)? {
+        let parts = children(extension)?;
+        if parts.first()?.1 != SUBJECT_ALT_NAME {
+            continue;
+        }
+
+        // The value is the last part, after an optional critical flag
+        let (_, value) = parts.last()?;
+        let (_, names, _) = read_tlv(value, 0)?;
+
+        let sans = children(names)?
+            .into_iter()
+            .filter_map(|(tag, contents)| match (tag, contents.len()) {
+                (0x82, _) => Some(String::from_utf8_lossy(contents).into_owned()),
+                (0x87, 4) => Some(IpAddr::from(<[u8; 4]>::try_from(contents).ok()?).to_string()),
+                (0x87, 16) => Some(IpAddr::from(<[u8; 16]>::try_from(contents).ok()?).to_string()),
+                _ => None,
+            })
+            .collect();
+
+        return Some(sans);
+    }
+
+    Some(Vec::new())
+}
+
+/// Parses a UTCTime (`YYMMDDHHMMSSZ`) or GeneralizedTime (`YYYYMMDDHHMMSSZ`).
+fn parse_time((tag, contents): &(u8, &[u8])) -> Option<DateTime<Utc>> {
+    let text = std::str::from_utf8(contents).ok()?.trim_end_matches('Z');
+
+    let (year, rest) = match tag {
+        0x17 => {
+            let year: i32 = text.get(..2)?.parse().ok()?;
+            (if year >= 50 { 1900 + year } else { 2000 + year }, text.get(2..)?)
+        }
+        0x18 => (text.get(..4)?.parse().ok()?, text.get(4..)?),
+        _ => return None,
+    };
+
+    let number = |range: std::ops::Range<usize>| rest.get(range)?.parse::<u32>().ok();
+    let date = NaiveDate::from_ymd_opt(year, number(0..2)?, number(2..4)?)?;
+    let time = date.and_hms_opt(number(4..6)?, number(6..8)?, number(8..10).unwrap_or(0))?;
+    Some(time.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+    const SERVER_DER: &[u8] = include_bytes!("testdata/server.der");
+
+    fn date(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text).unwrap().to_utc()
+    }
+
+    #[test]
+    fn certificates_are_decoded() {
+        let cert = parse_certificate(SERVER_DER).unwrap();
+        assert_eq!(cert.subject, "CN=localhost");
+        assert_eq!(cert.issuer, "CN=Fox Test CA");
+        assert_eq!(cert.sans, ["localhost", "127.0.0.1"]);
+        assert_eq!(cert.serial, "49:60:87:a0:7d:27:f0:fc:7c:f5:0a:88:39:6a:0a:4a:89:11:34:a4");
+        assert_eq!(cert.not_before, date("2026-10-16T00:49:01Z"));
+        // Dates from 2050 on are GeneralizedTime
+        assert_eq!(cert.not_after, date("2126-09-22T00:49:01Z"));
+
+        // The client certificate has no extensions
+        let client = CertificateDer::from_pem_slice(include_bytes!("testdata/client.pem")).unwrap();
+        let client = parse_certificate(&client).unwrap();
+        assert_eq!(client.subject, "CN=fox client");
+        assert!(client.sans.is_empty());
+    }
+
+    #[test]
+    fn malformed_certificates_are_rejected() {
+        assert_eq!(parse_certificate(&[]), None);
+        assert_eq!(parse_certificate(&SERVER_DER[..SERVER_DER.len() / 2]), None);
+        assert_eq!(parse_certificate(&[0x30, 0x03, 0x02, 0x01, 0x00]), None);
+        // A length longer than the data
+        assert_eq!(read_tlv(&[0x30, 0x84, 0xff, 0xff, 0xff, 0xff], 0), None);
+    }
+
+    #[test]
+    fn fields_are_formatted() {
+        assert_eq!(format_oid(&[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01]), "1.2.840.113549.1.9.1");
+        assert_eq!(parse_time(&(0x17, b"491231235959Z")), Some(date("2049-12-31T23:59:59Z")));
+        assert_eq!(parse_time(&(0x17, b"500101000000Z")), Some(date("1950-01-01T00:00:00Z")));
+        assert_eq!(parse_time(&(0x04, b"500101000000Z")), None);
+    }
+
+    #[test]
+    fn certificates_are_fetched_with_a_handshake() {
+        let key = PrivateKeyDer::from_pem_slice(include_bytes!("testdata/server.key")).unwrap();
+        let config = rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![CertificateDer::from(SERVER_DER.to_vec())], key)
+            .unwrap();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut connection = rustls::ServerConnection::new(Arc::new(config)).unwrap();
+            while connection.is_handshaking() {
+                if connection.complete_io(&mut stream).is_err() {
+                    break;
+                }
+            }
+        });
+
+        // The test CA isn't trusted, which doesn't matter for looking at the certificate
+        let cert = cert_info(format!("https://127.0.0.1:{}/health", port)).unwrap();
+        assert_eq!(cert, parse_certificate(SERVER_DER).unwrap());
+    }
+}
@@ -0,0 +1,292 @@
+//! Clients keeping settings and cookies across requests.
+
+use super::cookies::CookieJar;
+use super::proxy::Proxy;
+use super::{Body, Cache, Error, Method, Pages, RateLimiter, RequestBuilder, Response, RetryPolicy, Tls};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+/// The settings of a [`Client`], applying to each of its requests unless the request sets its own.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Settings {
+    pub(super) base_url: Option<String>,
+    pub(super) headers: Vec<(String, String)>,
+    pub(super) timeout: Option<Duration>,
+    pub(super) retry: Option<RetryPolicy>,
+    pub(super) rate_limit: Option<RateLimiter>,
+    pub(super) proxy: Option<Proxy>,
+    pub(super) tls: Option<Tls>,
+    pub(super) log_requests: Option<bool>,
+    pub(super) cache: Option<Cache>,
+}
+
+/// A client for talking to one service, with a base URL, default headers, settings and a cookie jar.
+///
+/// Clones share the cookie jar. Settings follow the order request, then client, then the global
+/// ones from [`set_retry_policy`](super::set_retry_policy), [`set_tls`](super::set_tls),
+/// [`set_rate_limit`](super::set_rate_limit) and [`set_cache`](super::set_cache). The module functions
+/// like [`net::get`](super::get) use a default client without a base URL or cookies.
+///
+/// ```rs
+/// let client = Client::new("https://app.example.com")
+///     .header("Accept", "application/json")
+///     .timeout(Duration::from_secs(10))
+///     .rate_limit(5, Duration::from_secs(1));
+///
+/// client.post("/login", serde_json::json!({ "user": "fox", "password": password }))?;
+/// let orders: Vec<Order> = client.get_json("/orders")?; // sends the session cookie from the login
+/// ```
+#[derive(Debug, Clone)]
+pub struct Client {
+    pub(super) settings: Arc<Settings>,
+    pub(super) cookies: Option<Arc<Mutex<CookieJar>>>,
+}
+
+impl Client {
+    /// A client for the service at `base_url`, which request paths are appended to. Cookies are kept.
+    pub fn new<U: Into<String>>(base_url: U) -> Self {
+        Client {
+            settings: Arc::new(Settings { base_url: Some(base_url.into()), ..Settings::default() }),
+            cookies: Some(Arc::new(Mutex::new(CookieJar::default()))),
+        }
+    }
+
+    fn settings(&mut self) -> &mut Settings {
+        Arc::make_mut(&mut self.settings)
+    }
+
+    /// Sends a header with every request, unless the request sets the same one.
+    pub fn header<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        let name = name.into();
+        self.settings().headers.retain(|(key, _)| !key.eq_ignore_ascii_case(&name));
+        self.settings().headers.push((name, value.into()));
+        self
+    }
+
+    /// Sends `user` and `password` with HTTP basic authentication on every request.
+    pub fn basic_auth<U: std::fmt::Display, P: std::fmt::Display>(self, user: U, password: P) -> Self {
+        self.header("Authorization", super::http::basic_auth(user, password))
+    }
+
+    /// Sends `token` as a bearer token on every request.
+    pub fn bearer<T: std::fmt::Display>(self, token: T) -> Self {
+        self.header("Authorization", format!("Bearer {}", token))
+    }
+
+    /// Overrides the [`DEFAULT_TIMEOUT`](super::DEFAULT_TIMEOUT) for every request.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.settings().timeout = Some(timeout);
+        self
+    }
+
+    /// Retries requests according to `policy`.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.settings().retry = Some(policy);
+        self
+    }
+
+    /// Sends at most `requests` requests every `per`, see [`RateLimiter`].
+    pub fn rate_limit(mut self, requests: u32, per: Duration) -> Self {
+        self.settings().rate_limit = Some(RateLimiter::new(requests, per));
+        self
+    }
+
+    /// Connects through the HTTP proxy at `url`, instead of the one set in the environment.
+    pub fn proxy<U: Into<String>>(mut self, url: U) -> Self {
+        self.settings().proxy = Some(Proxy::Url(url.into()));
+        self
+    }
+
+    /// Connects directly, ignoring any proxy set in the environment.
+    pub fn no_proxy(mut self) -> Self {
+        self.settings().proxy = Some(Proxy::Direct);
+        self
+    }
+
+    /// Uses `tls` for HTTPS.
+    pub fn tls(mut self, tls: Tls) -> Self {
+        self.settings().tls = Some(tls);
+        self
+    }
+
+    /// Logs every request at debug level, or never, whatever [`set_request_logging`](super::set_request_logging) says.
+    pub fn log_requests(mut self, enabled: bool) -> Self {
+        self.settings().log_requests = Some(enabled);
+        self
+    }
+
+    /// Caches GET responses in `cache`, instead of the one set with [`set_cache`](super::set_cache).
+    pub fn cache(mut self, cache: Cache) -> Self {
+        self.settings().cache = Some(cache);
+        self
+    }
+
+    /// Keeps cookies the server sets and sends them back, which is on by default.
+    pub fn cookies(mut self, enabled: bool) -> Self {
+        self.cookies = match enabled {
+            true => Some(self.cookies.unwrap_or_default()),
+            false => None,
+        };
+        self
+    }
+
+    /// The value of the cookie called `name`, if the server set it and it hasn't expired.
+    pub fn cookie(&self, name: &str) -> Option<String> {
+        let cookies = self.cookies.as_ref()?.lock().unwrap();
+        cookies.get(name).map(str::to_string)
+    }
+
+    /// Forgets every cookie, e.g. to log out.
+    pub fn clear_cookies(&self) {
+        if let Some(cookies) = &self.cookies {
+            cookies.lock().unwrap().clear();
+        }
+    }
+
+    /// The URL for `path`: the base URL and `path` joined by a slash. Full URLs are kept as they are.
+    pub fn url(&self, path: &str) -> String {
+        match &self.settings.base_url {
+            Some(base) if !path.contains("://") => match path.is_empty() {
+                true => base.clone(),
+                false => format!("{}/{}", base.trim_end_matches('/'), path.trim_start_matches('/')),
+            },
+            _ => path.to_string(),
+        }
+    }
+
+    /// Starts building a request to `path`, with this client's settings.
+    pub fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        RequestBuilder::new(self.clone(), method, self.url(path))
+    }
+
+    /// Sends a GET request to `path`.
+    ///
+    /// Fails with [`Error::Status`] if the server answers with a 4xx or 5xx status.
+    pub fn get(&self, path: &str) -> Result<Response, Error> {
+        self.request(Method::Get, path).send()
+    }
+
+    /// Sends a POST request with `body` to `path`.
+    pub fn post<B: Into<Body>>(&self, path: &str, body: B) -> Result<Response, Error> {
+        self.request(Method::Post, path).body(body).send()
+    }
+
+    /// Sends a PUT request with `body` to `path`.
+    pub fn put<B: Into<Body>>(&self, path: &str, body: B) -> Result<Response, Error> {
+        self.request(Method::Put, path).body(body).send()
+    }
+
+    /// Sends a PATCH request with `body` to `path`.
+    pub fn patch<B: Into<Body>>(&self, path: &str, body: B) -> Result<Response, Error> {
+        self.request(Method::Patch, path).body(body).send()
+    }
+
+    /// Sends a DELETE request to `path`.
+    pub fn delete(&self, path: &str) -> Result<Response, Error> {
+        self.request(Method::Delete, path).send()
+    }
+
+    /// Sends a HEAD request to `path`.
+    pub fn head(&self, path: &str) -> Result<Response, Error> {
+        self.request(Method::Head, path).send()
+    }
+
+    /// Iterates over the pages of a listing at `path`, like [`get_paginated`](super::get_paginated).
+    pub fn get_paginated(&self, path: &str) -> Pages {
+        self.request(Method::Get, path).paginate()
+    }
+
+    /// Fetches `path` and deserializes the JSON it answers with, like [`get_json`](super::get_json).
+    pub fn get_json<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, Error> {
+        self.request(Method::Get, path).header("Accept", "application/json").send()?.json()
+    }
+
+    /// Sends `body` as JSON in a POST request to `path`, and deserializes the JSON the server answers with,
+    /// like [`post_json`](super::post_json).
+    pub fn post_json<T: serde::de::DeserializeOwned, B: serde::Serialize>(&self, path: &str, body: &B) -> Result<T, Error> {
+        let body = serde_json::to_value(body).map_err(|err| Error::Json { url: self.url(path), reason: err.to_string(), snippet: String::new() })?;
+        self.request(Method::Post, path).header("Accept", "application/json").body(body).send()?.json()
+    }
+}
+
+/// The client behind the module functions: no base URL, no cookies, only the global settings.
+pub(super) fn default_client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| Client { settings: Arc::new(Settings::default()), cookies: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::test::Server;
+
+    #[test]
+    fn paths_join_the_base_url() {
+        let client = Client::new("https://api.example.com/v1/");
+        assert_eq!(client.url("/users"), "https://api.example.com/v1/users");
+        assert_eq!(client.url("users/42"), "https://api.example.com/v1/users/42");
+        assert_eq!(client.url(""), "https://api.example.com/v1/");
+        assert_eq!(client.url("https://other.example.com/x"), "https://other.example.com/x");
+    }
+
+    #[test]
+    fn default_headers_apply_unless_overridden() {
+        let server = Server::start().unwrap();
+        server.when("GET", "/a").respond(200, "");
+
+        let client = Client::new(server.url()).header("Accept", "text/plain").header("X-Team", "fox").bearer("abc");
+        client.get("/a").unwrap();
+        client.request(Method::Get, "/a").header("accept", "application/json").send().unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].header("Accept"), Some("text/plain"));
+        assert_eq!(requests[0].header("Authorization"), Some("Bearer abc"));
+        assert_eq!(requests[1].header("Accept"), Some("application/json"));
+        assert_eq!(requests[1].header("X-Team"), Some("fox"));
+    }
+
+    #[test]
+    fn cookies_survive_login_redirects() {
+        let server = Server::start().unwrap();
+        server.when("POST", "/login").header("Set-Cookie", "session=s3cr3t; Path=/; HttpOnly").header("Location", "/home").respond(303, "");
+        server.when("GET", "/home").respond(200, "welcome");
+        server.when("GET", "/orders").respond(200, "[]");
+
+        let client = Client::new(server.url());
+        let response = client.post("/login", "user=fox").unwrap();
+        assert_eq!(response.url(), format!("{}/home", server.url()));
+        assert_eq!(response.text().unwrap(), "welcome");
+        assert_eq!(client.cookie("session").as_deref(), Some("s3cr3t"));
+
+        client.get("/orders").unwrap();
+        let requests = server.requests();
+        assert_eq!(requests[1].method, "GET");
+        assert_eq!(requests[1].header("Cookie"), Some("session=s3cr3t"));
+        assert_eq!(requests[2].header("Cookie"), Some("session=s3cr3t"));
+
+        // The module functions don't keep cookies
+        crate::net::try_get(format!("{}/orders", server.url())).unwrap();
+        assert_eq!(server.requests()[3].header("Cookie"), None);
+
+        client.clear_cookies();
+        assert_eq!(client.cookie("session"), None);
+    }
+
+    #[test]
+    fn credentials_stay_on_their_origin() {
+        let other = Server::start().unwrap();
+        other.when("GET", "/landing").respond(200, "");
+
+        let server = Server::start().unwrap();
+        server.when("GET", "/go").header("Location", format!("{}/landing", other.url())).respond(302, "");
+        server.when("GET", "/loop").header("Location", "/loop").respond(302, "");
+
+        let client = Client::new(server.url()).bearer("token");
+        client.get("/go").unwrap();
+        assert_eq!(server.requests()[0].header("Authorization"), Some("Bearer token"));
+        assert_eq!(other.requests()[0].header("Authorization"), None);
+
+        assert!(matches!(client.get("/loop"), Err(Error::Protocol { .. })));
+        assert_eq!(server.hits("GET", "/loop"), 6);
+    }
+}
@@ -0,0 +1,218 @@
+//! The cookie jar of a [`Client`](super::Client).
+//!
+//! This covers what login-based web apps need from RFC 6265, not all of it: cookies are matched by
+//! domain and path, expire through `Max-Age` or `Expires`, and `Secure` cookies are only sent over
+//! HTTPS. `SameSite` and `HttpOnly` are ignored, and there is no public suffix list, so the only guard
+//! against a `Domain` covering a whole TLD is that it needs a dot.
+
+use super::Url;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, PartialEq)]
+struct Cookie {
+    name: String,
+    value: String,
+    domain: String,
+    host_only: bool,
+    path: String,
+    secure: bool,
+    expires: Option<DateTime<Utc>>,
+}
+
+impl Cookie {
+    /// Parses a `Set-Cookie` header received from `url`.
+    fn parse(header: &str, url: &Url) -> Option<Cookie> {
+        let host = url.host().to_ascii_lowercase();
+        let mut attributes = header.split(';');
+
+        let (name, value) = attributes.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().trim_matches('"').to_string(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(url.path()),
+            secure: false,
+            expires: None,
+        };
+        let mut max_age = None;
+
+        for attribute in attributes {
+            let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "domain" if !value.is_empty() => {
+                    let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                    if !domain.contains('.') || !domain_matches(&host, &domain) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+                "path" if value.starts_with('/') => cookie.path = value.to_string(),
+                "secure" => cookie.secure = true,
+                "max-age" => max_age = value.parse::<i64>().ok(),
+                "expires" => {
+                    if let Ok(date) = DateTime::parse_from_rfc2822(&value.replace('-', " ")) {
+                        cookie.expires = Some(date.with_timezone(&Utc));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Max-Age wins over Expires
+        if let Some(seconds) = max_age {
+            cookie.expires = Some(Utc::now() + chrono::Duration::seconds(seconds.clamp(-1, 400 * 24 * 3600)));
+        }
+        Some(cookie)
+    }
+
+    fn expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let host = url.host().to_ascii_lowercase();
+
+        let domain_ok = match self.host_only {
+            true => host == self.domain,
+            false => domain_matches(&host, &self.domain),
+        };
+        domain_ok && path_matches(url.path(), &self.path) && (!self.secure || url.scheme() == "https")
+    }
+}
+
+/// Returns true if `host` is `domain` or one of its subdomains.
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain || host.strip_suffix(domain).is_some_and(|rest| rest.ends_with('.'))
+}
+
+/// Returns true if `path` is `cookie_path` or below it.
+fn path_matches(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path
+        || path.strip_prefix(cookie_path).is_some_and(|rest| cookie_path.ends_with('/') || rest.starts_with('/'))
+}
+
+/// The directory of the request path, which is where cookies without a `Path` apply.
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(end) => path[..end].to_string(),
+    }
+}
+
+/// The cookies a client received.
+#[derive(Debug, Default)]
+pub(super) struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    /// Stores the cookies from the `Set-Cookie` headers of a response from `url`.
+    pub(super) fn store<'a, I: IntoIterator<Item = &'a str>>(&mut self, url: &str, headers: I) {
+        let url = match Url::parse(url) {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+
+        let now = Utc::now();
+        for header in headers {
+            let cookie = match Cookie::parse(header, &url) {
+                Some(cookie) => cookie,
+                None => continue,
+            };
+
+            self.cookies.retain(|old| !(old.name == cookie.name && old.domain == cookie.domain && old.path == cookie.path));
+            if !cookie.expired(now) {
+                self.cookies.push(cookie);
+            }
+        }
+    }
+
+    /// The `Cookie` header to send to `url`, if any cookie applies.
+    pub(super) fn header_for(&mut self, url: &str) -> Option<String> {
+        let url = Url::parse(url).ok()?;
+        let now = Utc::now();
+        self.cookies.retain(|cookie| !cookie.expired(now));
+
+        let mut matching: Vec<&Cookie> = self.cookies.iter().filter(|cookie| cookie.matches(&url)).collect();
+        if matching.is_empty() {
+            return None;
+        }
+
+        // More specific paths first, as browsers do
+        matching.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        Some(matching.iter().map(|cookie| format!("{}={}", cookie.name, cookie.value)).collect::<Vec<_>>().join("; "))
+    }
+
+    /// The value of the cookie called `name`, if it was received and hasn't expired.
+    pub(super) fn get(&self, name: &str) -> Option<&str> {
+        let now = Utc::now();
+        self.cookies.iter().find(|cookie| cookie.name == name && !cookie.expired(now)).map(|cookie| cookie.value.as_str())
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.cookies.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cookies_match_domain_path_and_scheme() {
+        let mut jar = CookieJar::default();
+        jar.store(
+            "https://app.example.com/account/login",
+            [
+                "session=abc; Path=/; Secure; HttpOnly",
+                "theme=dark",
+                "shared=1; Domain=.example.com; Path=/",
+                "tld=1; Domain=com",
+                "other=1; Domain=evil.org",
+            ],
+        );
+
+        assert_eq!(jar.header_for("https://app.example.com/account/settings").as_deref(), Some("theme=dark; session=abc; shared=1"));
+        assert_eq!(jar.header_for("http://app.example.com/").as_deref(), Some("shared=1"));
+        assert_eq!(jar.header_for("https://api.example.com/account").as_deref(), Some("shared=1"));
+        assert_eq!(jar.header_for("https://example.org/"), None);
+        assert_eq!(jar.get("theme"), Some("dark"));
+        assert_eq!(jar.get("tld"), None);
+        assert_eq!(jar.get("other"), None);
+    }
+
+    #[test]
+    fn cookies_expire_and_get_replaced() {
+        let mut jar = CookieJar::default();
+        jar.store("https://example.com/", ["a=1", "b=2; Max-Age=3600", "c=3; Expires=Wed, 21 Oct 2015 07:28:00 GMT"]);
+        assert_eq!(jar.get("a"), Some("1"));
+        assert_eq!(jar.get("b"), Some("2"));
+        assert_eq!(jar.get("c"), None);
+
+        jar.store("https://example.com/", ["a=changed", "b=; Max-Age=0"]);
+        assert_eq!(jar.header_for("https://example.com/").as_deref(), Some("a=changed"));
+
+        // Unreadable dates make session cookies
+        jar.store("https://example.com/", ["d=4; Expires=someday", "e=5; Expires=Thu, 01-Jan-1970 00:00:00 GMT"]);
+        assert_eq!(jar.get("d"), Some("4"));
+        assert_eq!(jar.get("e"), None);
+    }
+
+    #[test]
+    fn paths_match_by_segment() {
+        assert!(path_matches("/docs", "/docs"));
+        assert!(path_matches("/docs/page", "/docs"));
+        assert!(path_matches("/docs/page", "/docs/"));
+        assert!(!path_matches("/docsearch", "/docs"));
+        assert_eq!(default_path("/account/login"), "/account");
+        assert_eq!(default_path("/login"), "/");
+    }
+}
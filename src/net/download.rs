@@ -0,0 +1,406 @@
+//! Downloading files to disk, with progress and checksum verification.
+
+use super::{request, Error, Method, RequestBuilder, StatusCode};
+use crate::sdebug;
+use crate as fox;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// A progress callback, called with the bytes downloaded so far and the total size if the server sent it.
+type Progress = Box<dyn FnMut(u64, Option<u64>) + Send>;
+
+/// Options for [`download_with`].
+#[derive(Default)]
+pub struct DownloadOptions {
+    pub(super) progress_bar: bool,
+    pub(super) progress: Option<Progress>,
+    pub(super) sha256: Option<String>,
+}
+
+impl DownloadOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Show a [`ProgressBar`](crate::snips::ProgressBar) while downloading, if the server sends the size.
+    pub fn progress_bar(mut self, enabled: bool) -> Self {
+        self.progress_bar = enabled;
+        self
+    }
+
+    /// Call `progress` with the bytes downloaded so far and the total size, if the server sent it.
+    pub fn on_progress<F: FnMut(u64, Option<u64>) + Send + 'static>(mut self, progress: F) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Verify the download against a hex SHA-256 checksum. Files that don't match are deleted.
+    pub fn sha256<S: Into<String>>(mut self, checksum: S) -> Self {
+        self.sha256 = Some(checksum.into().trim().to_ascii_lowercase());
+        self
+    }
+}
+
+/// Downloads `url` to `dest`, returning the amount of bytes written.
+///
+/// The body is streamed into a `.part` file next to `dest`, which replaces `dest` once complete.
+/// If the download is interrupted and the server sent an `ETag`, the `.part` file is kept, and the
+/// next download to `dest` asks for the rest with a `Range` request, as long as the file is unchanged.
+pub fn download<U: AsRef<str>, P: AsRef<Path>>(url: U, dest: P) -> Result<u64, Error> {
+    download_with(url, dest, DownloadOptions::default())
+}
+
+/// Downloads `url` to `dest` with the given [`DownloadOptions`], returning the amount of bytes written.
+///
+/// ```rs
+/// net::download_with(
+///     "https://example.com/tool.tar.gz",
+///     "tool.tar.gz",
+///     DownloadOptions::new()
+///         .progress_bar(true)
+///         .sha256("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"),
+/// )?;
+/// ```
+pub fn download_with<U: AsRef<str>, P: AsRef<Path>>(url: U, dest: P, options: DownloadOptions) -> Result<u64, Error> {
+    let url = url.as_ref();
+    download_from(url, dest.as_ref(), options, || request(Method::Get, url))
+}
+
+/// Downloads the response to the requests `start` builds for `url`.
+fn download_from<F: Fn() -> RequestBuilder>(url: &str, dest: &Path, mut options: DownloadOptions, start: F) -> Result<u64, Error> {
+    use crate::disk::{create_dir_all, fail, move_file, open_with, remove_temp_file};
+
+    if crate::disk::skip_dry_run(|| format!("download {} to `{}`", url, dest.display())) {
+        return Ok(0);
+    }
+
+    let part = part_path(dest);
+    let etag = etag_path(&part);
+
+    // Resuming appends bytes as the server stores them, which breaks with compressed transfers
+    let fresh = || start().header("Accept-Encoding", "identity");
+    let resume = resumable(&part, &etag);
+    let response = match &resume {
+        Some((offset, tag)) => {
+            sdebug!("Resuming the download of {} after {}.", url, crate::disk::format_size(*offset));
+            match fresh().header("Range", format!("bytes={}-", offset)).header("If-Range", tag.as_str()).send() {
+                Err(err) if err.status() == Some(StatusCode::RangeNotSatisfiable) => fresh().send()?,
+                result => result?,
+            }
+        }
+        None => fresh().send()?,
+    };
+
+    // With If-Range, the server answers 206 with the rest if the file is unchanged, or 200 with all of it
+    let offset = match response.status().as_u16() {
+        206 => match resume.as_ref().map(|(offset, _)| *offset).filter(|offset| range_start(response.header("Content-Range")) == Some(*offset)) {
+            Some(offset) => offset,
+            None => {
+                let _ = remove_temp_file(&part);
+                let _ = remove_temp_file(&etag);
+                return Err(Error::Protocol { url: url.to_string(), reason: "partial content for a range that wasn't asked for".to_string() });
+            }
+        },
+        _ => 0,
+    };
+    let total = response.content_length().map(|length| offset + length);
+
+    if let Some(parent) = dest.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        create_dir_all(parent)?;
+    }
+    if offset == 0 {
+        // Weak ETags can't be used with If-Range
+        let _ = match response.header("ETag").filter(|tag| !tag.starts_with("W/")) {
+            Some(tag) => crate::disk::write_file(&etag, tag),
+            None => remove_temp_file(&etag),
+        };
+    }
+
+    let mut bar = match (options.progress_bar, total) {
+        (true, Some(total)) => Some(crate::snips::ProgressBar::new(total).bytes()),
+        _ => None,
+    };
+
+    let result = (|| {
+        let mut hasher = ring::digest::Context::new(&ring::digest::SHA256);
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut file = match offset {
+            0 => open_with(&part, std::fs::OpenOptions::new().write(true).create(true).truncate(true))?,
+            _ => {
+                let mut existing = open_with(&part, std::fs::OpenOptions::new().read(true))?;
+                let mut hashed = 0u64;
+                while hashed < offset {
+                    let read = existing.read(&mut buffer).map_err(|err| fail("read file", &part, err))?;
+                    if read == 0 {
+                        break;
+                    }
+                    let read = read.min((offset - hashed) as usize);
+                    hasher.update(&buffer[..read]);
+                    hashed += read as u64;
+                }
+
+                open_with(&part, std::fs::OpenOptions::new().append(true))?
+            }
+        };
+        let mut reader = response.into_reader();
+        let mut written = offset;
+
+        loop {
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+
+            file.write_all(&buffer[..read]).map_err(|err| fail("write file", &part, err))?;
+            hasher.update(&buffer[..read]);
+            written += read as u64;
+
+            if let Some(bar) = &mut bar {
+                bar.set_position(written);
+            }
+            if let Some(progress) = &mut options.progress {
+                progress(written, total);
+            }
+        }
+
+        file.sync_all().map_err(|err| fail("write file", &part, err))?;
+
+        if let Some(expected) = &options.sha256 {
+            let actual = hex(hasher.finish().as_ref());
+            if &actual != expected {
+                return Err(Error::Checksum { url: url.to_string(), expected: expected.clone(), actual });
+            }
+        }
+
+        Ok(written)
+    })();
+
+    if let Some(bar) = &mut bar {
+        bar.finish();
+    }
+
+    match result {
+        Ok(written) => {
+            move_file(&part, dest)?;
+            let _ = remove_temp_file(&etag);
+            sdebug!("Downloaded {} to `{}` ({}).", url, dest.display(), crate::disk::format_size(written));
+            Ok(written)
+        }
+        // Interrupted downloads of files with an ETag are kept, to resume them next time
+        Err(err) if !matches!(err, Error::Checksum { .. }) && etag.exists() => {
+            sdebug!("Kept `{}` to resume the download of {} later.", part.display(), url);
+            Err(err)
+        }
+        Err(err) => {
+            let _ = remove_temp_file(&part);
+            let _ = remove_temp_file(&etag);
+            Err(err)
+        }
+    }
+}
+
+/// The size of the partial download at `part` and the ETag it was downloaded with, if it can be resumed.
+fn resumable(part: &Path, etag: &Path) -> Option<(u64, String)> {
+    let tag = std::fs::read_to_string(etag).ok()?.trim().to_string();
+    let size = std::fs::metadata(part).ok()?.len();
+    (size > 0 && !tag.is_empty()).then_some((size, tag))
+}
+
+/// The first byte of a `Content-Range` header, e.g. 100 for `bytes 100-199/200`.
+fn range_start(header: Option<&str>) -> Option<u64> {
+    header?.trim().strip_prefix("bytes ")?.split('-').next()?.trim().parse().ok()
+}
+
+/// Where the ETag of a partial download is kept, to check it's still the same file when resuming.
+pub(super) fn etag_path(part: &Path) -> PathBuf {
+    let mut name = part.file_name().unwrap_or_default().to_os_string();
+    name.push(".etag");
+    part.with_file_name(name)
+}
+
+/// Where a download to `dest` is written until it's complete.
+pub(super) fn part_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    dest.with_file_name(name)
+}
+
+pub(super) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::test::Server;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("fox-download-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn downloads_with_progress_and_checksum() {
+        let server = Server::start().unwrap();
+        server.when("GET", "/file").respond(200, "test");
+
+        let dir = temp_dir("ok");
+        let dest = dir.join("nested/file.txt");
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        let written = download_with(
+            format!("{}/file", server.url()),
+            &dest,
+            DownloadOptions::new()
+                .on_progress(move |done, total| sender.send((done, total)).unwrap())
+                .sha256("9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08"),
+        ).unwrap();
+
+        assert_eq!(written, 4);
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), "test");
+        assert!(!part_path(&dest).exists());
+        assert_eq!(receiver.try_iter().last(), Some((4, Some(4))));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn checksum_mismatches_leave_nothing_behind() {
+        let server = Server::start().unwrap();
+        server.when("GET", "/file").respond(200, "tampered");
+
+        let dir = temp_dir("mismatch");
+        let dest = dir.join("file.txt");
+        let err = download_with(format!("{}/file", server.url()), &dest, DownloadOptions::new().sha256("00")).unwrap_err();
+
+        assert!(matches!(err, Error::Checksum { .. }));
+        assert!(!dest.exists());
+        assert!(!part_path(&dest).exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn failed_requests_leave_the_destination_alone() {
+        let server = Server::start().unwrap();
+        let dir = temp_dir("missing");
+        std::fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("file.txt");
+        std::fs::write(&dest, "old").unwrap();
+
+        assert!(download(format!("{}/missing", server.url()), &dest).is_err());
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), "old");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn partial_downloads_resume_with_a_range() {
+        let server = Server::start().unwrap();
+        server.when("GET", "/file").header("ETag", "\"v1\"").header("Content-Range", "bytes 2-3/4").respond(206, "st");
+
+        let dir = temp_dir("resume");
+        std::fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("file.txt");
+        std::fs::write(part_path(&dest), "te").unwrap();
+        std::fs::write(etag_path(&part_path(&dest)), "\"v1\"").unwrap();
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let written = download_with(
+            format!("{}/file", server.url()),
+            &dest,
+            DownloadOptions::new()
+                .on_progress(move |done, total| sender.send((done, total)).unwrap())
+                .sha256("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"),
+        ).unwrap();
+
+        assert_eq!(written, 4);
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), "test");
+        assert_eq!(receiver.try_iter().last(), Some((4, Some(4))));
+        assert!(!etag_path(&part_path(&dest)).exists());
+
+        let requests = server.requests();
+        assert_eq!(requests[0].header("Range"), Some("bytes=2-"));
+        assert_eq!(requests[0].header("If-Range"), Some("\"v1\""));
+        assert_eq!(requests[0].header("Accept-Encoding"), Some("identity"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn changed_files_download_again() {
+        let server = Server::start().unwrap();
+        server.when("GET", "/file").header("ETag", "\"v2\"").respond(200, "fresh");
+
+        let dir = temp_dir("changed");
+        std::fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("file.txt");
+        std::fs::write(part_path(&dest), "stale bytes").unwrap();
+        std::fs::write(etag_path(&part_path(&dest)), "\"v1\"").unwrap();
+
+        assert_eq!(download(format!("{}/file", server.url()), &dest).unwrap(), 5);
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), "fresh");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn interrupted_downloads_are_kept() {
+        use std::io::BufRead;
+
+        // Promises 10 bytes, then hangs up after 4
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let mut stream = stream;
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\nETag: \"v1\"\r\n\r\ntest").unwrap();
+        });
+
+        let dir = temp_dir("interrupted");
+        let dest = dir.join("file.txt");
+        assert!(download(&url, &dest).is_err());
+        assert!(!dest.exists());
+        assert_eq!(std::fs::read_to_string(part_path(&dest)).unwrap(), "test");
+        assert_eq!(resumable(&part_path(&dest), &etag_path(&part_path(&dest))), Some((4, "\"v1\"".to_string())));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn cached_downloads_are_streamed() {
+        use std::io::BufRead;
+
+        // Promises 10 bytes, then hangs up after 4
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let mut stream = stream;
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\nETag: \"v1\"\r\n\r\ntest").unwrap();
+        });
+
+        let dir = temp_dir("cached");
+        let dest = dir.join("file.txt");
+        let cache = crate::net::Cache::new(dir.join("cache"));
+
+        // Reading the whole body before writing any of it would fail before the .part file exists
+        assert!(download_from(&url, &dest, DownloadOptions::new(), || request(Method::Get, &url).cache(cache.clone())).is_err());
+        assert_eq!(std::fs::read_to_string(part_path(&dest)).unwrap(), "test");
+        assert!(cache.load(&url).is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn content_ranges_are_parsed() {
+        assert_eq!(range_start(Some("bytes 100-199/200")), Some(100));
+        assert_eq!(range_start(Some("bytes 0-0/*")), Some(0));
+        assert_eq!(range_start(Some("bytes */200")), None);
+        assert_eq!(range_start(None), None);
+    }
+}
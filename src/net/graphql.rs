@@ -0,0 +1,153 @@
+//! GraphQL requests over HTTP.
+
+use super::{Client, Error, Method, Response};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// An error a GraphQL server reported in the `errors` array of its answer.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct GraphQlError {
+    pub message: String,
+    /// The path to the field that failed, e.g. `["user", "repos", 0, "name"]`.
+    #[serde(default)]
+    pub path: Vec<serde_json::Value>,
+    /// Extra details, whose shape depends on the server, e.g. `{"code": "NOT_FOUND"}`.
+    #[serde(default)]
+    pub extensions: Option<serde_json::Value>,
+}
+
+impl std::fmt::Display for GraphQlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.path.is_empty() {
+            true => write!(f, "{}", self.message),
+            false => {
+                let path: Vec<String> = self.path.iter().map(|segment| segment.as_str().map_or_else(|| segment.to_string(), str::to_string)).collect();
+                write!(f, "{} (at {})", self.message, path.join("."))
+            }
+        }
+    }
+}
+
+/// The answer to a GraphQL request.
+#[derive(serde::Deserialize)]
+struct Envelope {
+    #[serde(default)]
+    data: Option<serde_json::Value>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+impl Client {
+    /// Sends a GraphQL `query` with its `variables` to `path`, like [`graphql`](super::graphql).
+    pub fn graphql<T: DeserializeOwned, V: Serialize>(&self, path: &str, query: &str, variables: &V) -> Result<T, Error> {
+        let url = self.url(path);
+        let variables = serde_json::to_value(variables).map_err(|err| Error::Json { url: url.clone(), reason: err.to_string(), snippet: String::new() })?;
+        let body = serde_json::json!({ "query": query, "variables": variables });
+
+        let response = match self.request(Method::Post, path).header("Accept", "application/json").body(body).send() {
+            Ok(response) => response,
+            // Servers often answer failed queries with a 400 or 500, with the reasons in the body
+            Err(Error::Status(response)) => return Err(status_error(response)),
+            Err(err) => return Err(err),
+        };
+
+        let envelope: Envelope = response.json()?;
+        if !envelope.errors.is_empty() {
+            return Err(Error::GraphQl { url, errors: envelope.errors });
+        }
+        let data = envelope.data.ok_or_else(|| Error::Json { url: url.clone(), reason: "no `data` in the answer".to_string(), snippet: String::new() })?;
+        serde_json::from_value(data).map_err(|err| Error::Json { url, reason: err.to_string(), snippet: String::new() })
+    }
+}
+
+/// The error for a GraphQL request answered with an error status: the GraphQL errors if the body has
+/// them, the status otherwise.
+fn status_error(response: Response) -> Error {
+    let (status, url, headers) = (response.status(), response.url().to_string(), response.headers().to_vec());
+    let bytes = match response.bytes() {
+        Ok(bytes) => bytes,
+        Err(err) => return err,
+    };
+
+    match serde_json::from_slice::<Envelope>(&bytes) {
+        Ok(envelope) if !envelope.errors.is_empty() => Error::GraphQl { url, errors: envelope.errors },
+        _ => Error::Status(Response::from_parts(status, url, headers, bytes)),
+    }
+}
+
+/// Sends a GraphQL `query` with its `variables` in a POST request to `url`, and deserializes the `data` of the answer.
+///
+/// Errors the server reports in the answer become [`Error::GraphQl`], even with partial data
+/// and a 200 status, so they can't go unnoticed.
+///
+/// ```rs
+/// #[derive(serde::Deserialize)]
+/// struct Data { repository: Repository }
+///
+/// let data: Data = net::graphql(
+///     "https://api.github.com/graphql",
+///     "query($owner: String!, $name: String!) { repository(owner: $owner, name: $name) { stargazerCount } }",
+///     &serde_json::json!({ "owner": "rust-lang", "name": "rust" }),
+/// )?;
+/// ```
+pub fn graphql<T: DeserializeOwned, V: Serialize, U: AsRef<str>>(url: U, query: &str, variables: &V) -> Result<T, Error> {
+    super::client::default_client().graphql(url.as_ref(), query, variables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::test::Server;
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Data {
+        user: User,
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct User {
+        name: String,
+    }
+
+    #[test]
+    fn data_is_deserialized() {
+        let server = Server::start().unwrap();
+        server.when("POST", "/graphql").respond_json(200, &serde_json::json!({ "data": { "user": { "name": "fox" } } }));
+
+        let data: Data = graphql(format!("{}/graphql", server.url()), "query($id: ID!) { user(id: $id) { name } }", &serde_json::json!({ "id": 7 })).unwrap();
+        assert_eq!(data, Data { user: User { name: "fox".to_string() } });
+
+        let sent: serde_json::Value = serde_json::from_slice(&server.requests()[0].body).unwrap();
+        assert_eq!(sent["query"], "query($id: ID!) { user(id: $id) { name } }");
+        assert_eq!(sent["variables"]["id"], 7);
+    }
+
+    #[test]
+    fn errors_are_surfaced() {
+        let server = Server::start().unwrap();
+        let partial = serde_json::json!({
+            "data": { "user": null },
+            "errors": [{ "message": "not allowed", "path": ["user", 0, "name"], "extensions": { "code": "FORBIDDEN" } }],
+        });
+        server.when("POST", "/partial").respond_json(200, &partial);
+        server.when("POST", "/invalid").respond_json(400, &serde_json::json!({ "errors": [{ "message": "syntax error" }] }));
+        server.when("POST", "/down").respond(503, "maintenance");
+
+        let client = Client::new(server.url());
+        match client.graphql::<serde_json::Value, _>("/partial", "{ user { name } }", &()) {
+            Err(Error::GraphQl { errors, .. }) => {
+                assert_eq!(errors[0].extensions, Some(serde_json::json!({ "code": "FORBIDDEN" })));
+                assert_eq!(errors[0].to_string(), "not allowed (at user.0.name)");
+            }
+            other => panic!("expected GraphQL errors, got {:?}", other),
+        }
+
+        let err = client.graphql::<serde_json::Value, _>("/invalid", "{ user", &()).unwrap_err();
+        assert_eq!(err.to_string(), format!("{}/invalid answered with GraphQL errors: syntax error", server.url()));
+
+        match client.graphql::<serde_json::Value, _>("/down", "{ user { name } }", &()) {
+            Err(Error::Status(response)) => assert_eq!(response.text().unwrap(), "maintenance"),
+            other => panic!("expected a status error, got {:?}", other),
+        }
+    }
+}
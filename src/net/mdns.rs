@@ -0,0 +1,340 @@
+//! Finding services on the local network with multicast DNS (RFC 6762) and DNS-SD (RFC 6763).
+//!
+//! [`discover`] sends one PTR query for a service type and collects the PTR, SRV, TXT, A and AAAA
+//! records in the answers that arrive before the timeout. Packets are parsed by hand, since only
+//! those record types are needed.
+
+use crate::{sdebug, serror};
+use crate as fox;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+const MDNS_ADDR: ([u8; 4], u16) = ([224, 0, 0, 251], 5353);
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+
+/// A service instance found by [`discover`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Service {
+    /// The full instance name, e.g. `Office Printer._ipp._tcp.local`.
+    pub instance: String,
+    /// The host the service runs on, e.g. `printer.local`.
+    pub host: String,
+    pub addrs: Vec<IpAddr>,
+    pub port: u16,
+    /// The raw TXT strings, usually `key=value` pairs.
+    pub txt: Vec<String>,
+}
+
+impl Service {
+    /// The instance name without the service type, e.g. `Office Printer`.
+    pub fn name(&self) -> &str {
+        self.instance.split("._").next().unwrap_or(&self.instance)
+    }
+
+    /// Returns the value of a `key=value` TXT entry, ignoring the key's case.
+    pub fn txt_value(&self, key: &str) -> Option<&str> {
+        self.txt.iter().find_map(|entry| {
+            let (k, v) = entry.split_once('=').unwrap_or((entry, ""));
+            k.eq_ignore_ascii_case(key).then_some(v)
+        })
+    }
+}
+
+/// Browses the local network over mDNS for instances of `service_type`, listening for answers until `timeout`.
+///
+/// The type looks like `_http._tcp`, with `.local` added if missing. Only IPv4 multicast is used.
+///
+/// ```rs
+/// for printer in net::discover("_ipp._tcp", Duration::from_secs(2))? {
+///     info!("{} at {:?}:{}", printer.name(), printer.addrs, printer.port);
+/// }
+/// ```
+pub fn discover<S: AsRef<str>>(service_type: S, timeout: Duration) -> Result<Vec<Service>, std::io::Error> {
+    let service_type = service_type.as_ref().trim_end_matches('.').to_ascii_lowercase();
+    let service_type = if service_type.ends_with(".local") { service_type } else { format!("{}.local", service_type) };
+
+    let socket = UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| {
+            socket.set_multicast_ttl_v4(255)?;
+            socket.send_to(&build_query(&service_type), SocketAddr::from(MDNS_ADDR))?;
+            Ok(socket)
+        })
+        .inspect_err(|err| serror!("Failed to send mDNS query: {}", err))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut records = Vec::new();
+    let mut buffer = [0u8; 9000];
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+
+        socket.set_read_timeout(Some(remaining))?;
+        match socket.recv_from(&mut buffer) {
+            Ok((read, from)) => match parse_packet(&buffer[..read]) {
+                Some(parsed) => records.extend(parsed),
+                None => sdebug!("Ignoring malformed mDNS packet from {}.", from),
+            },
+            Err(err) if matches!(err.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => break,
+            Err(err) => {
+                serror!("Failed to receive mDNS answers: {}", err);
+                return Err(err);
+            }
+        }
+    }
+
+    let services = collect_services(&service_type, &records);
+    sdebug!("Found {} {} services.", services.len(), service_type);
+    Ok(services)
+}
+
+#[derive(Debug, Clone)]
+enum RecordData {
+    Ptr(String),
+    Srv { port: u16, target: String },
+    Txt(Vec<String>),
+    Addr(IpAddr),
+}
+
+#[derive(Debug, Clone)]
+struct Record {
+    name: String,
+    data: RecordData,
+}
+
+fn collect_services(service_type: &str, records: &[Record]) -> Vec<Service> {
+    let mut services: Vec<Service> = Vec::new();
+
+    for record in records {
+        let instance = match &record.data {
+            RecordData::Ptr(instance) if record.name.eq_ignore_ascii_case(service_type) => instance,
+            _ => continue,
+        };
+
+        if services.iter().any(|service| service.instance.eq_ignore_ascii_case(instance)) {
+            continue;
+        }
+
+        let mut service = Service { instance: instance.clone(), host: String::new(), addrs: Vec::new(), port: 0, txt: Vec::new() };
+
+        for record in records.iter().filter(|record| record.name.eq_ignore_ascii_case(instance)) {
+            match &record.data {
+                RecordData::Srv { port, target } => {
+                    service.port = *port;
+                    service.host = target.clone();
+                }
+                RecordData::Txt(txt) => service.txt = txt.clone(),
+                _ => {}
+            }
+        }
+
+        for record in records.iter().filter(|record| !service.host.is_empty() && record.name.eq_ignore_ascii_case(&service.host)) {
+            if let RecordData::Addr(addr) = record.data {
+                if !service.addrs.contains(&addr) {
+                    service.addrs.push(addr);
+                }
+            }
+        }
+
+        sdebug!("Found {} on {}:{}.", service.instance, service.host, service.port);
+        services.push(service);
+    }
+
+    services
+}
+
+fn build_query(name: &str) -> Vec<u8> {
+    // ID, flags, 1 question, no answer/authority/additional records
+    let mut packet = vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        let label = &label.as_bytes()[..label.len().min(63)];
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label);
+    }
+    packet.push(0);
+
+    packet.extend_from_slice(&TYPE_PTR.to_be_bytes());
+    // Class IN, with the top bit asking for a unicast response
+    packet.extend_from_slice(&0x8001u16.to_be_bytes());
+    packet
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]))
+}
+
+/// Reads a possibly compressed name at `pos`, returning it and the position after it.
+fn read_name(data: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    let mut jumps = 0;
+
+    loop {
+        let length = *data.get(pos)? as usize;
+
+        if length & 0xc0 == 0xc0 {
+            let pointer = (read_u16(data, pos)? & 0x3fff) as usize;
+            end.get_or_insert(pos + 2);
+
+            jumps += 1;
+            if jumps > 32 {
+                return None;
+            }
+            pos = pointer;
+        } else if length == 0 {
+            let end = end.unwrap_or(pos + 1);
+            return Some((labels.join("."), end));
+        } else {
+            let label = data.get(pos + 1..pos + 1 + length)?;
+            labels.push(String::from_utf8_lossy(label).into_owned());
+            pos += 1 + length;
+        }
+    }
+}
+
+fn parse_packet(data: &[u8]) -> Option<Vec<Record>> {
+    let questions = read_u16(data, 4)?;
+    let records = read_u16(data, 6)? as usize + read_u16(data, 8)? as usize + read_u16(data, 10)? as usize;
+    let mut pos = 12;
+
+    for _ in 0..questions {
+        pos = read_name(data, pos)?.1 + 4;
+    }
+
+    let mut parsed = Vec::new();
+    for _ in 0..records {
+        let (name, after) = read_name(data, pos)?;
+        let kind = read_u16(data, after)?;
+        let length = read_u16(data, after + 8)? as usize;
+        let start = after + 10;
+        let rdata = data.get(start..start + length)?;
+        pos = start + length;
+
+        let record_data = match kind {
+            TYPE_PTR => RecordData::Ptr(read_name(data, start)?.0),
+            TYPE_SRV => RecordData::Srv { port: read_u16(data, start + 4)?, target: read_name(data, start + 6)?.0 },
+            TYPE_TXT => {
+                let mut strings = Vec::new();
+                let mut i = 0;
+                while i < rdata.len() {
+                    let len = rdata[i] as usize;
+                    let string = rdata.get(i + 1..i + 1 + len)?;
+                    if !string.is_empty() {
+                        strings.push(String::from_utf8_lossy(string).into_owned());
+                    }
+                    i += 1 + len;
+                }
+                RecordData::Txt(strings)
+            }
+            TYPE_A if length == 4 => RecordData::Addr(IpAddr::V4(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]))),
+            TYPE_AAAA if length == 16 => {
+                let octets: [u8; 16] = rdata.try_into().ok()?;
+                RecordData::Addr(IpAddr::V6(Ipv6Addr::from(octets)))
+            }
+            _ => continue,
+        };
+
+        parsed.push(Record { name, data: record_data });
+    }
+
+    Some(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Appends a DNS name as labels.
+    fn name(packet: &mut Vec<u8>, name: &str) {
+        for label in name.split('.') {
+            packet.push(label.len() as u8);
+            packet.extend_from_slice(label.as_bytes());
+        }
+        packet.push(0);
+    }
+
+    /// Appends a record of class IN whose owner name is already in `packet` at `owner`,
+    /// returning where its data starts.
+    fn record(packet: &mut Vec<u8>, owner: u16, kind: u16, rdata: &[u8]) -> u16 {
+        packet.extend_from_slice(&(0xc000 | owner).to_be_bytes());
+        packet.extend_from_slice(&kind.to_be_bytes());
+        packet.extend_from_slice(&[0x80, 0x01, 0, 0, 0x11, 0x94]);
+        packet.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        let start = packet.len() as u16;
+        packet.extend_from_slice(rdata);
+        start
+    }
+
+    /// An answer from a printer, with the names compressed the way responders do.
+    fn printer_answer() -> Vec<u8> {
+        // No questions, 1 answer, no authority records, 4 additional records
+        let mut packet = vec![0, 0, 0x84, 0, 0, 0, 0, 1, 0, 0, 0, 4];
+
+        // The PTR answer spells out the service type, later names point back into it
+        let service_type = packet.len() as u16;
+        name(&mut packet, "_ipp._tcp.local");
+        packet.extend_from_slice(&[0, 12, 0, 1, 0, 0, 0x11, 0x94]);
+
+        let mut instance = vec![14];
+        instance.extend_from_slice(b"Office Printer");
+        instance.extend_from_slice(&(0xc000 | service_type).to_be_bytes());
+        packet.extend_from_slice(&(instance.len() as u16).to_be_bytes());
+        let instance_start = packet.len() as u16;
+        packet.extend_from_slice(&instance);
+
+        let mut srv = vec![0, 0, 0, 0, 0x02, 0x77];
+        name(&mut srv, "printer.local");
+        let host_start = record(&mut packet, instance_start, TYPE_SRV, &srv) + 6;
+        record(&mut packet, instance_start, TYPE_TXT, b"\x06rp=ipp\x00\x0aty=Laser 5");
+        record(&mut packet, host_start, TYPE_A, &[192, 168, 1, 40]);
+        record(&mut packet, host_start, TYPE_AAAA, &Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0x40).octets());
+        packet
+    }
+
+    #[test]
+    fn answers_are_parsed_into_services() {
+        let records = parse_packet(&printer_answer()).unwrap();
+        assert_eq!(records.len(), 5);
+
+        let services = collect_services("_ipp._tcp.local", &records);
+        assert_eq!(services.len(), 1);
+        let printer = &services[0];
+        assert_eq!(printer.instance, "Office Printer._ipp._tcp.local");
+        assert_eq!(printer.name(), "Office Printer");
+        assert_eq!(printer.host, "printer.local");
+        assert_eq!(printer.port, 631);
+        assert_eq!(printer.addrs, [IpAddr::from([192, 168, 1, 40]), IpAddr::from(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0x40))]);
+        assert_eq!(printer.txt, ["rp=ipp", "ty=Laser 5"]);
+        assert_eq!(printer.txt_value("TY"), Some("Laser 5"));
+
+        assert!(collect_services("_http._tcp.local", &records).is_empty());
+    }
+
+    #[test]
+    fn queries_skip_their_questions() {
+        let query = build_query("_http._tcp.local");
+        assert_eq!(read_name(&query, 12), Some(("_http._tcp.local".to_string(), 30)));
+        assert_eq!(parse_packet(&query).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn malformed_packets_are_rejected() {
+        let answer = printer_answer();
+        assert!(parse_packet(&answer[..answer.len() - 3]).is_none());
+        assert!(parse_packet(&answer[..8]).is_none());
+
+        // A name pointing at itself
+        let mut looping = vec![0, 0, 0x84, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+        looping.extend_from_slice(&[0xc0, 12]);
+        assert!(read_name(&looping, 12).is_none());
+        assert!(parse_packet(&looping).is_none());
+    }
+}
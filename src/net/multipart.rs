@@ -0,0 +1,197 @@
+//! Multipart forms, for uploading files the way a browser form does.
+
+use super::{Error, Response};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// A progress callback, called with the bytes sent so far and the total size.
+type Progress = Arc<Mutex<dyn FnMut(u64, u64) + Send>>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text { name: String, value: String },
+    File { name: String, path: PathBuf },
+}
+
+/// A `multipart/form-data` body with text fields and files. Files are streamed from disk when the request is sent.
+///
+/// ```rs
+/// let form = Multipart::new()
+///     .text("version", "1.2.0")
+///     .file("artifact", "target/release/tool")
+///     .progress_bar(true);
+///
+/// let response = net::try_post("https://releases.example.com/upload", form)?;
+/// ```
+#[derive(Clone)]
+pub struct Multipart {
+    boundary: String,
+    parts: Vec<Part>,
+    progress_bar: bool,
+    progress: Option<Progress>,
+}
+
+impl Default for Multipart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Multipart {
+    pub fn new() -> Self {
+        use std::hash::{BuildHasher, Hasher};
+
+        // RandomState is seeded randomly, which is plenty to not collide with the content
+        let random = || std::collections::hash_map::RandomState::new().build_hasher().finish();
+        Multipart {
+            boundary: format!("fox-{:016x}{:016x}", random(), random()),
+            parts: Vec::new(),
+            progress_bar: false,
+            progress: None,
+        }
+    }
+
+    /// Adds a text field.
+    pub fn text<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.parts.push(Part::Text { name: name.into(), value: value.into() });
+        self
+    }
+
+    /// Adds the file at `path` as the field `name`. The file is read when the request is sent.
+    pub fn file<N: Into<String>, P: AsRef<Path>>(mut self, name: N, path: P) -> Self {
+        self.parts.push(Part::File { name: name.into(), path: path.as_ref().to_path_buf() });
+        self
+    }
+
+    /// Show a [`ProgressBar`](crate::snips::ProgressBar) while sending.
+    pub fn progress_bar(mut self, enabled: bool) -> Self {
+        self.progress_bar = enabled;
+        self
+    }
+
+    /// Call `progress` with the bytes sent so far and the total size.
+    pub fn on_progress<F: FnMut(u64, u64) + Send + 'static>(mut self, progress: F) -> Self {
+        self.progress = Some(Arc::new(Mutex::new(progress)));
+        self
+    }
+
+    /// The `Content-Type` header for this form.
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    /// Opens the files and returns the encoded form as a reader, along with its size.
+    pub(super) fn reader(&self) -> Result<(impl Read + Send, u64), Error> {
+        let mut segments: Vec<(Box<dyn Read + Send>, u64)> = Vec::new();
+        let bytes = |bytes: Vec<u8>| -> (Box<dyn Read + Send>, u64) {
+            let length = bytes.len() as u64;
+            (Box::new(Cursor::new(bytes)), length)
+        };
+
+        for part in &self.parts {
+            match part {
+                Part::Text { name, value } => {
+                    let header = format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n", self.boundary, escape(name));
+                    segments.push(bytes([header.as_bytes(), value.as_bytes(), b"\r\n"].concat()));
+                }
+                Part::File { name, path } => {
+                    let file = std::fs::File::open(path).map_err(|err| crate::disk::Error::new("open file", path, err))?;
+                    let size = file.metadata().map_err(|err| crate::disk::Error::new("read metadata of", path, err))?.len();
+                    let mime = crate::disk::detect_type(path)?.mime();
+                    let filename = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+
+                    let header = format!(
+                        "--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+                        self.boundary,
+                        escape(name),
+                        escape(&filename),
+                        mime
+                    );
+                    segments.push(bytes(header.into_bytes()));
+                    segments.push((Box::new(file.take(size)), size));
+                    segments.push(bytes(b"\r\n".to_vec()));
+                }
+            }
+        }
+        segments.push(bytes(format!("--{}--\r\n", self.boundary).into_bytes()));
+
+        let total = segments.iter().map(|(_, length)| length).sum();
+        let segments: Vec<_> = segments.into_iter().map(|(segment, _)| segment).collect();
+        let bar = self.progress_bar.then(|| crate::snips::ProgressBar::new(total).bytes());
+        let reader = Sending { segments: segments.into_iter(), current: None, sent: 0, total, progress: self.progress.clone(), bar };
+        Ok((reader, total))
+    }
+}
+
+impl PartialEq for Multipart {
+    fn eq(&self, other: &Self) -> bool {
+        self.boundary == other.boundary && self.parts == other.parts
+    }
+}
+
+impl std::fmt::Debug for Multipart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Multipart").field("boundary", &self.boundary).field("parts", &self.parts).finish_non_exhaustive()
+    }
+}
+
+/// Escapes a field or file name for a quoted header value, like browsers do.
+fn escape(name: &str) -> String {
+    name.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Reads the segments of a form one after the other, reporting progress.
+struct Sending {
+    segments: std::vec::IntoIter<Box<dyn Read + Send>>,
+    current: Option<Box<dyn Read + Send>>,
+    sent: u64,
+    total: u64,
+    progress: Option<Progress>,
+    bar: Option<crate::snips::ProgressBar>,
+}
+
+impl Read for Sending {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let current = match &mut self.current {
+                Some(current) => current,
+                None => match self.segments.next() {
+                    Some(next) => self.current.insert(next),
+                    None => {
+                        if let Some(bar) = &mut self.bar {
+                            bar.finish();
+                        }
+                        return Ok(0);
+                    }
+                },
+            };
+
+            let read = current.read(buf)?;
+            if read == 0 {
+                self.current = None;
+                continue;
+            }
+
+            self.sent += read as u64;
+            if let Some(bar) = &mut self.bar {
+                bar.set_position(self.sent);
+            }
+            if let Some(progress) = &self.progress {
+                (progress.lock().unwrap())(self.sent, self.total);
+            }
+            return Ok(read);
+        }
+    }
+}
+
+/// Uploads the file at `path` as the form field `field`, in a multipart POST request.
+///
+/// See [`Multipart`] for sending more fields or showing progress.
+///
+/// ```rs
+/// net::upload("https://paste.example.com/upload", "file", "build.log")?;
+/// ```
+pub fn upload<U: AsRef<str>, P: AsRef<Path>>(url: U, field: &str, path: P) -> Result<Response, Error> {
+    super::try_post(url, Multipart::new().file(field, path))
+}
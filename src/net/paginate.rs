@@ -0,0 +1,193 @@
+//! Iterating over paginated API listings.
+
+use super::{Error, Method, RequestBuilder, Response, Url};
+
+/// How [`Pages`] finds the next page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Strategy {
+    /// Follow the `Link` header with `rel="next"`, as GitHub and GitLab send.
+    Link,
+    /// Send the cursor from the JSON field at `pointer` as the query parameter `param`.
+    Cursor { param: String, pointer: String },
+}
+
+/// The pages of a paginated listing, fetched one by one while iterating.
+/// Created by [`get_paginated`], [`Client::get_paginated`](super::Client::get_paginated) and
+/// [`RequestBuilder::paginate`].
+///
+/// By default the next page is the one in the RFC 8288 `Link` header with `rel="next"`.
+/// Use [`cursor`](Pages::cursor) for APIs that return a cursor in the body instead.
+/// The iteration stops at the last page, or after yielding the first error.
+///
+/// ```rs
+/// let mut repos = Vec::new();
+/// for page in net::get_paginated("https://api.github.com/orgs/rust-lang/repos?per_page=100") {
+///     repos.extend(page?.json::<Vec<Repo>>()?);
+/// }
+///
+/// // {"items": [...], "meta": {"next_cursor": "c2"}} -> GET /events?cursor=c2
+/// let pages = client.request(Method::Get, "/events").paginate().cursor("cursor", "meta.next_cursor");
+/// ```
+#[derive(Debug)]
+#[must_use = "pages are only fetched while iterating"]
+pub struct Pages {
+    request: RequestBuilder,
+    next: Option<String>,
+    strategy: Strategy,
+    max_pages: Option<usize>,
+    fetched: usize,
+}
+
+impl Pages {
+    pub(super) fn new(request: RequestBuilder) -> Self {
+        Pages { next: Some(request.url().to_string()), request, strategy: Strategy::Link, max_pages: None, fetched: 0 }
+    }
+
+    /// Finds the next page through a cursor in the JSON body instead of the `Link` header.
+    ///
+    /// `field` is the path to the cursor, with dots between nested keys, e.g. `meta.next_cursor`.
+    /// The next page is requested with the cursor as the query parameter `param`. The iteration
+    /// stops when the field is missing, null or empty.
+    pub fn cursor(mut self, param: &str, field: &str) -> Self {
+        let pointer = field.split('.').map(|key| format!("/{}", key.replace('~', "~0").replace('/', "~1"))).collect();
+        self.strategy = Strategy::Cursor { param: param.to_string(), pointer };
+        self
+    }
+
+    /// Stops after `pages` pages, even if there are more.
+    pub fn max_pages(mut self, pages: usize) -> Self {
+        self.max_pages = Some(pages);
+        self
+    }
+
+    /// Fetches the page at `url` and works out the URL of the one after it.
+    fn fetch(&self, url: &str) -> Result<(Response, Option<String>), Error> {
+        let request = match self.fetched {
+            0 => self.request.clone(),
+            _ => self.request.clone().with_url(url.to_string()),
+        };
+        let response = request.send()?;
+
+        match &self.strategy {
+            Strategy::Link => {
+                let next = response
+                    .headers()
+                    .iter()
+                    .filter(|(name, _)| name.eq_ignore_ascii_case("Link"))
+                    .find_map(|(_, value)| next_link(value))
+                    .and_then(|next| Url::parse(response.url()).ok()?.resolve(next).ok())
+                    .map(|next| next.to_string());
+                Ok((response, next))
+            }
+            Strategy::Cursor { param, pointer } => {
+                let (status, page_url, headers) = (response.status(), response.url().to_string(), response.headers().to_vec());
+                let bytes = response.bytes()?;
+                let body: serde_json::Value = super::http::parse_json(&page_url, &String::from_utf8_lossy(&bytes))?;
+
+                let cursor = match body.pointer(pointer) {
+                    Some(serde_json::Value::String(cursor)) => Some(cursor.clone()),
+                    Some(serde_json::Value::Number(cursor)) => Some(cursor.to_string()),
+                    _ => None,
+                };
+                let next = cursor
+                    .filter(|cursor| !cursor.is_empty())
+                    .and_then(|cursor| Some(Url::parse(&page_url).ok()?.set_query(param, cursor).to_string()));
+                Ok((Response::from_parts(status, page_url, headers, bytes), next))
+            }
+        }
+    }
+}
+
+impl Iterator for Pages {
+    type Item = Result<Response, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.max_pages.is_some_and(|max| self.fetched >= max) {
+            return None;
+        }
+        let url = self.next.take()?;
+
+        let result = self.fetch(&url);
+        self.fetched += 1;
+        Some(result.map(|(response, next)| {
+            // A page linking to itself would never end
+            self.next = next.filter(|next| *next != url);
+            response
+        }))
+    }
+}
+
+/// The target of the link with `rel="next"` in a `Link` header, e.g.
+/// `<https://api.example.com/items?page=2>; rel="next", <https://api.example.com/items?page=5>; rel="last"`.
+fn next_link(header: &str) -> Option<&str> {
+    let mut rest = header;
+    while let Some(start) = rest.find('<') {
+        let end = start + rest[start..].find('>')?;
+        let target = &rest[start + 1..end];
+        let params_end = rest[end..].find('<').map_or(rest.len(), |offset| end + offset);
+
+        let is_next = rest[end + 1..params_end].split([';', ',']).any(|param| {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            key.trim().eq_ignore_ascii_case("rel") && value.trim().trim_matches('"').split_whitespace().any(|rel| rel.eq_ignore_ascii_case("next"))
+        });
+        if is_next {
+            return Some(target);
+        }
+        rest = &rest[params_end..];
+    }
+    None
+}
+
+/// Iterates over the pages of a listing at `url`, following `Link: rel="next"` headers. See [`Pages`].
+pub fn get_paginated<U: Into<String>>(url: U) -> Pages {
+    super::request(Method::Get, url).paginate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::{request, test::Server, Client, StatusCode};
+
+    #[test]
+    fn link_headers_are_parsed() {
+        let github = r#"<https://api.github.com/repositories/1/issues?page=2>; rel="next", <https://api.github.com/repositories/1/issues?page=9>; rel="last""#;
+        assert_eq!(next_link(github), Some("https://api.github.com/repositories/1/issues?page=2"));
+        assert_eq!(next_link(r#"<https://x.test/a?page=1>; rel="prev first", <https://x.test/a?page=3>; rel="next""#), Some("https://x.test/a?page=3"));
+        assert_eq!(next_link("</items?after=a,b>; title=\"more\"; REL=next"), Some("/items?after=a,b"));
+        assert_eq!(next_link(r#"<https://x.test/a?page=1>; rel="prev""#), None);
+        assert_eq!(next_link(""), None);
+    }
+
+    #[test]
+    fn pages_follow_link_headers() {
+        let server = Server::start().unwrap();
+        server.when("GET", "/items").header("Link", r#"</items?page=2>; rel="next""#).respond(200, "[1,2]");
+        server.when("GET", "/items?page=2").header("Link", format!(r#"<{}/items?page=3>; rel="next", </items>; rel="first""#, server.url())).respond(200, "[3,4]");
+        server.when("GET", "/items?page=3").header("Link", r#"</items>; rel="first""#).respond(200, "[5]");
+
+        let client = Client::new(server.url()).bearer("t");
+        let items: Vec<u32> = client.get_paginated("/items").flat_map(|page| page.unwrap().json::<Vec<u32>>().unwrap()).collect();
+        assert_eq!(items, [1, 2, 3, 4, 5]);
+        assert!(server.requests().iter().all(|request| request.header("Authorization") == Some("Bearer t")));
+
+        assert_eq!(client.get_paginated("/items").max_pages(2).count(), 2);
+    }
+
+    #[test]
+    fn pages_follow_cursors_and_stop_at_errors() {
+        let server = Server::start().unwrap();
+        server.when("GET", "/events?limit=2").respond(200, r#"{"items":[1,2],"meta":{"next":"c2"}}"#);
+        server.when("GET", "/events?limit=2&after=c2").respond(200, r#"{"items":[3],"meta":{"next":null}}"#);
+        server.when("GET", "/broken").header("Link", "</missing>; rel=next").respond(200, "");
+
+        let pages: Vec<_> = request(Method::Get, format!("{}/events", server.url())).query("limit", 2).paginate().cursor("after", "meta.next").collect();
+        assert_eq!(pages.len(), 2);
+        let last: serde_json::Value = pages.into_iter().last().unwrap().unwrap().json().unwrap();
+        assert_eq!(last["items"], serde_json::json!([3]));
+
+        let mut pages = get_paginated(format!("{}/broken", server.url()));
+        assert!(pages.next().unwrap().is_ok());
+        assert_eq!(pages.next().unwrap().unwrap_err().status(), Some(StatusCode::NotFound));
+        assert!(pages.next().is_none());
+    }
+}
@@ -0,0 +1,124 @@
+//! Fetching many URLs at once.
+
+use super::{Client, Error, Response};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+impl Client {
+    /// Sends GET requests to `paths`, at most `concurrency` at a time, like [`get_all`](super::get_all).
+    pub fn get_all<P: AsRef<str> + Sync>(&self, paths: &[P], concurrency: usize) -> Vec<Result<Response, Error>> {
+        fetch_all(self, paths, concurrency, None)
+    }
+}
+
+/// Fetches `urls` on up to `concurrency` threads, showing how many are done on `bar`.
+fn fetch_all<U: AsRef<str> + Sync>(client: &Client, urls: &[U], concurrency: usize, mut bar: Option<crate::snips::ProgressBar>) -> Vec<Result<Response, Error>> {
+    let next = AtomicUsize::new(0);
+    let transport = super::transport::test_transport();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let mut results: Vec<Option<Result<Response, Error>>> = urls.iter().map(|_| None).collect();
+
+    std::thread::scope(|scope| {
+        for _ in 0..concurrency.clamp(1, urls.len().max(1)) {
+            let (next, transport, sender) = (&next, transport.clone(), sender.clone());
+            scope.spawn(move || {
+                super::transport::inherit(transport);
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let url = match urls.get(index) {
+                        Some(url) => url.as_ref(),
+                        None => break,
+                    };
+                    if sender.send((index, client.get(url))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        for (index, result) in receiver {
+            results[index] = Some(result);
+            if let Some(bar) = &mut bar {
+                bar.inc(1);
+            }
+        }
+    });
+
+    if let Some(bar) = &mut bar {
+        bar.finish();
+    }
+    results.into_iter().map(|result| result.expect("every URL is fetched")).collect()
+}
+
+/// Sends GET requests to `urls`, at most `concurrency` at a time, and returns the results in the
+/// same order as `urls`. A failed request doesn't stop the others.
+///
+/// ```rs
+/// let urls: Vec<String> = ids.iter().map(|id| format!("https://api.example.com/items/{}", id)).collect();
+/// for (id, result) in ids.iter().zip(net::get_all(&urls, 8)) {
+///     match result {
+///         Ok(response) => save(id, response.json::<Item>()?),
+///         Err(err) => swarn!("Skipping {}: {}", id, err),
+///     }
+/// }
+/// ```
+pub fn get_all<U: AsRef<str> + Sync>(urls: &[U], concurrency: usize) -> Vec<Result<Response, Error>> {
+    fetch_all(super::client::default_client(), urls, concurrency, None)
+}
+
+/// Like [`get_all`], showing a [`ProgressBar`](crate::snips::ProgressBar) of the requests done so far.
+pub fn get_all_with_progress<U: AsRef<str> + Sync>(urls: &[U], concurrency: usize) -> Vec<Result<Response, Error>> {
+    let bar = crate::snips::ProgressBar::new(urls.len() as u64);
+    fetch_all(super::client::default_client(), urls, concurrency, Some(bar))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::{set_transport_for_tests, test::Server, TransportRequest};
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn results_keep_the_order_of_the_urls() {
+        let server = Server::start().unwrap();
+        let urls: Vec<String> = (0..20).map(|i| format!("{}/item/{}", server.url(), i)).collect();
+        for i in 0..20 {
+            if i != 7 {
+                server.when("GET", format!("/item/{}", i)).respond(200, i.to_string());
+            }
+        }
+
+        let results = get_all(&urls, 4);
+        assert_eq!(results.len(), 20);
+        for (i, result) in results.into_iter().enumerate() {
+            match i {
+                7 => assert_eq!(result.unwrap_err().status(), Some(crate::net::StatusCode::NotFound)),
+                i => assert_eq!(result.unwrap().text().unwrap(), i.to_string()),
+            }
+        }
+        assert!(get_all::<&str>(&[], 4).is_empty());
+    }
+
+    #[test]
+    fn concurrency_is_bounded() {
+        let (running, peak) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let (counter, max) = (running.clone(), peak.clone());
+        let _guard = set_transport_for_tests(move |request: &TransportRequest| {
+            let now = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            max.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(20));
+            counter.fetch_sub(1, Ordering::SeqCst);
+            Ok(Response::new(200, request.url().to_string()))
+        });
+
+        let client = Client::new("https://api.test");
+        let paths: Vec<String> = (0..12).map(|i| format!("/{}", i)).collect();
+        let results = client.get_all(&paths, 3);
+
+        assert_eq!(results[11].as_ref().unwrap().url(), "https://api.test/11");
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+        assert!(peak.load(Ordering::SeqCst) > 1);
+    }
+}
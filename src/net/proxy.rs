@@ -0,0 +1,107 @@
+//! Picking the proxy for a request, from [`RequestBuilder::proxy`](super::RequestBuilder::proxy) or the environment.
+
+/// Where a request connects through.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) enum Proxy {
+    /// Use the proxy from the environment, if any.
+    #[default]
+    Env,
+    /// Connect directly, whatever the environment says.
+    Direct,
+    /// Connect through this proxy.
+    Url(String),
+}
+
+impl Proxy {
+    /// The proxy URL to use for `url`, if any.
+    pub(super) fn for_url(&self, url: &str) -> Option<String> {
+        match self {
+            Proxy::Env => from_env(url, |name| std::env::var(name).ok()),
+            Proxy::Direct => None,
+            Proxy::Url(proxy) => Some(proxy.clone()),
+        }
+    }
+}
+
+/// The proxy the environment sets for `url`, like curl picks it.
+///
+/// `http_proxy` is used for `http` URLs, `https_proxy` for `https` URLs, and `all_proxy` when the one
+/// for the scheme isn't set. The uppercase names work too.
+///
+/// `no_proxy` is a comma separated list of hosts to reach directly. An entry matches the host itself
+/// and all its subdomains, so `example.com` and `.example.com` both match `api.example.com`. Ports in
+/// entries are ignored, and `*` matches every host. IP ranges are not supported.
+fn from_env<F: Fn(&str) -> Option<String>>(url: &str, var: F) -> Option<String> {
+    let lookup = |name: &str| {
+        var(name)
+            .or_else(|| var(&name.to_ascii_uppercase()))
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    let url = super::Url::parse(url).ok()?;
+    if let Some(no_proxy) = lookup("no_proxy") {
+        if bypasses_proxy(url.host(), &no_proxy) {
+            return None;
+        }
+    }
+
+    let scheme = url.scheme().to_ascii_lowercase();
+    lookup(&format!("{}_proxy", scheme)).or_else(|| lookup("all_proxy"))
+}
+
+/// Returns true if `host` matches an entry of a `no_proxy` list.
+fn bypasses_proxy(host: &str, no_proxy: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']').trim_end_matches('.').to_ascii_lowercase();
+
+    no_proxy.split(',').map(str::trim).filter(|entry| !entry.is_empty()).any(|entry| {
+        if entry == "*" {
+            return true;
+        }
+
+        let mut entry = entry.to_ascii_lowercase();
+        if let Some((name, port)) = entry.rsplit_once(':') {
+            if port.parse::<u16>().is_ok() && (!name.contains(':') || name.ends_with(']')) {
+                entry.truncate(name.len());
+            }
+        }
+        let entry = entry.trim_start_matches("*.").trim_start_matches('.').trim_start_matches('[').trim_end_matches(']');
+
+        host == entry || host.strip_suffix(entry).is_some_and(|rest| rest.ends_with('.'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_proxy_matches_hosts_and_subdomains() {
+        let list = "localhost, .internal.corp,example.com:8080, 10.0.0.1, [::1]";
+        assert!(bypasses_proxy("localhost", list));
+        assert!(bypasses_proxy("git.internal.corp", list));
+        assert!(bypasses_proxy("internal.corp", list));
+        assert!(bypasses_proxy("API.Example.com", list));
+        assert!(bypasses_proxy("10.0.0.1", list));
+        assert!(bypasses_proxy("[::1]", list));
+        assert!(!bypasses_proxy("notexample.com", list));
+        assert!(!bypasses_proxy("10.0.0.10", list));
+        assert!(bypasses_proxy("anything", "*"));
+    }
+
+    #[test]
+    fn env_proxies_follow_the_scheme() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+        };
+
+        let vars = env(&[("http_proxy", "http://plain:3128"), ("HTTPS_PROXY", "http://secure:3128"), ("NO_PROXY", "local.dev")]);
+        assert_eq!(from_env("http://example.com", vars).as_deref(), Some("http://plain:3128"));
+        assert_eq!(from_env("https://example.com/path", vars).as_deref(), Some("http://secure:3128"));
+        assert_eq!(from_env("https://api.local.dev", vars), None);
+
+        let vars = env(&[("all_proxy", "socks5://fallback:1080"), ("https_proxy", " ")]);
+        assert_eq!(from_env("https://example.com", vars).as_deref(), Some("socks5://fallback:1080"));
+        assert_eq!(from_env("https://example.com", env(&[])), None);
+    }
+}
@@ -0,0 +1,113 @@
+//! Client-side rate limiting, so scripts stay under the limits of the APIs they call.
+
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// A token bucket allowing `requests` requests every `per`, with bursts of up to `requests`.
+///
+/// Clones share the same bucket, so one limiter can cover several requests or threads.
+///
+/// ```rs
+/// let limiter = RateLimiter::new(5, Duration::from_secs(1));
+/// for id in ids {
+///     net::request(Method::Get, format!("https://api.example.com/items/{}", id)).rate_limit(&limiter).send()?;
+/// }
+///
+/// // Or for every request
+/// net::set_rate_limit(Some(RateLimiter::new(10, Duration::from_secs(60))));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    capacity: f64,
+    per_second: f64,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(requests: u32, per: Duration) -> Self {
+        let capacity = requests.max(1) as f64;
+        RateLimiter {
+            capacity,
+            per_second: capacity / per.as_secs_f64().max(f64::EPSILON),
+            bucket: Arc::new(Mutex::new(Bucket { tokens: capacity, updated: Instant::now() })),
+        }
+    }
+
+    /// Takes a token, waiting until one is available.
+    pub fn acquire(&self) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            self.refill(&mut bucket);
+
+            // Going below zero reserves a token, so waiting threads are served in turn
+            bucket.tokens -= 1.0;
+            match bucket.tokens < 0.0 {
+                true => Duration::from_secs_f64(-bucket.tokens / self.per_second),
+                false => Duration::ZERO,
+            }
+        };
+
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+
+    /// Takes a token if one is available right away.
+    pub fn try_acquire(&self) -> bool {
+        let mut bucket = self.bucket.lock().unwrap();
+        self.refill(&mut bucket);
+
+        match bucket.tokens >= 1.0 {
+            true => {
+                bucket.tokens -= 1.0;
+                true
+            }
+            false => false,
+        }
+    }
+
+    fn refill(&self, bucket: &mut Bucket) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.capacity);
+        bucket.updated = now;
+    }
+}
+
+static RATE_LIMIT: RwLock<Option<RateLimiter>> = RwLock::new(None);
+
+/// Rate limits every request that doesn't set its own limiter with
+/// [`RequestBuilder::rate_limit`](super::RequestBuilder::rate_limit). Requests are not limited by default.
+pub fn set_rate_limit(limiter: Option<RateLimiter>) {
+    *RATE_LIMIT.write().unwrap() = limiter;
+}
+
+pub(super) fn global_rate_limit() -> Option<RateLimiter> {
+    RATE_LIMIT.read().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bursts_then_waits_for_tokens() {
+        let limiter = RateLimiter::new(3, Duration::from_millis(300));
+        assert!(limiter.try_acquire());
+        assert!(limiter.try_acquire());
+        assert!(limiter.clone().try_acquire());
+        assert!(!limiter.try_acquire());
+
+        let start = Instant::now();
+        limiter.acquire();
+        limiter.acquire();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(150) && elapsed < Duration::from_millis(600), "{:?}", elapsed);
+    }
+}
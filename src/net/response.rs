@@ -0,0 +1,267 @@
+//! The response to an HTTP request.
+
+use super::{Error, StatusCode};
+use std::io::{BufRead, BufReader, Read};
+use std::time::Duration;
+
+/// The response to an HTTP request. The body can be read once, with [`text`](Response::text),
+/// [`bytes`](Response::bytes) or [`json`](Response::json).
+///
+/// Bodies compressed with gzip, deflate or brotli are decompressed while reading them. The headers are kept
+/// as the server sent them, so `Content-Length` is the compressed size; see [`raw`](Response::raw)
+/// for reading the body as it was sent.
+///
+/// ```rs
+/// let response = net::try_get("https://example.com/data.json")?;
+/// sinfo!("{} ({:?})", response.status(), response.header("Content-Type"));
+/// let data: Data = response.json()?;
+/// ```
+pub struct Response {
+    status: StatusCode,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Box<dyn Read + Send + Sync>,
+    encoding: Option<Encoding>,
+}
+
+/// A `Content-Encoding` that responses are decompressed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Gzip,
+    Deflate,
+    Brotli,
+}
+
+impl Encoding {
+    fn parse(header: Option<&str>) -> Option<Encoding> {
+        match header?.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Encoding::Gzip),
+            "deflate" => Some(Encoding::Deflate),
+            "br" => Some(Encoding::Brotli),
+            _ => None,
+        }
+    }
+}
+
+/// The `Accept-Encoding` header sent with requests, listing the encodings responses are decompressed from.
+pub(super) const ACCEPT_ENCODING: &str = "gzip, deflate, br";
+
+impl Response {
+    pub(super) fn from_ureq(response: ureq::Response) -> Self {
+        // headers_names() lists a name once for every time it was sent
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for name in response.headers_names() {
+            if headers.iter().any(|(seen, _)| *seen == name) {
+                continue;
+            }
+            for value in response.all(&name) {
+                headers.push((name.clone(), value.to_string()));
+            }
+        }
+
+        let encoding = Encoding::parse(response.header("Content-Encoding"));
+        Response {
+            status: StatusCode::from(response.status()),
+            url: response.get_url().to_string(),
+            headers,
+            body: response.into_reader(),
+            encoding,
+        }
+    }
+
+    /// A response with `status` and `body`, e.g. for a [`Transport`](super::Transport) in tests.
+    /// Add headers with [`with_header`](Response::with_header).
+    pub fn new<S: Into<StatusCode>, B: Into<Vec<u8>>>(status: S, body: B) -> Self {
+        Response::from_parts(status.into(), String::new(), Vec::new(), body.into())
+    }
+
+    /// Adds a header. A `Content-Encoding` set this way is not decoded, since the body is given as is.
+    pub fn with_header<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.headers.push((name.into().to_ascii_lowercase(), value.into()));
+        self
+    }
+
+    /// The same response, as the answer to a request to `url`.
+    pub(super) fn with_url(mut self, url: &str) -> Self {
+        self.url = url.to_string();
+        self
+    }
+
+    /// A response with a body already in memory and decompressed.
+    pub(super) fn from_parts(status: StatusCode, url: String, headers: Vec<(String, String)>, body: Vec<u8>) -> Self {
+        Response { status, url, headers, body: Box::new(std::io::Cursor::new(body)), encoding: None }
+    }
+
+    /// A response whose body is still compressed as its `Content-Encoding` header says.
+    pub(super) fn encoded<R: Read + Send + Sync + 'static>(status: StatusCode, url: String, headers: Vec<(String, String)>, body: R) -> Self {
+        let encoding = headers.iter().find(|(name, _)| name.eq_ignore_ascii_case("Content-Encoding")).and_then(|(_, value)| Encoding::parse(Some(value)));
+        Response { status, url, headers, body: Box::new(body), encoding }
+    }
+
+    /// Wraps the body as it was sent, before it is decompressed.
+    pub(super) fn map_body<F: FnOnce(Box<dyn Read + Send + Sync>) -> Box<dyn Read + Send + Sync>>(mut self, f: F) -> Self {
+        self.body = f(self.body);
+        self
+    }
+
+    /// The status the server answered with.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns true if the status is 2xx.
+    pub fn ok(&self) -> bool {
+        self.status.is_success()
+    }
+
+    /// The URL of the response, which differs from the requested one after redirects.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The value of the first header called `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    /// How long the server asked to wait before trying again, from the `Retry-After` header.
+    /// Dates in the past give zero.
+    pub fn retry_after(&self) -> Option<Duration> {
+        let value = self.header("Retry-After")?.trim();
+        if let Ok(seconds) = value.parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+
+        let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+        Some((date.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().unwrap_or(Duration::ZERO))
+    }
+
+    /// The size of the body from the `Content-Length` header, or None if the server didn't send it
+    /// or the body is compressed, since it decompresses to a different size.
+    pub fn content_length(&self) -> Option<u64> {
+        match self.encoding {
+            Some(_) => None,
+            None => self.header("Content-Length").and_then(|length| length.trim().parse().ok()),
+        }
+    }
+
+    /// Stops decompressing the body, so it is read exactly as the server sent it.
+    ///
+    /// ```rs
+    /// // Keep the server's gzip file as it is
+    /// let compressed = net::try_get(url)?.raw().bytes()?;
+    /// ```
+    pub fn raw(mut self) -> Self {
+        self.encoding = None;
+        self
+    }
+
+    /// Every header, in the order the server sent them. Names are lowercase.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Reads the body as UTF-8 text.
+    pub fn text(self) -> Result<String, Error> {
+        let bytes = self.bytes()?;
+        String::from_utf8(bytes).map_err(|err| Error::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, err)))
+    }
+
+    /// Reads the body as bytes.
+    pub fn bytes(self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        self.into_reader().read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Deserializes the body as JSON. If it doesn't fit `T`, the error shows a snippet of the body.
+    pub fn json<T: serde::de::DeserializeOwned>(self) -> Result<T, Error> {
+        let url = self.url.clone();
+        let text = self.text()?;
+        super::http::parse_json(&url, &text)
+    }
+
+    /// The body as a reader, for processing large bodies as they arrive.
+    ///
+    /// ```rs
+    /// let mut file = std::fs::File::create("dump.sql")?;
+    /// std::io::copy(&mut net::try_get(url)?.into_reader(), &mut file)?;
+    /// ```
+    pub fn into_reader(self) -> Box<dyn Read + Send + Sync> {
+        match self.encoding {
+            Some(encoding) => Box::new(Decoder { encoding, source: Some(BufReader::new(self.body)), decoder: None }),
+            None => self.body,
+        }
+    }
+
+    /// Calls `f` with each chunk of the body as it arrives, returning the total size.
+    /// Stops at the first error `f` returns.
+    ///
+    /// ```rs
+    /// let mut lines = 0;
+    /// net::try_get(url)?.stream_chunks(|chunk| {
+    ///     lines += chunk.iter().filter(|&&byte| byte == b'\n').count();
+    ///     Ok(())
+    /// })?;
+    /// ```
+    pub fn stream_chunks<F: FnMut(&[u8]) -> std::io::Result<()>>(self, mut f: F) -> Result<u64, Error> {
+        let mut body = self.into_reader();
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut total = 0u64;
+
+        loop {
+            let read = match body.read(&mut buffer) {
+                Ok(0) => return Ok(total),
+                Ok(read) => read,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            };
+
+            f(&buffer[..read])?;
+            total += read as u64;
+        }
+    }
+}
+
+impl std::fmt::Debug for Response {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Response")
+            .field("status", &self.status)
+            .field("url", &self.url)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Decompresses a body. The decoder is picked on the first read, so empty bodies, e.g. for HEAD
+/// requests, stay empty instead of failing as truncated streams.
+struct Decoder {
+    encoding: Encoding,
+    source: Option<BufReader<Box<dyn Read + Send + Sync>>>,
+    decoder: Option<Box<dyn Read + Send + Sync>>,
+}
+
+impl Read for Decoder {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let Some(mut source) = self.source.take() {
+            let head = source.fill_buf()?;
+            if head.is_empty() {
+                return Ok(0);
+            }
+
+            // "deflate" should be zlib data, but some servers send raw deflate streams
+            let zlib = head.len() >= 2 && head[0] & 0x0f == 8 && u16::from_be_bytes([head[0], head[1]]) % 31 == 0;
+            self.decoder = Some(match self.encoding {
+                Encoding::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(source)),
+                Encoding::Deflate if zlib => Box::new(flate2::bufread::ZlibDecoder::new(source)),
+                Encoding::Deflate => Box::new(flate2::bufread::DeflateDecoder::new(source)),
+                Encoding::Brotli => Box::new(brotli_decompressor::Decompressor::new(source, 4096)),
+            });
+        }
+
+        match &mut self.decoder {
+            Some(decoder) => decoder.read(buf),
+            None => Ok(0),
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Small clients for REST APIs, with path templates.
+
+use super::{Client, Error, Method, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A client for a JSON REST API, whose endpoints are path templates like `/users/{id}`.
+///
+/// Each call takes the parameters as anything serializable to a JSON object: parameters named in
+/// the template fill in its placeholders, percent-encoded, and the rest are sent as the query string.
+/// `None` parameters are left out. Bodies are sent and answers deserialized as JSON.
+///
+/// ```rs
+/// let github = Rest::new("https://api.github.com").auth(token);
+///
+/// let repo: Repo = github.get("/repos/{owner}/{name}", &json!({ "owner": "rust-lang", "name": "rust" }))?;
+/// let issues: Vec<Issue> = github.get("/repos/{owner}/{name}/issues", &json!({ "owner": "rust-lang", "name": "rust", "state": "open" }))?;
+/// github.post::<Comment, _, _>("/repos/{owner}/{name}/issues/{number}/comments", &params, &json!({ "body": "On it" }))?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Rest {
+    client: Client,
+}
+
+impl From<Client> for Rest {
+    /// A REST client sending its requests with `client`, and its settings.
+    fn from(client: Client) -> Self {
+        Rest { client }
+    }
+}
+
+impl Rest {
+    /// A client for the API at `base_url`.
+    pub fn new<U: Into<String>>(base_url: U) -> Self {
+        Rest::from(Client::new(base_url).header("Accept", "application/json"))
+    }
+
+    /// Sends `token` as a bearer token with every request.
+    pub fn auth<T: std::fmt::Display>(self, token: T) -> Self {
+        Rest { client: self.client.bearer(token) }
+    }
+
+    /// Sends a header with every request.
+    pub fn header<K: Into<String>, V: Into<String>>(self, name: K, value: V) -> Self {
+        Rest { client: self.client.header(name, value) }
+    }
+
+    /// The client the requests are sent with.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Starts building a request to the endpoint `path` with `params`, for when more control is needed.
+    pub fn request<P: Serialize>(&self, method: Method, path: &str, params: &P) -> Result<RequestBuilder, Error> {
+        let params = serde_json::to_value(params).map_err(|err| Error::InvalidUrl { url: path.to_string(), reason: format!("bad parameters: {}", err) })?;
+        let (path, query) = expand(path, params).map_err(|reason| Error::InvalidUrl { url: path.to_string(), reason })?;
+
+        let mut request = self.client.request(method, &path);
+        for (key, value) in query {
+            request = request.query(key, value);
+        }
+        Ok(request)
+    }
+
+    fn call<T: DeserializeOwned, P: Serialize>(&self, method: Method, path: &str, params: &P, body: Option<serde_json::Value>) -> Result<T, Error> {
+        let mut request = self.request(method, path, params)?;
+        if let Some(body) = body {
+            request = request.body(body);
+        }
+
+        let response = request.send()?;
+        let url = response.url().to_string();
+        let text = response.text()?;
+        // Lets endpoints answering 204 No Content be called with `()` as `T`
+        let text = if text.trim().is_empty() { "null" } else { &text };
+        super::http::parse_json(&url, text)
+    }
+
+    fn json<B: Serialize>(&self, path: &str, body: &B) -> Result<serde_json::Value, Error> {
+        serde_json::to_value(body).map_err(|err| Error::Json { url: self.client.url(path), reason: err.to_string(), snippet: String::new() })
+    }
+
+    /// Sends a GET request to the endpoint `path`.
+    pub fn get<T: DeserializeOwned, P: Serialize>(&self, path: &str, params: &P) -> Result<T, Error> {
+        self.call(Method::Get, path, params, None)
+    }
+
+    /// Sends `body` in a POST request to the endpoint `path`.
+    pub fn post<T: DeserializeOwned, P: Serialize, B: Serialize>(&self, path: &str, params: &P, body: &B) -> Result<T, Error> {
+        self.call(Method::Post, path, params, Some(self.json(path, body)?))
+    }
+
+    /// Sends `body` in a PUT request to the endpoint `path`.
+    pub fn put<T: DeserializeOwned, P: Serialize, B: Serialize>(&self, path: &str, params: &P, body: &B) -> Result<T, Error> {
+        self.call(Method::Put, path, params, Some(self.json(path, body)?))
+    }
+
+    /// Sends `body` in a PATCH request to the endpoint `path`.
+    pub fn patch<T: DeserializeOwned, P: Serialize, B: Serialize>(&self, path: &str, params: &P, body: &B) -> Result<T, Error> {
+        self.call(Method::Patch, path, params, Some(self.json(path, body)?))
+    }
+
+    /// Sends a DELETE request to the endpoint `path`.
+    pub fn delete<T: DeserializeOwned, P: Serialize>(&self, path: &str, params: &P) -> Result<T, Error> {
+        self.call(Method::Delete, path, params, None)
+    }
+}
+
+/// A parameter as it goes in a URL, or None for null.
+fn param_text(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(text) => Some(text.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+/// Fills in the `{name}` placeholders of `template` from `params`, returning the path and the
+/// parameters left for the query string. Arrays become one query parameter per item.
+fn expand(template: &str, params: serde_json::Value) -> Result<(String, Vec<(String, String)>), String> {
+    let mut params = match params {
+        serde_json::Value::Object(params) => params,
+        serde_json::Value::Null => serde_json::Map::new(),
+        _ => return Err("parameters must serialize to an object".to_string()),
+    };
+
+    let mut path = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = start + rest[start..].find('}').ok_or("unclosed `{` in the path")?;
+        let name = &rest[start + 1..end];
+        let value = params.remove(name).as_ref().and_then(param_text).ok_or_else(|| format!("no value for `{{{}}}`", name))?;
+
+        path.push_str(&rest[..start]);
+        path.push_str(&super::encode(&value));
+        rest = &rest[end + 1..];
+    }
+    path.push_str(rest);
+
+    let mut query = Vec::new();
+    for (key, value) in params {
+        match value {
+            serde_json::Value::Array(items) => query.extend(items.iter().filter_map(param_text).map(|item| (key.clone(), item))),
+            value => query.extend(param_text(&value).map(|value| (key.clone(), value))),
+        }
+    }
+    Ok((path, query))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::test::Server;
+    use serde_json::json;
+
+    #[test]
+    fn templates_fill_the_path_and_query() {
+        let (path, query) = expand("/repos/{owner}/{name}/issues", json!({ "owner": "rust-lang", "name": "a b/c", "state": "open", "labels": ["bug", "ui"], "since": null, "page": 2 })).unwrap();
+        assert_eq!(path, "/repos/rust-lang/a%20b%2Fc/issues");
+        assert_eq!(query, [("labels", "bug"), ("labels", "ui"), ("page", "2"), ("state", "open")].map(|(key, value)| (key.to_string(), value.to_string())));
+
+        assert_eq!(expand("/health", serde_json::Value::Null).unwrap(), ("/health".to_string(), Vec::new()));
+        assert_eq!(expand("/users/{id}", json!({})).unwrap_err(), "no value for `{id}`");
+        assert_eq!(expand("/users/{id", json!({ "id": 1 })).unwrap_err(), "unclosed `{` in the path");
+        assert!(expand("/users", json!([1])).is_err());
+    }
+
+    #[test]
+    fn endpoints_are_called_with_json() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct User {
+            id: u32,
+            name: String,
+        }
+
+        let server = Server::start().unwrap();
+        server.when("GET", "/v1/users/42").respond_json(200, &json!({ "id": 42, "name": "fox" }));
+        server.when("PATCH", "/v1/users/42").respond_json(200, &json!({ "id": 42, "name": "vixen" }));
+        server.when("DELETE", "/v1/users/42").respond(204, "");
+
+        let api = Rest::new(format!("{}/v1", server.url())).auth("secret");
+        let user: User = api.get("/users/{id}", &json!({ "id": 42, "fields": "name" })).unwrap();
+        assert_eq!(user, User { id: 42, name: "fox".to_string() });
+
+        let user: User = api.patch("/users/{id}", &json!({ "id": 42 }), &json!({ "name": "vixen" })).unwrap();
+        assert_eq!(user.name, "vixen");
+        api.delete::<(), _>("/users/{id}", &json!({ "id": 42 })).unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/v1/users/42?fields=name");
+        assert_eq!(requests[0].header("Authorization"), Some("Bearer secret"));
+        assert_eq!(requests[0].header("Accept"), Some("application/json"));
+        assert_eq!(requests[1].body_text(), r#"{"name":"vixen"}"#);
+        assert!(matches!(api.get::<User, _>("/users/{id}", &()), Err(Error::InvalidUrl { .. })));
+    }
+}
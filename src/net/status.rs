@@ -0,0 +1,160 @@
+//! HTTP status codes, from the IANA registry (RFC 9110 and its extensions).
+
+macro_rules! status_codes {
+    ($($name:ident = $code:literal, $reason:literal;)*) => {
+        /// An HTTP status code, with names for every registered code.
+        ///
+        /// ```rs
+        /// let status = StatusCode::from(404);
+        /// assert_eq!(status, StatusCode::NotFound);
+        /// assert!(status.is_client_error());
+        /// assert_eq!(status.to_string(), "404 Not Found");
+        /// ```
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum StatusCode {
+            $($name,)*
+            /// A code without a registered meaning.
+            Other(u16),
+        }
+
+        impl StatusCode {
+            /// The numeric code.
+            pub fn as_u16(self) -> u16 {
+                match self {
+                    $(StatusCode::$name => $code,)*
+                    StatusCode::Other(code) => code,
+                }
+            }
+
+            /// The standard reason phrase, e.g. `Not Found`. None for unregistered codes.
+            pub fn canonical_reason(self) -> Option<&'static str> {
+                match self {
+                    $(StatusCode::$name => Some($reason),)*
+                    StatusCode::Other(_) => None,
+                }
+            }
+        }
+
+        impl From<u16> for StatusCode {
+            fn from(code: u16) -> Self {
+                match code {
+                    $($code => StatusCode::$name,)*
+                    code => StatusCode::Other(code),
+                }
+            }
+        }
+    };
+}
+
+status_codes! {
+    Continue = 100, "Continue";
+    SwitchingProtocols = 101, "Switching Protocols";
+    Processing = 102, "Processing";
+    EarlyHints = 103, "Early Hints";
+    Ok = 200, "OK";
+    Created = 201, "Created";
+    Accepted = 202, "Accepted";
+    NonAuthoritativeInformation = 203, "Non-Authoritative Information";
+    NoContent = 204, "No Content";
+    ResetContent = 205, "Reset Content";
+    PartialContent = 206, "Partial Content";
+    MultiStatus = 207, "Multi-Status";
+    AlreadyReported = 208, "Already Reported";
+    ImUsed = 226, "IM Used";
+    MultipleChoices = 300, "Multiple Choices";
+    MovedPermanently = 301, "Moved Permanently";
+    Found = 302, "Found";
+    SeeOther = 303, "See Other";
+    NotModified = 304, "Not Modified";
+    UseProxy = 305, "Use Proxy";
+    TemporaryRedirect = 307, "Temporary Redirect";
+    PermanentRedirect = 308, "Permanent Redirect";
+    BadRequest = 400, "Bad Request";
+    Unauthorized = 401, "Unauthorized";
+    PaymentRequired = 402, "Payment Required";
+    Forbidden = 403, "Forbidden";
+    NotFound = 404, "Not Found";
+    MethodNotAllowed = 405, "Method Not Allowed";
+    NotAcceptable = 406, "Not Acceptable";
+    ProxyAuthenticationRequired = 407, "Proxy Authentication Required";
+    RequestTimeout = 408, "Request Timeout";
+    Conflict = 409, "Conflict";
+    Gone = 410, "Gone";
+    LengthRequired = 411, "Length Required";
+    PreconditionFailed = 412, "Precondition Failed";
+    PayloadTooLarge = 413, "Payload Too Large";
+    UriTooLong = 414, "URI Too Long";
+    UnsupportedMediaType = 415, "Unsupported Media Type";
+    RangeNotSatisfiable = 416, "Range Not Satisfiable";
+    ExpectationFailed = 417, "Expectation Failed";
+    ImATeapot = 418, "I'm a teapot";
+    MisdirectedRequest = 421, "Misdirected Request";
+    UnprocessableEntity = 422, "Unprocessable Entity";
+    Locked = 423, "Locked";
+    FailedDependency = 424, "Failed Dependency";
+    TooEarly = 425, "Too Early";
+    UpgradeRequired = 426, "Upgrade Required";
+    PreconditionRequired = 428, "Precondition Required";
+    TooManyRequests = 429, "Too Many Requests";
+    RequestHeaderFieldsTooLarge = 431, "Request Header Fields Too Large";
+    UnavailableForLegalReasons = 451, "Unavailable For Legal Reasons";
+    InternalServerError = 500, "Internal Server Error";
+    NotImplemented = 501, "Not Implemented";
+    BadGateway = 502, "Bad Gateway";
+    ServiceUnavailable = 503, "Service Unavailable";
+    GatewayTimeout = 504, "Gateway Timeout";
+    HttpVersionNotSupported = 505, "HTTP Version Not Supported";
+    VariantAlsoNegotiates = 506, "Variant Also Negotiates";
+    InsufficientStorage = 507, "Insufficient Storage";
+    LoopDetected = 508, "Loop Detected";
+    NotExtended = 510, "Not Extended";
+    NetworkAuthenticationRequired = 511, "Network Authentication Required";
+}
+
+impl StatusCode {
+    /// 1xx codes.
+    pub fn is_informational(self) -> bool {
+        (100..200).contains(&self.as_u16())
+    }
+
+    /// 2xx codes.
+    pub fn is_success(self) -> bool {
+        (200..300).contains(&self.as_u16())
+    }
+
+    /// 3xx codes.
+    pub fn is_redirection(self) -> bool {
+        (300..400).contains(&self.as_u16())
+    }
+
+    /// 4xx codes.
+    pub fn is_client_error(self) -> bool {
+        (400..500).contains(&self.as_u16())
+    }
+
+    /// 5xx codes.
+    pub fn is_server_error(self) -> bool {
+        (500..600).contains(&self.as_u16())
+    }
+}
+
+impl From<StatusCode> for u16 {
+    fn from(status: StatusCode) -> Self {
+        status.as_u16()
+    }
+}
+
+impl std::fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.canonical_reason() {
+            Some(reason) => write!(f, "{} {}", self.as_u16(), reason),
+            None => write!(f, "{}", self.as_u16()),
+        }
+    }
+}
+
+impl PartialEq<u16> for StatusCode {
+    fn eq(&self, code: &u16) -> bool {
+        self.as_u16() == *code
+    }
+}
@@ -0,0 +1,156 @@
+//! A small TCP client for scripting line- and byte-oriented protocols.
+//!
+//! ```rs
+//! let mut conn = net::tcp::connect("localhost:6379")?;
+//! conn.send("PING\r\n")?;
+//! let reply = conn.recv_until(b"\r\n")?;
+//! ```
+
+use crate::{sdebug, serror};
+use crate as fox;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// The timeout used by [`connect`] for connecting, sending and receiving.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A connected TCP stream with buffered, timeout-aware reads.
+///
+/// Traffic is logged as a hexdump at debug level.
+pub struct Connection {
+    stream: TcpStream,
+    peer: SocketAddr,
+    buffer: Vec<u8>,
+}
+
+/// Connects to `addr` with the [`DEFAULT_TIMEOUT`].
+pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<Connection, std::io::Error> {
+    connect_timeout(addr, DEFAULT_TIMEOUT)
+}
+
+/// Connects to `addr`, trying each resolved address in turn. `timeout` also applies to every send and receive.
+pub fn connect_timeout<A: ToSocketAddrs>(addr: A, timeout: Duration) -> Result<Connection, std::io::Error> {
+    let addrs: Vec<SocketAddr> = addr.to_socket_addrs().inspect_err(|err| serror!("Failed to resolve address: {}", err))?.collect();
+    let mut last_error = std::io::Error::new(std::io::ErrorKind::InvalidInput, "address resolved to nothing");
+
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                sdebug!("Connected to {}.", addr);
+                return Ok(Connection { stream, peer: addr, buffer: Vec::new() });
+            }
+            Err(err) => {
+                sdebug!("Could not connect to {}: {}", addr, err);
+                last_error = err;
+            }
+        }
+    }
+
+    serror!("Failed to connect: {}", last_error);
+    Err(last_error)
+}
+
+impl Connection {
+    /// The address of the remote end.
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// Changes the send and receive timeout. `None` blocks forever.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<(), std::io::Error> {
+        self.stream.set_read_timeout(timeout)?;
+        self.stream.set_write_timeout(timeout)
+    }
+
+    /// Sends all of `data`.
+    pub fn send<D: AsRef<[u8]>>(&mut self, data: D) -> Result<(), std::io::Error> {
+        let data = data.as_ref();
+        log_traffic("->", self.peer, data);
+        self.stream.write_all(data).inspect_err(|err| serror!("Failed to send to {}: {}", self.peer, err))
+    }
+
+    /// Receives until `delimiter` is seen, returning everything up to and including it.
+    ///
+    /// Bytes received after the delimiter are kept for the next call.
+    pub fn recv_until<D: AsRef<[u8]>>(&mut self, delimiter: D) -> Result<Vec<u8>, std::io::Error> {
+        let delimiter = delimiter.as_ref();
+        if delimiter.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut searched = 0;
+        loop {
+            if let Some(index) = self.buffer[searched..].windows(delimiter.len()).position(|window| window == delimiter) {
+                let end = searched + index + delimiter.len();
+                return Ok(self.buffer.drain(..end).collect());
+            }
+
+            searched = self.buffer.len().saturating_sub(delimiter.len() - 1);
+            self.fill()?;
+        }
+    }
+
+    /// Receives until a `\n`, returning the line without its line ending.
+    pub fn recv_line(&mut self) -> Result<String, std::io::Error> {
+        let line = self.recv_until(b"\n")?;
+        let line = String::from_utf8_lossy(&line);
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    /// Receives exactly `count` bytes.
+    pub fn recv_exact(&mut self, count: usize) -> Result<Vec<u8>, std::io::Error> {
+        while self.buffer.len() < count {
+            self.fill()?;
+        }
+
+        Ok(self.buffer.drain(..count).collect())
+    }
+
+    /// Returns the underlying stream. Bytes that were received but not yet returned are lost.
+    pub fn into_inner(self) -> TcpStream {
+        self.stream
+    }
+
+    /// Reads whatever is available into the buffer, failing on end of stream.
+    fn fill(&mut self) -> Result<(), std::io::Error> {
+        let mut chunk = [0u8; 8192];
+        let read = match self.stream.read(&mut chunk) {
+            Ok(0) => Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "connection closed by peer")),
+            Ok(read) => Ok(read),
+            Err(err) => Err(err),
+        }
+        .inspect_err(|err| serror!("Failed to receive from {}: {}", self.peer, err))?;
+
+        log_traffic("<-", self.peer, &chunk[..read]);
+        self.buffer.extend_from_slice(&chunk[..read]);
+        Ok(())
+    }
+}
+
+fn log_traffic(direction: &str, peer: SocketAddr, data: &[u8]) {
+    if fox::log::should_log(fox::log::LogLevel::Debug) {
+        sdebug!("{} {} ({} bytes)\n{}", direction, peer, data.len(), hexdump(data));
+    }
+}
+
+/// Formats bytes as a classic hexdump: offset, 16 hex bytes and their printable ASCII.
+pub fn hexdump(data: &[u8]) -> String {
+    let mut dump = String::new();
+
+    for (i, line) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = line.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let ascii: String = line.iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect();
+
+        if i > 0 {
+            dump.push('\n');
+        }
+        dump.push_str(&format!("{:08x}  {:<47}  |{}|", i * 16, hex.join(" "), ascii));
+    }
+
+    dump
+}
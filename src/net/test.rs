@@ -0,0 +1,256 @@
+//! An embedded HTTP server for testing code that talks to HTTP services, without going online.
+//!
+//! ```rs
+//! let server = net::test::Server::start()?;
+//! server.when("GET", "/health").respond(200, "ok");
+//! server.when("POST", "/users").header("Content-Type", "application/json").respond(201, r#"{"id":1}"#);
+//!
+//! run_client_against(&server.url());
+//!
+//! assert_eq!(server.hits("POST", "/users"), 1);
+//! assert_eq!(server.requests()[0].body_text(), r#"{"name":"fox"}"#);
+//! ```
+
+use super::StatusCode;
+use crate::{sdebug, serror};
+use crate as fox;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A request received by a [`Server`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// The request target, including any query string.
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    /// Returns the value of the first header named `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    /// The body as text, with invalid UTF-8 replaced.
+    pub fn body_text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Route {
+    method: String,
+    path: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Route {
+    /// Routes without a query string match any query.
+    fn matches(&self, method: &str, target: &str) -> bool {
+        let target = if self.path.contains('?') { target } else { target.split('?').next().unwrap_or(target) };
+        self.method.eq_ignore_ascii_case(method) && self.path == target
+    }
+}
+
+#[derive(Default)]
+struct State {
+    routes: Vec<Route>,
+    requests: Vec<RecordedRequest>,
+}
+
+/// A local HTTP server on an ephemeral port that answers with canned responses and records every request.
+///
+/// Requests that match no route get a 404. The server stops when dropped.
+pub struct Server {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    running: Arc<AtomicBool>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Server {
+    /// Binds to an ephemeral port on localhost and starts serving in the background.
+    pub fn start() -> Result<Server, std::io::Error> {
+        let listener = TcpListener::bind("127.0.0.1:0").inspect_err(|err| serror!("Failed to start test server: {}", err))?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let handle = {
+            let state = Arc::clone(&state);
+            let running = Arc::clone(&running);
+
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if !running.load(Ordering::Relaxed) {
+                        break;
+                    }
+
+                    if let Ok(stream) = stream {
+                        let state = Arc::clone(&state);
+                        std::thread::spawn(move || handle_connection(stream, &state));
+                    }
+                }
+            })
+        };
+
+        sdebug!("Test server listening on {}.", addr);
+        Ok(Server { addr, state, running, handle: Some(handle) })
+    }
+
+    /// The address the server is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The base URL of the server, e.g. `http://127.0.0.1:41234`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Starts defining the response for `method` and `path`. Routes registered later take precedence.
+    pub fn when<M: Into<String>, P: Into<String>>(&self, method: M, path: P) -> When<'_> {
+        When { server: self, method: method.into(), path: path.into(), headers: Vec::new() }
+    }
+
+    /// Every request received so far, in order.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// How many requests were received for `method` and `path`, using the same matching as routes.
+    pub fn hits(&self, method: &str, path: &str) -> usize {
+        let probe = Route { method: method.into(), path: path.into(), status: 0, headers: Vec::new(), body: Vec::new() };
+        self.state.lock().unwrap().requests.iter().filter(|request| probe.matches(&request.method, &request.path)).count()
+    }
+
+    /// Forgets all recorded requests, keeping the routes.
+    pub fn clear_requests(&self) {
+        self.state.lock().unwrap().requests.clear();
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+
+        // Wake the accept loop so it sees the flag
+        let _ = TcpStream::connect_timeout(&self.addr, Duration::from_secs(1));
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// A route being defined with [`Server::when`].
+pub struct When<'a> {
+    server: &'a Server,
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+}
+
+impl When<'_> {
+    /// Adds a header to the response.
+    pub fn header<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Registers the route, answering with `status` and `body`.
+    pub fn respond<B: Into<Vec<u8>>>(self, status: u16, body: B) {
+        let route = Route { method: self.method, path: self.path, status, headers: self.headers, body: body.into() };
+        self.server.state.lock().unwrap().routes.push(route);
+    }
+
+    /// Registers the route, answering with `status` and `value` serialized as JSON.
+    pub fn respond_json<T: serde::Serialize>(self, status: u16, value: &T) {
+        let body = serde_json::to_vec(value).unwrap_or_default();
+        self.header("Content-Type", "application/json").respond(status, body);
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, state: &Mutex<State>) {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+
+    let request = match read_request(&mut stream) {
+        Some(request) => request,
+        None => return,
+    };
+
+    let route = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(request.clone());
+        state.routes.iter().rev().find(|route| route.matches(&request.method, &request.path)).cloned()
+    };
+
+    let (status, headers, body) = match route {
+        Some(route) => (route.status, route.headers, route.body),
+        None => (404, Vec::new(), b"no route matched".to_vec()),
+    };
+
+    sdebug!("Test server: {} {} -> {}", request.method, request.path, status);
+
+    let reason = StatusCode::from(status).canonical_reason().unwrap_or("");
+    let mut response = format!("HTTP/1.1 {} {}\r\n", status, reason);
+    for (name, value) in &headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", body.len()));
+
+    let mut bytes = response.into_bytes();
+    if request.method != "HEAD" {
+        bytes.extend_from_slice(&body);
+    }
+    let _ = stream.write_all(&bytes);
+}
+
+fn read_request(stream: &mut TcpStream) -> Option<RecordedRequest> {
+    let mut data = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let head_end = loop {
+        if let Some(index) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+            break index;
+        }
+
+        match stream.read(&mut chunk) {
+            Ok(0) | Err(_) => return None,
+            Ok(read) => data.extend_from_slice(&chunk[..read]),
+        }
+    };
+
+    let head = String::from_utf8_lossy(&data[..head_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    let length = headers.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("Content-Length"))
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let mut body = data.split_off(head_end + 4);
+    while body.len() < length {
+        match stream.read(&mut chunk) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => body.extend_from_slice(&chunk[..read]),
+        }
+    }
+    body.truncate(length);
+
+    Some(RecordedRequest { method, path, headers, body })
+}
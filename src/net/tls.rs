@@ -0,0 +1,292 @@
+//! TLS settings for HTTPS requests: private root CAs, client certificates, and skipping verification.
+
+use super::Error;
+use crate::swarn;
+use crate as fox;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// TLS settings for HTTPS requests, for services with a private CA or that ask for a client certificate.
+///
+/// The usual public CAs stay trusted when adding root certificates.
+///
+/// ```rs
+/// let tls = Tls::new()
+///     .root_certificate("/etc/corp/ca.pem")?
+///     .client_certificate("client.pem", "client.key")?;
+///
+/// let response = net::request(Method::Get, "https://build.corp/api/status").tls(tls.clone()).send()?;
+///
+/// // Or for every request
+/// net::set_tls(Some(tls));
+/// ```
+#[derive(Clone)]
+pub struct Tls {
+    roots: Vec<CertificateDer<'static>>,
+    identity: Option<Identity>,
+    accept_invalid_certs: bool,
+    config: Arc<rustls::ClientConfig>,
+}
+
+impl Default for Tls {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tls {
+    /// Settings that trust the public CAs, like requests do by default.
+    pub fn new() -> Self {
+        let config = build(&[], None, false).expect("TLS settings without a client certificate are always valid");
+        Tls { roots: Vec::new(), identity: None, accept_invalid_certs: false, config }
+    }
+
+    /// Trusts the certificates in the PEM file at `path`.
+    pub fn root_certificate<P: AsRef<Path>>(self, path: P) -> Result<Self, Error> {
+        let pem = read(path.as_ref())?;
+        self.root_certificate_pem(&pem)
+    }
+
+    /// Trusts the certificates in `pem`.
+    pub fn root_certificate_pem(mut self, pem: &[u8]) -> Result<Self, Error> {
+        self.roots.extend(certificates(pem)?);
+        self.config = self.build()?;
+        Ok(self)
+    }
+
+    /// Presents the certificate chain in the PEM file at `cert` to servers that ask for one,
+    /// signing with the private key in the PEM file at `key`.
+    pub fn client_certificate<C: AsRef<Path>, K: AsRef<Path>>(self, cert: C, key: K) -> Result<Self, Error> {
+        let cert = read(cert.as_ref())?;
+        let key = read(key.as_ref())?;
+        self.client_certificate_pem(&cert, &key)
+    }
+
+    /// Presents the certificate chain in `cert` to servers that ask for one, signing with the private key in `key`.
+    pub fn client_certificate_pem(mut self, cert: &[u8], key: &[u8]) -> Result<Self, Error> {
+        let chain = certificates(cert)?;
+        let key = PrivateKeyDer::from_pem_slice(key).map_err(|err| tls_error("could not read the private key", err))?;
+        self.identity = Some((chain, Arc::new(key)));
+        self.config = self.build()?;
+        Ok(self)
+    }
+
+    /// Accepts any certificate, even expired, self-signed or for another host.
+    ///
+    /// This makes HTTPS no safer than plain HTTP, so every request made with it logs a warning.
+    /// Prefer [`root_certificate`](Tls::root_certificate) for private CAs.
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        if accept {
+            swarn!("TLS certificate verification is disabled. Connections can be intercepted.");
+        }
+        self.accept_invalid_certs = accept;
+        self.config = self.build().expect("TLS settings that were valid stay valid");
+        self
+    }
+
+    /// Returns true if certificates are not verified.
+    pub fn accepts_invalid_certs(&self) -> bool {
+        self.accept_invalid_certs
+    }
+
+    pub(super) fn config(&self) -> Arc<rustls::ClientConfig> {
+        Arc::clone(&self.config)
+    }
+
+    fn build(&self) -> Result<Arc<rustls::ClientConfig>, Error> {
+        build(&self.roots, self.identity.as_ref(), self.accept_invalid_certs)
+    }
+}
+
+type Identity = (Vec<CertificateDer<'static>>, Arc<PrivateKeyDer<'static>>);
+
+fn build(extra_roots: &[CertificateDer<'static>], identity: Option<&Identity>, accept_invalid_certs: bool) -> Result<Arc<rustls::ClientConfig>, Error> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = rustls::ClientConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()
+        .expect("ring supports the default protocol versions");
+
+    let builder = match accept_invalid_certs {
+        true => builder.dangerous().with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate(provider))),
+        false => {
+            let mut roots = rustls::RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() };
+            for cert in extra_roots {
+                roots.add(cert.clone()).map_err(|err| tls_error("bad root certificate", err))?;
+            }
+            builder.with_root_certificates(roots)
+        }
+    };
+
+    let config = match identity {
+        Some((chain, key)) => builder.with_client_auth_cert(chain.clone(), key.clone_key()).map_err(|err| tls_error("bad client certificate", err))?,
+        None => builder.with_no_client_auth(),
+    };
+    Ok(Arc::new(config))
+}
+
+impl PartialEq for Tls {
+    fn eq(&self, other: &Self) -> bool {
+        self.roots == other.roots && self.identity == other.identity && self.accept_invalid_certs == other.accept_invalid_certs
+    }
+}
+
+impl std::fmt::Debug for Tls {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tls")
+            .field("roots", &self.roots.len())
+            .field("client_certificate", &self.identity.is_some())
+            .field("accept_invalid_certs", &self.accept_invalid_certs)
+            .finish()
+    }
+}
+
+fn tls_error<E: std::fmt::Display>(context: &str, err: E) -> Error {
+    Error::Tls { reason: format!("{}: {}", context, err) }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, Error> {
+    std::fs::read(path).map_err(|err| crate::disk::Error::new("read file", path, err).into())
+}
+
+fn certificates(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, Error> {
+    let certs = CertificateDer::pem_slice_iter(pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| tls_error("could not read the certificates", err))?;
+    match certs.is_empty() {
+        true => Err(tls_error("could not read the certificates", "no PEM certificate found")),
+        false => Ok(certs),
+    }
+}
+
+static TLS: RwLock<Option<Tls>> = RwLock::new(None);
+
+/// Sets the TLS settings for every request that doesn't set its own with
+/// [`RequestBuilder::tls`](super::RequestBuilder::tls). None goes back to trusting the public CAs.
+pub fn set_tls(tls: Option<Tls>) {
+    *TLS.write().unwrap() = tls;
+}
+
+pub(super) fn global_tls() -> Option<Tls> {
+    TLS.read().unwrap().clone()
+}
+
+/// Accepts any server certificate, while still checking the handshake signatures.
+#[derive(Debug)]
+pub(super) struct AcceptAnyCertificate(pub(super) Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::{request, Method};
+    use std::io::{Read, Write};
+
+    const CA: &[u8] = include_bytes!("testdata/ca.pem");
+    const SERVER_CERT: &[u8] = include_bytes!("testdata/server.pem");
+    const SERVER_KEY: &[u8] = include_bytes!("testdata/server.key");
+    const CLIENT_CERT: &[u8] = include_bytes!("testdata/client.pem");
+    const CLIENT_KEY: &[u8] = include_bytes!("testdata/client.key");
+
+    /// Starts an HTTPS server with the test certificate, answering `ok` to everything. Returns its port.
+    fn serve(require_client_cert: bool) -> u16 {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = rustls::ServerConfig::builder_with_provider(Arc::clone(&provider)).with_safe_default_protocol_versions().unwrap();
+        let builder = match require_client_cert {
+            true => {
+                let mut roots = rustls::RootCertStore::empty();
+                roots.add(certificates(CA).unwrap().remove(0)).unwrap();
+                let verifier = rustls::server::WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider).build().unwrap();
+                builder.with_client_cert_verifier(verifier)
+            }
+            false => builder.with_no_client_auth(),
+        };
+        let key = PrivateKeyDer::from_pem_slice(SERVER_KEY).unwrap();
+        let config = Arc::new(builder.with_single_cert(certificates(SERVER_CERT).unwrap(), key).unwrap());
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let connection = rustls::ServerConnection::new(Arc::clone(&config)).unwrap();
+                let mut stream = rustls::StreamOwned::new(connection, stream);
+
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 1024];
+                while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                    match stream.read(&mut buffer) {
+                        Ok(0) | Err(_) => break,
+                        Ok(read) => request.extend_from_slice(&buffer[..read]),
+                    }
+                }
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok");
+                let _ = stream.flush();
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn private_roots_are_trusted() {
+        let url = format!("https://localhost:{}/", serve(false));
+
+        let err = request(Method::Get, &url).send().unwrap_err();
+        assert!(matches!(err, Error::Connection { .. }), "{}", err);
+
+        let tls = Tls::new().root_certificate_pem(CA).unwrap();
+        assert_eq!(request(Method::Get, &url).tls(tls).send().unwrap().text().unwrap(), "ok");
+
+        let tls = Tls::new().danger_accept_invalid_certs(true);
+        assert_eq!(request(Method::Get, &url).tls(tls).send().unwrap().text().unwrap(), "ok");
+    }
+
+    #[test]
+    fn client_certificates_are_presented() {
+        let url = format!("https://localhost:{}/", serve(true));
+        let tls = Tls::new().root_certificate_pem(CA).unwrap();
+
+        assert!(request(Method::Get, &url).tls(tls.clone()).send().is_err());
+
+        let tls = tls.client_certificate_pem(CLIENT_CERT, CLIENT_KEY).unwrap();
+        assert_eq!(request(Method::Get, &url).tls(tls).send().unwrap().text().unwrap(), "ok");
+    }
+
+    #[test]
+    fn bad_pem_is_rejected() {
+        assert!(matches!(Tls::new().root_certificate_pem(b"not a certificate"), Err(Error::Tls { .. })));
+        assert!(matches!(Tls::new().client_certificate_pem(CLIENT_CERT, b""), Err(Error::Tls { .. })));
+        assert!(matches!(Tls::new().client_certificate_pem(CLIENT_CERT, SERVER_KEY), Err(Error::Tls { .. })));
+        assert!(matches!(Tls::new().root_certificate("/nonexistent/ca.pem"), Err(Error::Io(_))));
+
+        assert_eq!(Tls::new().root_certificate_pem(CA).unwrap(), Tls::new().root_certificate_pem(CA).unwrap());
+        assert_ne!(Tls::new(), Tls::new().danger_accept_invalid_certs(true));
+    }
+}
@@ -0,0 +1,212 @@
+//! The layer that sends requests over the network, which tests can replace.
+
+use super::{Body, Error, Method, Response, Tls, DEFAULT_TIMEOUT};
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A request on its way to a [`Transport`], after redirects, cookies and the settings of its
+/// client were worked out.
+#[derive(Debug, Clone)]
+pub struct TransportRequest {
+    pub(super) method: Method,
+    pub(super) url: String,
+    pub(super) headers: Vec<(String, String)>,
+    pub(super) body: Body,
+    pub(super) proxy: Option<String>,
+    pub(super) tls: Option<Tls>,
+    pub(super) timeout: Option<Duration>,
+}
+
+impl TransportRequest {
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    /// The full URL, including the query string.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The value of the first header called `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    /// Every header, in the order they are sent.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
+}
+
+/// Sends requests and receives their responses. Every request fox sends goes through one,
+/// which [`set_transport_for_tests`] can replace with canned responses.
+///
+/// Closures taking a [`TransportRequest`] and returning a response are transports.
+pub trait Transport: Send + Sync {
+    /// Sends `request` once, without following redirects. Responses are returned whatever their
+    /// status; errors are for when no response arrived.
+    fn send(&self, request: &TransportRequest) -> Result<Response, Error>;
+}
+
+impl<F: Fn(&TransportRequest) -> Result<Response, Error> + Send + Sync> Transport for F {
+    fn send(&self, request: &TransportRequest) -> Result<Response, Error> {
+        self(request)
+    }
+}
+
+/// The transport doing real I/O, with ureq.
+struct Ureq;
+
+impl Transport for Ureq {
+    fn send(&self, request: &TransportRequest) -> Result<Response, Error> {
+        let mut call = agent(request.proxy.as_deref(), request.tls.as_ref())?.request(request.method.as_str(), &request.url);
+        for (name, value) in &request.headers {
+            call = call.set(name, value);
+        }
+        if let Some(timeout) = request.timeout {
+            call = call.timeout(timeout);
+        }
+
+        let result = match &request.body {
+            Body::Empty => call.call(),
+            Body::Multipart(form) => {
+                let (reader, length) = form.reader()?;
+                call.set("Content-Length", &length.to_string()).send(reader)
+            }
+            body => call.send_bytes(&body.to_bytes()),
+        };
+
+        match result {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => Ok(Response::from_ureq(response)),
+            Err(err) => Err(Error::from_ureq(&request.url, err)),
+        }
+    }
+}
+
+/// The agent for connecting through `proxy` with the `tls` settings. Agents keep connections open, so they are shared.
+fn agent(proxy: Option<&str>, tls: Option<&Tls>) -> Result<ureq::Agent, Error> {
+    type Key = (Option<String>, Option<Tls>);
+    static AGENTS: Mutex<Vec<(Key, ureq::Agent)>> = Mutex::new(Vec::new());
+
+    let mut agents = AGENTS.lock().unwrap();
+    if let Some((_, agent)) = agents.iter().find(|((key_proxy, key_tls), _)| key_proxy.as_deref() == proxy && key_tls.as_ref() == tls) {
+        return Ok(agent.clone());
+    }
+
+    // Redirects are followed by RequestBuilder, to keep the cookies they set
+    let mut builder = ureq::AgentBuilder::new()
+        .redirects(0)
+        .timeout(DEFAULT_TIMEOUT)
+        .user_agent(concat!("fox/", env!("CARGO_PKG_VERSION")));
+    if let Some(proxy) = proxy {
+        let parsed = ureq::Proxy::new(proxy).map_err(|err| Error::InvalidUrl { url: proxy.to_string(), reason: format!("bad proxy: {}", err) })?;
+        builder = builder.proxy(parsed);
+    }
+    if let Some(tls) = tls {
+        builder = builder.tls_config(tls.config());
+    }
+
+    let agent = builder.build();
+    agents.push(((proxy.map(str::to_string), tls.cloned()), agent.clone()));
+    Ok(agent)
+}
+
+thread_local! {
+    static TEST_TRANSPORT: RefCell<Option<Arc<dyn Transport>>> = const { RefCell::new(None) };
+}
+
+/// The transport for requests sent from this thread.
+pub(super) fn transport() -> Arc<dyn Transport> {
+    TEST_TRANSPORT.with(|transport| transport.borrow().clone()).unwrap_or_else(|| Arc::new(Ureq))
+}
+
+/// The transport set with [`set_transport_for_tests`] on this thread, to hand on to threads working for it.
+pub(super) fn test_transport() -> Option<Arc<dyn Transport>> {
+    TEST_TRANSPORT.with(|transport| transport.borrow().clone())
+}
+
+/// Uses the test transport of the thread this one works for.
+pub(super) fn inherit(transport: Option<Arc<dyn Transport>>) {
+    TEST_TRANSPORT.with(|current| *current.borrow_mut() = transport);
+}
+
+/// Puts back the transport that was used before [`set_transport_for_tests`] when dropped.
+#[must_use = "the transport is put back when the guard is dropped"]
+pub struct TransportGuard {
+    previous: Option<Arc<dyn Transport>>,
+    // The transport is per thread, so the guard has to stay on it
+    _thread: PhantomData<*const ()>,
+}
+
+impl Drop for TransportGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        TEST_TRANSPORT.with(|transport| *transport.borrow_mut() = previous);
+    }
+}
+
+/// Sends the requests of the current thread through `transport` instead of the network, until the
+/// returned guard is dropped. Other threads are not affected, so tests running in parallel don't
+/// see each other's transports. The requests [`get_all`](super::get_all) sends for this thread do
+/// use it, the ones sent with the `async` functions don't.
+///
+/// For testing against real sockets, see [`test::Server`](super::test::Server).
+///
+/// ```rs
+/// let _guard = net::set_transport_for_tests(|request: &TransportRequest| {
+///     assert_eq!(request.url(), "https://api.example.com/users/1");
+///     Ok(Response::new(200, r#"{"name":"fox"}"#).with_header("Content-Type", "application/json"))
+/// });
+///
+/// let user: User = net::get_json("https://api.example.com/users/1")?;
+/// ```
+pub fn set_transport_for_tests<T: Transport + 'static>(transport: T) -> TransportGuard {
+    let previous = TEST_TRANSPORT.with(|current| current.borrow_mut().replace(Arc::new(transport)));
+    TransportGuard { previous, _thread: PhantomData }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::{self, Client, StatusCode};
+
+    #[test]
+    fn requests_go_through_the_test_transport() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let guard = set_transport_for_tests(move |request: &TransportRequest| {
+            log.lock().unwrap().push((request.method(), request.url().to_string(), request.header("Cookie").map(str::to_string)));
+            match request.url() {
+                "https://api.test/login" => Ok(Response::new(303, "").with_header("Location", "/me").with_header("Set-Cookie", "session=1")),
+                "https://api.test/me" => Ok(Response::new(200, r#"{"name":"fox"}"#)),
+                _ => Ok(Response::new(StatusCode::NotFound, "nope")),
+            }
+        });
+
+        let client = Client::new("https://api.test");
+        let me: serde_json::Value = client.post("/login", "x").unwrap().json().unwrap();
+        assert_eq!(me["name"], "fox");
+        assert_eq!(net::try_get("https://api.test/missing").unwrap_err().status(), Some(StatusCode::NotFound));
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen[1], (Method::Get, "https://api.test/me".to_string(), Some("session=1".to_string())));
+        assert_eq!(seen.len(), 3);
+
+        // Other threads still use the network
+        std::thread::spawn(|| assert!(TEST_TRANSPORT.with(|transport| transport.borrow().is_none()))).join().unwrap();
+        drop(guard);
+        assert!(TEST_TRANSPORT.with(|transport| transport.borrow().is_none()));
+    }
+
+    #[test]
+    fn transport_errors_are_returned() {
+        let _guard = set_transport_for_tests(|request: &TransportRequest| Err(Error::Timeout { url: request.url().to_string() }));
+        assert!(net::try_get("https://api.test/slow").unwrap_err().is_timeout());
+    }
+}
@@ -0,0 +1,123 @@
+//! UDP helpers for quick discovery and telemetry scripts.
+//!
+//! ```rs
+//! net::udp::send("127.0.0.1:8125", "deploys:1|c")?;
+//!
+//! let listener = net::udp::UdpListener::bind("0.0.0.0:9999")?;
+//! let (data, from) = listener.recv()?;
+//! ```
+
+use super::tcp::hexdump;
+use crate::{sdebug, serror};
+use crate as fox;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+/// The receive timeout [`UdpListener::bind`] starts with.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends one datagram to `addr` from an ephemeral port, returning how many bytes were sent.
+///
+/// Broadcast is enabled on IPv4, so `255.255.255.255:9` works for Wake-on-LAN packets.
+pub fn send<A: ToSocketAddrs, D: AsRef<[u8]>>(addr: A, data: D) -> Result<usize, std::io::Error> {
+    let data = data.as_ref();
+
+    let target = addr.to_socket_addrs()
+        .and_then(|mut addrs| addrs.next().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "address resolved to nothing")))
+        .inspect_err(|err| serror!("Failed to resolve address: {}", err))?;
+
+    let bind = if target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let sent = UdpSocket::bind(bind)
+        .and_then(|socket| {
+            if target.is_ipv4() {
+                socket.set_broadcast(true)?;
+            }
+            socket.send_to(data, target)
+        })
+        .inspect_err(|err| serror!("Failed to send to {}: {}", target, err))?;
+
+    log_datagram("->", target, data);
+    Ok(sent)
+}
+
+/// A bound UDP socket that receives datagrams with a timeout.
+pub struct UdpListener {
+    socket: UdpSocket,
+}
+
+impl UdpListener {
+    /// Binds to `addr` with the [`DEFAULT_TIMEOUT`]. Use port 0 for an ephemeral port.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<UdpListener, std::io::Error> {
+        let socket = UdpSocket::bind(addr).inspect_err(|err| serror!("Failed to bind UDP socket: {}", err))?;
+        socket.set_read_timeout(Some(DEFAULT_TIMEOUT))?;
+
+        if let Ok(local) = socket.local_addr() {
+            sdebug!("Listening for UDP on {}.", local);
+        }
+
+        Ok(UdpListener { socket })
+    }
+
+    /// The address the listener is bound to.
+    pub fn local_addr(&self) -> Result<SocketAddr, std::io::Error> {
+        self.socket.local_addr()
+    }
+
+    /// Changes the receive timeout. `None` blocks forever.
+    pub fn set_timeout(&self, timeout: Option<Duration>) -> Result<(), std::io::Error> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    /// Receives one datagram, returning its contents and sender.
+    ///
+    /// Times out with [`std::io::ErrorKind::WouldBlock`] or [`std::io::ErrorKind::TimedOut`], depending on the platform.
+    pub fn recv(&self) -> Result<(Vec<u8>, SocketAddr), std::io::Error> {
+        let mut buffer = vec![0u8; 65536];
+        let (read, from) = self.socket.recv_from(&mut buffer)?;
+        buffer.truncate(read);
+
+        log_datagram("<-", from, &buffer);
+        Ok((buffer, from))
+    }
+
+    /// Receives datagrams until `timeout` passes without one, collecting all of them.
+    pub fn recv_all(&self, timeout: Duration) -> Vec<(Vec<u8>, SocketAddr)> {
+        let deadline = std::time::Instant::now() + timeout;
+        let previous = self.socket.read_timeout().ok().flatten();
+        let mut received = Vec::new();
+
+        loop {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            if remaining.is_zero() || self.socket.set_read_timeout(Some(remaining)).is_err() {
+                break;
+            }
+
+            match self.recv() {
+                Ok(datagram) => received.push(datagram),
+                Err(_) => break,
+            }
+        }
+
+        let _ = self.socket.set_read_timeout(previous);
+        received
+    }
+
+    /// Sends a datagram from the listener's own port, e.g. to answer a sender.
+    pub fn send_to<D: AsRef<[u8]>>(&self, addr: SocketAddr, data: D) -> Result<usize, std::io::Error> {
+        let data = data.as_ref();
+        let sent = self.socket.send_to(data, addr).inspect_err(|err| serror!("Failed to send to {}: {}", addr, err))?;
+        log_datagram("->", addr, data);
+        Ok(sent)
+    }
+
+    /// Returns the underlying socket.
+    pub fn into_inner(self) -> UdpSocket {
+        self.socket
+    }
+}
+
+fn log_datagram(direction: &str, peer: SocketAddr, data: &[u8]) {
+    if fox::log::should_log(fox::log::LogLevel::Debug) {
+        sdebug!("{} {} ({} bytes)\n{}", direction, peer, data.len(), hexdump(data));
+    }
+}
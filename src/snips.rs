@@ -1,13 +1,15 @@
 //! Simple CLI visual snippets
 
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::{io, thread};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::io::Write;
 
 use colored::Colorize;
 
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
 pub struct Spinner {
     running: Arc<AtomicBool>,
     handle: Option<thread::JoinHandle<()>>,
@@ -27,7 +29,7 @@ impl Spinner {
         let message = message.to_string();
 
         let handle = thread::spawn(move || {
-            let spinner_chars = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+            let spinner_chars = SPINNER_FRAMES;
             let mut i = 0;
 
             while running.load(Ordering::Relaxed) {
@@ -50,6 +52,24 @@ impl Spinner {
             handle.join().unwrap();
         }
     }
+
+    /// Stop the spinner, leaving a ✓ line with `message` in its place
+    pub fn success<S: AsRef<str>>(&mut self, message: S) {
+        self.stop();
+        println!("{} {}", "✓".green().bold(), message.as_ref());
+    }
+
+    /// Stop the spinner, leaving a ✗ line with `message` in its place
+    pub fn fail<S: AsRef<str>>(&mut self, message: S) {
+        self.stop();
+        println!("{} {}", "✗".red().bold(), message.as_ref());
+    }
+}
+
+impl Default for Spinner {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Drop for Spinner {
@@ -58,49 +78,645 @@ impl Drop for Spinner {
     }
 }
 
-pub struct Loader {
-    amount: u8,
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TaskState {
+    Running,
+    Success,
+    Failed,
+}
+
+struct Task {
+    name: String,
+    message: String,
+    state: TaskState,
+}
+
+/// Several named spinners, one per line, for tasks running in parallel.
+///
+/// ```rs
+/// let mut spinners = MultiSpinner::new();
+/// let download = spinners.add("download", "fetching index");
+/// let build = spinners.add("build", "compiling");
+///
+/// download.set_message("fetching packages");
+/// download.success("done");
+/// build.fail("2 errors");
+/// spinners.stop();
+/// ```
+pub struct MultiSpinner {
+    tasks: Arc<Mutex<Vec<Task>>>,
+    running: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+/// Handle to a single line of a [`MultiSpinner`]. Can be cloned and sent to other threads
+#[derive(Clone)]
+pub struct SpinnerTask {
+    index: usize,
+    tasks: Arc<Mutex<Vec<Task>>>,
+}
+
+impl MultiSpinner {
+    pub fn new() -> Self {
+        MultiSpinner {
+            tasks: Arc::new(Mutex::new(vec![])),
+            running: Arc::new(AtomicBool::new(false)),
+            handle: None,
+        }
+    }
+
+    /// Add a spinner line, starting the animation if it isn't running yet
+    pub fn add<N: Into<String>, M: Into<String>>(&mut self, name: N, message: M) -> SpinnerTask {
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.push(Task { name: name.into(), message: message.into(), state: TaskState::Running });
+        let index = tasks.len() - 1;
+        drop(tasks);
+
+        if self.handle.is_none() {
+            self.running.store(true, Ordering::Relaxed);
+            let running = Arc::clone(&self.running);
+            let tasks = Arc::clone(&self.tasks);
+
+            self.handle = Some(thread::spawn(move || {
+                let mut frame = 0;
+                let mut drawn = 0;
+
+                while running.load(Ordering::Relaxed) {
+                    drawn = render_tasks(&tasks.lock().unwrap(), frame, drawn);
+                    thread::sleep(Duration::from_millis(80));
+                    frame = (frame + 1) % SPINNER_FRAMES.len();
+                }
+
+                render_tasks(&tasks.lock().unwrap(), frame, drawn);
+            }));
+        }
+
+        SpinnerTask { index, tasks: Arc::clone(&self.tasks) }
+    }
+
+    /// Stop the animation, leaving the final state of every line on screen
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.join().unwrap();
+        }
+    }
+}
+
+impl Default for MultiSpinner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for MultiSpinner {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl SpinnerTask {
+    fn update(&self, state: TaskState, message: String) {
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks[self.index].state = state;
+        tasks[self.index].message = message;
+    }
+
+    /// Replace the message shown next to the spinner
+    pub fn set_message<S: Into<String>>(&self, message: S) {
+        self.update(TaskState::Running, message.into());
+    }
+
+    /// Stop this spinner, showing a ✓ and `message`
+    pub fn success<S: Into<String>>(&self, message: S) {
+        self.update(TaskState::Success, message.into());
+    }
+
+    /// Stop this spinner, showing a ✗ and `message`
+    pub fn fail<S: Into<String>>(&self, message: S) {
+        self.update(TaskState::Failed, message.into());
+    }
+}
+
+/// Redraw every task line over the `drawn` lines printed last time, returning how many lines were printed
+fn render_tasks(tasks: &[Task], frame: usize, drawn: usize) -> usize {
+    let width = tasks.iter().map(|task| task.name.chars().count()).max().unwrap_or(0);
+    let mut out = String::new();
+
+    if drawn > 0 {
+        out.push_str(&format!("\x1b[{}A", drawn));
+    }
+
+    for task in tasks {
+        let symbol = match task.state {
+            TaskState::Running => SPINNER_FRAMES[frame].to_string().cyan(),
+            TaskState::Success => "✓".green().bold(),
+            TaskState::Failed => "✗".red().bold(),
+        };
+        out.push_str(&format!("\r\x1b[2K{} {:width$} {}\n", symbol, task.name.bold(), task.message, width = width));
+    }
+
+    print!("{}", out);
+    io::stdout().flush().unwrap();
+    tasks.len()
+}
+
+/// How a [`ProgressBar`] looks.
+///
+/// The template is the text after the bar, with these placeholders:
+/// `{percent}`, `{pos}`, `{total}`, `{rate}`, `{elapsed}`, `{eta}` and `{msg}`.
+///
+/// ```rs
+/// let style = ProgressStyle::new()
+///     .fill('=')
+///     .empty('-')
+///     .brackets("<", ">")
+///     .color("green")
+///     .template("{pos}/{total} ({eta})");
+/// let mut bar = ProgressBar::new(100).style(style);
+/// ```
+#[derive(Clone)]
+pub struct ProgressStyle {
+    fill: char,
+    empty: char,
     width: usize,
+    brackets: (String, String),
+    color: Option<colored::Color>,
+    template: String,
+}
+
+impl ProgressStyle {
+    pub fn new() -> Self {
+        ProgressStyle {
+            fill: '█',
+            empty: ' ',
+            width: 30,
+            brackets: ("[".into(), "]".into()),
+            color: None,
+            template: "{percent} {pos}/{total} {rate} {elapsed} {eta} {msg}".into(),
+        }
+    }
+
+    /// Character for the done part of the bar
+    pub fn fill(mut self, fill: char) -> Self {
+        self.fill = fill;
+        self
+    }
+
+    /// Character for the part of the bar that's left
+    pub fn empty(mut self, empty: char) -> Self {
+        self.empty = empty;
+        self
+    }
+
+    /// Width of the bar in characters, without the brackets
+    pub fn width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Text around the bar. Use empty strings for no brackets
+    pub fn brackets<O: Into<String>, C: Into<String>>(mut self, open: O, close: C) -> Self {
+        self.brackets = (open.into(), close.into());
+        self
+    }
+
+    /// Color of the done part of the bar, e.g. `"green"` or `colored::Color::Cyan`
+    pub fn color<C: Into<colored::Color>>(mut self, color: C) -> Self {
+        self.color = Some(color.into());
+        self
+    }
+
+    /// Text after the bar. See [`ProgressStyle`] for the placeholders
+    pub fn template<S: Into<String>>(mut self, template: S) -> Self {
+        self.template = template.into();
+        self
+    }
+
+    /// The bar itself, `fraction` of the way done
+    fn bar(&self, fraction: f64) -> String {
+        let filled = (fraction.clamp(0.0, 1.0) * self.width as f64).round() as usize;
+        let done = self.fill.to_string().repeat(filled);
+        let done = match self.color {
+            Some(color) => done.color(color).to_string(),
+            None => done,
+        };
+
+        format!("{}{}{}{}", self.brackets.0, done, self.empty.to_string().repeat(self.width - filled), self.brackets.1)
+    }
+
+    /// Fill in the template's placeholders
+    fn render(&self, fraction: f64, values: &[(&str, String)]) -> String {
+        let mut text = self.template.clone();
+        for (name, value) in values {
+            // Empty values take the space before them along, so they don't leave gaps
+            if value.is_empty() {
+                text = text.replace(&format!(" {{{}}}", name), "");
+            }
+            text = text.replace(&format!("{{{}}}", name), value);
+        }
+
+        format!("{} {}", self.bar(fraction), text.trim_end())
+    }
 }
 
+impl Default for ProgressStyle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[deprecated = "use ProgressBar instead"]
+pub struct Loader {
+    bar: ProgressBar,
+}
+
+#[allow(deprecated)]
 impl Loader {
     pub fn new() -> Self {
         Loader {
-            amount: 0,
-            width: 30,
+            bar: ProgressBar::new(100).style(ProgressStyle::new().template("{pos}/{total}")),
         }
     }
 
+    /// Change how the loader looks
+    pub fn style(mut self, style: ProgressStyle) -> Self {
+        self.bar = self.bar.style(style);
+        self
+    }
+
     pub fn set_amount(&mut self, amount: u8) {
-        let amount = amount.min(100);
-        self.amount = amount;
+        self.bar.set_position(amount as u64);
+    }
+
+    pub fn clear(&mut self) {
+        self.bar.clear();
+    }
+}
 
-        let filled_width = (amount as f32 / 100.0 * self.width as f32).round() as usize;
+#[allow(deprecated)]
+impl Default for Loader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        print!("\r[");
+/// A progress bar for a known amount of items or bytes, showing the elapsed time, rate and ETA.
+///
+/// ```rs
+/// let mut bar = ProgressBar::new(total_bytes).bytes();
+/// for (name, chunk) in chunks {
+///     bar.set_message(format!("copying {}", name));
+///     bar.inc(write(chunk));
+/// }
+/// bar.finish();
+/// ```
+pub struct ProgressBar {
+    total: u64,
+    position: u64,
+    style: ProgressStyle,
+    bytes: bool,
+    message: String,
+    start: Instant,
+    finished: bool,
+    multi: Option<(Arc<Mutex<MultiState>>, usize)>,
+}
 
-        for i in 0..self.width {
-            if i < filled_width {
-                print!("█");
-            } else {
-                print!(" ");
-            }
+impl ProgressBar {
+    pub fn new(total: u64) -> Self {
+        ProgressBar {
+            total,
+            position: 0,
+            style: ProgressStyle::new(),
+            bytes: false,
+            message: String::new(),
+            start: Instant::now(),
+            finished: false,
+            multi: None,
+        }
+    }
+
+    /// Change how the bar looks
+    pub fn style(mut self, style: ProgressStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Show the position and rate as byte sizes, e.g. `1.5 MiB/s`
+    pub fn bytes(mut self) -> Self {
+        self.bytes = true;
+        self
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Time since the bar was created
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Average items or bytes per second so far
+    pub fn rate(&self) -> f64 {
+        let seconds = self.elapsed().as_secs_f64();
+        match seconds > 0.0 {
+            true => self.position as f64 / seconds,
+            false => 0.0,
+        }
+    }
+
+    /// Estimated time left at the current rate, if anything has happened yet
+    pub fn eta(&self) -> Option<Duration> {
+        let rate = self.rate();
+        if rate <= 0.0 {
+            return None;
         }
-        print!("] {}/100", amount);
 
-        std::io::stdout().flush().unwrap();
+        let left = self.total.saturating_sub(self.position) as f64;
+        Some(Duration::from_secs_f64(left / rate))
+    }
+
+    /// Move the bar to `position` and redraw it
+    pub fn set_position(&mut self, position: u64) {
+        self.position = position.min(self.total);
+        self.draw();
+    }
+
+    /// Move the bar forward by `amount` and redraw it
+    pub fn inc(&mut self, amount: u64) {
+        self.set_position(self.position.saturating_add(amount));
+    }
+
+    /// Show `message` after the bar, e.g. the file being worked on, and redraw it
+    pub fn set_message<S: Into<String>>(&mut self, message: S) {
+        self.message = message.into();
+        self.draw();
+    }
+
+    /// Draw the bar one last time and move to the next line, leaving it on screen
+    pub fn finish(&mut self) {
+        self.draw();
+        if self.multi.is_none() {
+            println!();
+        }
+        self.finished = true;
     }
 
     pub fn clear(&mut self) {
-        print!("\r{}\r", " ".repeat(self.width + 10));
+        match &self.multi {
+            Some((state, index)) => state.lock().unwrap().update(*index, String::new()),
+            None => {
+                print!("\r\x1b[2K");
+                io::stdout().flush().unwrap();
+            },
+        }
+    }
+
+    fn draw(&self) {
+        match &self.multi {
+            Some((state, index)) => state.lock().unwrap().update(*index, self.line()),
+            None => {
+                print!("\r{}\x1b[K", self.line());
+                io::stdout().flush().unwrap();
+            },
+        }
+    }
+
+    /// The rendered bar, e.g. `[██████        ]  45% 45/100 12.0/s 00:03 ETA 00:04`
+    fn line(&self) -> String {
+        let fraction = match self.total {
+            0 => 1.0,
+            total => self.position as f64 / total as f64,
+        };
 
-        std::io::stdout().flush().unwrap();
+        let (position, total, rate) = match self.bytes {
+            true => (crate::disk::format_size(self.position), crate::disk::format_size(self.total), format!("{}/s", crate::disk::format_size(self.rate() as u64))),
+            false => (self.position.to_string(), self.total.to_string(), format!("{:.1}/s", self.rate())),
+        };
+
+        let eta = match (self.position >= self.total, self.eta()) {
+            (true, _) => String::new(),
+            (false, Some(eta)) => format!("ETA {}", format_duration(eta)).dimmed().to_string(),
+            (false, None) => "ETA --:--".dimmed().to_string(),
+        };
+
+        self.style.render(fraction, &[
+            ("percent", format!("{:>3}%", (fraction * 100.0).round() as u64)),
+            ("pos", position),
+            ("total", total),
+            ("rate", rate.dimmed().to_string()),
+            ("elapsed", format_duration(self.elapsed())),
+            ("eta", eta),
+            ("msg", self.message.clone()),
+        ])
     }
 }
 
-impl Drop for Loader {
+impl Drop for ProgressBar {
     fn drop(&mut self) {
-        self.clear();
+        // Bars in a `MultiProgress` keep their line, so the others don't move
+        if !self.finished && self.multi.is_none() {
+            self.clear();
+        }
+    }
+}
+
+/// Several progress bars stacked on separate lines, each of which can be updated from its own thread.
+///
+/// ```rs
+/// let multi = MultiProgress::new();
+/// let mut download = multi.add(ProgressBar::new(size).bytes());
+/// let mut extract = multi.add(ProgressBar::new(files));
+///
+/// thread::spawn(move || download.inc(chunk));
+/// thread::spawn(move || extract.inc(1));
+/// ```
+#[derive(Clone)]
+pub struct MultiProgress {
+    state: Arc<Mutex<MultiState>>,
+}
+
+struct MultiState {
+    lines: Vec<String>,
+    /// Visible width of each line as last drawn, to know how many rows they take after a resize
+    drawn: Vec<usize>,
+}
+
+impl MultiProgress {
+    pub fn new() -> Self {
+        MultiProgress {
+            state: Arc::new(Mutex::new(MultiState { lines: vec![], drawn: vec![] })),
+        }
+    }
+
+    /// Add a bar below the others, returning it ready to be updated
+    pub fn add(&self, mut bar: ProgressBar) -> ProgressBar {
+        let mut state = self.state.lock().unwrap();
+        state.lines.push(String::new());
+        bar.multi = Some((Arc::clone(&self.state), state.lines.len() - 1));
+        drop(state);
+
+        bar.draw();
+        bar
+    }
+}
+
+impl Default for MultiProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MultiState {
+    fn update(&mut self, index: usize, line: String) {
+        self.lines[index] = line;
+        self.redraw();
+    }
+
+    /// Move back up over the previous drawing and draw every line again, cut to the terminal width
+    fn redraw(&mut self) {
+        let width = terminal_width();
+        let rows: usize = self.drawn.iter().map(|&len| len.div_ceil(width).max(1)).sum();
+
+        let mut out = String::new();
+        if rows > 0 {
+            out.push_str(&format!("\x1b[{}A", rows));
+        }
+
+        self.drawn.clear();
+        for line in &self.lines {
+            let (line, len) = truncate_visible(line, width.saturating_sub(1));
+            out.push_str(&format!("\r\x1b[2K{}\n", line));
+            self.drawn.push(len);
+        }
+        out.push_str("\x1b[J");
+
+        print!("{}", out);
+        io::stdout().flush().unwrap();
+    }
+}
+
+/// Cut `text` to `width` visible characters, keeping ANSI escape codes intact.
+/// Returns the cut text and its visible length
+fn truncate_visible(text: &str, width: usize) -> (String, usize) {
+    let mut out = String::with_capacity(text.len());
+    let mut visible = 0;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            out.push(c);
+            if chars.peek() == Some(&'[') {
+                for c in chars.by_ref() {
+                    out.push(c);
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+
+        if visible < width {
+            out.push(c);
+            visible += 1;
+        }
+    }
+
+    (out, visible)
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn GetStdHandle(std_handle: u32) -> *mut std::ffi::c_void;
+    fn GetConsoleMode(console: *mut std::ffi::c_void, mode: *mut u32) -> i32;
+    fn SetConsoleMode(console: *mut std::ffi::c_void, mode: u32) -> i32;
+}
+
+/// Width of the terminal in columns, or 80 if it can't be determined
+#[cfg(unix)]
+fn terminal_width() -> usize {
+    // SAFETY: winsize is a plain C struct of integers, for which all zeroes is a valid value
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    // SAFETY: TIOCGWINSZ only writes a winsize through the pointer, which points to one that outlives the call.
+    // On a descriptor that isn't a terminal it fails without writing anything
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+
+    match result == 0 && size.ws_col > 0 {
+        true => size.ws_col as usize,
+        false => columns_from_env(),
+    }
+}
+
+#[cfg(windows)]
+fn terminal_width() -> usize {
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct Coord {
+        x: i16,
+        y: i16,
+    }
+
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct SmallRect {
+        left: i16,
+        top: i16,
+        right: i16,
+        bottom: i16,
+    }
+
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct ConsoleScreenBufferInfo {
+        size: Coord,
+        cursor_position: Coord,
+        attributes: u16,
+        window: SmallRect,
+        maximum_window_size: Coord,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetConsoleScreenBufferInfo(console: *mut std::ffi::c_void, info: *mut ConsoleScreenBufferInfo) -> i32;
+    }
+
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+
+    // SAFETY: ConsoleScreenBufferInfo is a #[repr(C)] struct of integers, for which all zeroes is a valid value
+    let mut info: ConsoleScreenBufferInfo = unsafe { std::mem::zeroed() };
+    // SAFETY: GetStdHandle takes no pointers. GetConsoleScreenBufferInfo writes a CONSOLE_SCREEN_BUFFER_INFO,
+    // which ConsoleScreenBufferInfo mirrors, through a pointer to a local that outlives the call.
+    // An invalid or non-console handle makes it fail without writing
+    let result = unsafe { GetConsoleScreenBufferInfo(GetStdHandle(STD_OUTPUT_HANDLE), &mut info) };
+
+    match result != 0 {
+        true => (info.window.right - info.window.left + 1).max(1) as usize,
+        false => columns_from_env(),
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn terminal_width() -> usize {
+    columns_from_env()
+}
+
+fn columns_from_env() -> usize {
+    std::env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok()).filter(|&columns| columns > 0).unwrap_or(80)
+}
+
+/// Format a duration as `mm:ss`, or `h:mm:ss` past an hour
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds >= 3600 {
+        true => format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60),
+        false => format!("{:02}:{:02}", seconds / 60, seconds % 60),
     }
 }
 
@@ -213,3 +829,618 @@ pub fn select_with_default<S: AsRef<str>>(message: S, options: &[S], default: us
         }
     }
 }
+
+pub fn input<S: Into<String>>(message: S) -> String {
+    read_input(&message.into(), None, |_| Ok(())).unwrap_or_default()
+}
+
+pub fn input_with_default<S: Into<String>, D: Into<String>>(message: S, default: D) -> String {
+    let default = default.into();
+    read_input(&message.into(), Some(&default), |_| Ok(())).unwrap_or(default)
+}
+
+/// Ask for text until `validator` accepts it, printing the validator's error message otherwise.
+/// An empty answer gives `default` if there is one, which isn't validated.
+/// Gives `None` if input ends before an answer is accepted and there's no default, since an empty string might not pass `validator`
+///
+/// Example: `let name = input_with_validator("Project name:", None, |name| if name.contains(' ') { Err("no spaces please".into()) } else { Ok(()) })?;`
+pub fn input_with_validator<S, F>(message: S, default: Option<&str>, validator: F) -> Option<String>
+where
+    S: Into<String>,
+    F: Fn(&str) -> Result<(), String>,
+{
+    read_input(&message.into(), default, validator)
+}
+
+/// Prompt until `validator` accepts an answer. Gives `None` if input ends without a default to fall back on
+fn read_input<F: Fn(&str) -> Result<(), String>>(message: &str, default: Option<&str>, validator: F) -> Option<String> {
+    loop {
+        match default {
+            Some(default) => print!("{} {} {} ", "INPT =>".blue().bold(), message, format!("[{}]", default).dimmed()),
+            None => print!("{} {} ", "INPT =>".blue().bold(), message),
+        }
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        match io::stdin().read_line(&mut input) {
+            // End of input, nothing more is coming
+            Ok(0) => {
+                println!();
+                return default.map(str::to_string);
+            }
+            Ok(_) => {
+                let input = input.trim();
+
+                if input.is_empty() {
+                    if let Some(default) = default {
+                        return Some(default.to_string());
+                    }
+                }
+
+                match validator(input) {
+                    Ok(()) => return Some(input.to_string()),
+                    Err(err) => println!("{}", err),
+                }
+            }
+            Err(_) => {
+                println!("Failed to read input");
+                return default.map(str::to_string);
+            }
+        }
+    }
+}
+
+/// Ask for a number until one within `range` is entered. Gives `None` if input ends first
+///
+/// Example: `let port: Option<u16> = input_number("Port:", 1..=65535);`
+pub fn input_number<T, S, R>(message: S, range: R) -> Option<T>
+where
+    T: std::str::FromStr + PartialOrd + std::fmt::Display,
+    S: Into<String>,
+    R: std::ops::RangeBounds<T>,
+{
+    read_number(&message.into(), &range, None)
+}
+
+/// Ask for a number until one within `range` is entered, giving `default` for an empty answer
+pub fn input_number_with_default<T, S, R>(message: S, range: R, default: T) -> T
+where
+    T: std::str::FromStr + PartialOrd + std::fmt::Display,
+    S: Into<String>,
+    R: std::ops::RangeBounds<T>,
+{
+    let default_text = default.to_string();
+    read_number(&message.into(), &range, Some(&default_text)).unwrap_or(default)
+}
+
+fn read_number<T, R>(message: &str, range: &R, default: Option<&str>) -> Option<T>
+where
+    T: std::str::FromStr + PartialOrd + std::fmt::Display,
+    R: std::ops::RangeBounds<T>,
+{
+    use std::ops::Bound;
+
+    let hint = match (range.start_bound(), range.end_bound()) {
+        (Bound::Included(start), Bound::Included(end)) => format!("Please enter a number between {} and {}", start, end),
+        (start, end) => {
+            let limits: Vec<String> = [
+                match start {
+                    Bound::Included(value) => Some(format!("at least {}", value)),
+                    Bound::Excluded(value) => Some(format!("above {}", value)),
+                    Bound::Unbounded => None,
+                },
+                match end {
+                    Bound::Included(value) => Some(format!("at most {}", value)),
+                    Bound::Excluded(value) => Some(format!("below {}", value)),
+                    Bound::Unbounded => None,
+                },
+            ].into_iter().flatten().collect();
+
+            match limits.is_empty() {
+                true => "Please enter a number".to_string(),
+                false => format!("Please enter a number {}", limits.join(" and ")),
+            }
+        },
+    };
+
+    let answer = read_input(message, default, |answer| match answer.parse::<T>() {
+        Ok(number) if range.contains(&number) => Ok(()),
+        _ => Err(hint.clone()),
+    })?;
+    answer.parse().ok()
+}
+
+/// Ask for a secret without showing what's typed, e.g. a password or an API token.
+/// The answer is never logged
+pub fn password<S: Into<String>>(message: S) -> String {
+    print!("{} {} ", "INPT =>".blue().bold(), message.into());
+    io::stdout().flush().unwrap();
+
+    let mut input = String::new();
+    let guard = EchoGuard::disable();
+    let result = io::stdin().read_line(&mut input);
+    drop(guard);
+
+    // The newline wasn't echoed either
+    println!();
+
+    if result.is_err() {
+        println!("Failed to read input");
+    }
+    input.trim_end_matches(['\r', '\n']).to_string()
+}
+
+/// Turns terminal echo off while alive, restoring the previous mode when dropped.
+/// On unix, [`EchoGuard::raw`] also reads keys one at a time for interactive prompts
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+struct EchoGuard {
+    #[cfg(unix)]
+    previous: libc::termios,
+    #[cfg(windows)]
+    previous: u32,
+}
+
+#[cfg(unix)]
+impl EchoGuard {
+    fn disable() -> Option<EchoGuard> {
+        // SAFETY: termios is a plain C struct of integers and arrays of them, for which all zeroes is a valid value
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        // SAFETY: tcgetattr only writes a termios through the pointer, which points to a local that outlives the call
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } != 0 {
+            return None;
+        }
+
+        let previous = termios;
+        termios.c_lflag &= !libc::ECHO;
+        // SAFETY: tcsetattr only reads the termios filled in by tcgetattr above, through a pointer that outlives the call
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) } != 0 {
+            return None;
+        }
+
+        Some(EchoGuard { previous })
+    }
+
+    /// Turn off echo, line buffering and Ctrl-C signals, so keys can be read one at a time.
+    /// Only works when both stdin and stdout are terminals
+    fn raw() -> Option<EchoGuard> {
+        // SAFETY: isatty only inspects the descriptor, and fails rather than misbehaving on a closed one
+        if unsafe { libc::isatty(libc::STDIN_FILENO) == 0 || libc::isatty(libc::STDOUT_FILENO) == 0 } {
+            return None;
+        }
+
+        // SAFETY: termios is a plain C struct of integers and arrays of them, for which all zeroes is a valid value
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        // SAFETY: tcgetattr only writes a termios through the pointer, which points to a local that outlives the call
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } != 0 {
+            return None;
+        }
+
+        let previous = termios;
+        termios.c_lflag &= !(libc::ECHO | libc::ICANON | libc::ISIG);
+        termios.c_cc[libc::VMIN] = 1;
+        termios.c_cc[libc::VTIME] = 0;
+        // SAFETY: tcsetattr only reads the termios filled in by tcgetattr above, through a pointer that outlives the call
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) } != 0 {
+            return None;
+        }
+
+        Some(EchoGuard { previous })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for EchoGuard {
+    fn drop(&mut self) {
+        // SAFETY: `previous` is the valid termios tcgetattr returned, and tcsetattr only reads it.
+        // If stdin stopped being a terminal, the call fails harmlessly
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.previous) };
+    }
+}
+
+#[cfg(windows)]
+const STD_INPUT_HANDLE: u32 = -10i32 as u32;
+
+#[cfg(windows)]
+impl EchoGuard {
+    fn disable() -> Option<EchoGuard> {
+        const ENABLE_ECHO_INPUT: u32 = 0x0004;
+
+        let mut previous = 0;
+        // SAFETY: GetStdHandle takes no pointers, GetConsoleMode writes one u32 through a pointer to a local
+        // that outlives the call, and SetConsoleMode takes the mode by value. A non-console handle makes them fail
+        unsafe {
+            let handle = GetStdHandle(STD_INPUT_HANDLE);
+            if GetConsoleMode(handle, &mut previous) == 0 || SetConsoleMode(handle, previous & !ENABLE_ECHO_INPUT) == 0 {
+                return None;
+            }
+        }
+
+        Some(EchoGuard { previous })
+    }
+}
+
+#[cfg(windows)]
+impl Drop for EchoGuard {
+    fn drop(&mut self) {
+        // SAFETY: Neither call takes pointers, and `previous` is the mode GetConsoleMode returned for this handle
+        unsafe { SetConsoleMode(GetStdHandle(STD_INPUT_HANDLE), self.previous) };
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+impl EchoGuard {
+    fn disable() -> Option<EchoGuard> {
+        None
+    }
+}
+
+/// A key pressed in an interactive prompt
+#[cfg(unix)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    Enter,
+    Backspace,
+    Interrupt,
+    Escape,
+    Char(char),
+    Other,
+}
+
+/// Read one byte from stdin, or `None` if `timeout_ms` passes first.
+/// Bypasses the buffering of [`io::stdin`], so `poll` sees whether more bytes are waiting
+#[cfg(unix)]
+fn read_byte(timeout_ms: Option<i32>) -> Option<u8> {
+    if let Some(timeout) = timeout_ms {
+        let mut stdin = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+        // SAFETY: poll reads and writes exactly the one pollfd it is given, a local that outlives the call
+        if unsafe { libc::poll(&mut stdin, 1, timeout) } <= 0 {
+            return None;
+        }
+    }
+
+    let mut byte = 0u8;
+    // SAFETY: read writes at most 1 byte, the length given, into a local u8 that outlives the call
+    match unsafe { libc::read(libc::STDIN_FILENO, (&mut byte as *mut u8).cast(), 1) } {
+        1 => Some(byte),
+        _ => None,
+    }
+}
+
+/// Read a key from a terminal in raw mode
+#[cfg(unix)]
+fn read_key() -> Option<Key> {
+    // Escape sequences arrive all at once, so a lone Esc is one with nothing after it for a moment
+    const SEQUENCE_TIMEOUT_MS: i32 = 50;
+
+    let key = match read_byte(None)? {
+        b'\r' | b'\n' => Key::Enter,
+        0x7f | 0x08 => Key::Backspace,
+        0x03 | 0x04 => Key::Interrupt,
+        0x1b => match read_byte(Some(SEQUENCE_TIMEOUT_MS)) {
+            None => Key::Escape,
+            Some(b'[' | b'O') => {
+                // Skip parameters like the `1;5` of `\x1b[1;5A` (Ctrl-Up), up to the final byte
+                let mut last = read_byte(Some(SEQUENCE_TIMEOUT_MS));
+                while let Some(0x20..=0x3f) = last {
+                    last = read_byte(Some(SEQUENCE_TIMEOUT_MS));
+                }
+                match last {
+                    Some(b'A') => Key::Up,
+                    Some(b'B') => Key::Down,
+                    _ => Key::Other,
+                }
+            },
+            Some(_) => Key::Other,
+        },
+        first => {
+            // Multi-byte UTF-8 characters
+            let len = match first {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            let mut bytes = vec![first];
+            for _ in 1..len {
+                bytes.push(read_byte(None)?);
+            }
+
+            match std::str::from_utf8(&bytes).ok().and_then(|text| text.chars().next()) {
+                Some(c) if !c.is_control() => Key::Char(c),
+                _ => Key::Other,
+            }
+        },
+    };
+
+    Some(key)
+}
+
+/// Ask to pick any number of options, giving their indices in order.
+///
+/// In a terminal on unix, the arrow keys move, space toggles, `a` toggles all, enter confirms and Esc cancels.
+/// Elsewhere, the choices are typed as numbers like `1,3-5`. Gives `None` if the prompt is cancelled
+pub fn multi_select<S: AsRef<str>>(message: S, options: &[S]) -> Option<Vec<usize>> {
+    if options.is_empty() {
+        return None;
+    }
+
+    let message = message.as_ref();
+
+    #[cfg(unix)]
+    if let Some(guard) = EchoGuard::raw() {
+        let result = multi_select_interactive(message, options);
+        drop(guard);
+        return result;
+    }
+
+    loop {
+        println!("{} {}", "INPT =>".blue().bold(), message);
+        for (i, option) in options.iter().enumerate() {
+            println!("  {}. {}", (i + 1).to_string().cyan(), option.as_ref());
+        }
+        print!("Enter choices, e.g. 1,3-{} (empty for none): ", options.len());
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        match io::stdin().read_line(&mut input) {
+            Ok(0) => return None,
+            Ok(_) => match parse_choices(input.trim(), options.len()) {
+                Some(choices) => return Some(choices),
+                None => println!("Invalid choices. Please enter numbers between 1 and {}, separated by commas\n", options.len()),
+            },
+            Err(_) => {
+                println!("Failed to read input");
+                return None;
+            }
+        }
+    }
+}
+
+/// Parse choices like `1, 3-5` into sorted, deduplicated indices
+fn parse_choices(input: &str, count: usize) -> Option<Vec<usize>> {
+    let mut choices = vec![];
+
+    for part in input.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        let (start, end) = match part.split_once('-') {
+            Some((start, end)) => (start.trim().parse::<usize>().ok()?, end.trim().parse::<usize>().ok()?),
+            None => {
+                let choice = part.parse::<usize>().ok()?;
+                (choice, choice)
+            },
+        };
+
+        if start < 1 || end > count || start > end {
+            return None;
+        }
+        choices.extend(start - 1..end);
+    }
+
+    choices.sort();
+    choices.dedup();
+    Some(choices)
+}
+
+#[cfg(unix)]
+fn multi_select_interactive<S: AsRef<str>>(message: &str, options: &[S]) -> Option<Vec<usize>> {
+    let mut selected = vec![false; options.len()];
+    let mut cursor = 0;
+
+    println!("{} {} {}", "INPT =>".blue().bold(), message, "(↑/↓ move, space toggles, a toggles all, enter confirms)".dimmed());
+    loop {
+        let mut out = String::new();
+        for (i, option) in options.iter().enumerate() {
+            let check = match selected[i] {
+                true => "[x]".green().to_string(),
+                false => "[ ]".to_string(),
+            };
+            let line = match i == cursor {
+                true => format!("{} {} {}", ">".cyan().bold(), check, option.as_ref().cyan()),
+                false => format!("  {} {}", check, option.as_ref()),
+            };
+            out.push_str(&format!("\r\x1b[2K{}\r\n", line));
+        }
+        print!("{}", out);
+        io::stdout().flush().unwrap();
+
+        let key = read_key();
+        print!("\x1b[{}A", options.len());
+
+        match key? {
+            Key::Up | Key::Char('k') => cursor = cursor.checked_sub(1).unwrap_or(options.len() - 1),
+            Key::Down | Key::Char('j') => cursor = (cursor + 1) % options.len(),
+            Key::Char(' ') => selected[cursor] = !selected[cursor],
+            Key::Char('a') => {
+                let all = selected.iter().all(|&selected| selected);
+                selected.iter_mut().for_each(|selected| *selected = !all);
+            },
+            Key::Enter => break,
+            Key::Interrupt | Key::Escape => {
+                print!("\x1b[{}B", options.len());
+                println!();
+                return None;
+            },
+            _ => {},
+        }
+    }
+
+    // Collapse the list into a summary of what was chosen
+    print!("\x1b[J");
+    let chosen: Vec<usize> = (0..options.len()).filter(|&i| selected[i]).collect();
+    let names: Vec<&str> = chosen.iter().map(|&i| options[i].as_ref()).collect();
+    println!("  {}", names.join(", ").cyan());
+
+    Some(chosen)
+}
+
+/// How many matches a fuzzy select shows at once
+const FUZZY_ROWS: usize = 10;
+
+/// Score how well `query` fuzzily matches `candidate`, ignoring case.
+/// The letters of the query must appear in order, with consecutive letters and word starts scoring higher
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let mut query = query.chars().flat_map(char::to_lowercase).filter(|c| !c.is_whitespace()).peekable();
+    let mut score = 0;
+    let mut previous: Option<char> = None;
+    let mut last_match: Option<usize> = None;
+
+    for (i, c) in candidate.chars().flat_map(char::to_lowercase).enumerate() {
+        let Some(&wanted) = query.peek() else { break };
+        if c == wanted {
+            score += 1;
+            if last_match.is_some_and(|last| last + 1 == i) {
+                score += 5;
+            }
+            if previous.is_none_or(|previous| !previous.is_alphanumeric()) {
+                score += 3;
+            }
+            last_match = Some(i);
+            query.next();
+        }
+        previous = Some(c);
+    }
+
+    match query.peek() {
+        Some(_) => None,
+        None => Some(score),
+    }
+}
+
+/// Indices of the options matching `query`, best matches first
+fn fuzzy_filter<S: AsRef<str>>(query: &str, options: &[S]) -> Vec<usize> {
+    let mut matches: Vec<(usize, i64)> = options.iter()
+        .enumerate()
+        .filter_map(|(i, option)| fuzzy_score(query, option.as_ref()).map(|score| (i, score)))
+        .collect();
+
+    // Stable, so equal scores keep their original order
+    matches.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    matches.into_iter().map(|(i, _)| i).collect()
+}
+
+/// Ask to pick one of many options, narrowing them down by typing part of the name, like `fzf`.
+/// Gives the index of the chosen option.
+///
+/// In a terminal on unix, the list filters while typing, the arrow keys move, enter confirms and Esc cancels.
+/// Elsewhere, a search is typed first and a number is picked from the matches. Gives `None` if the prompt is cancelled
+///
+/// ```rs
+/// let branches = vec!["main", "feature/login", "fix/crash-on-start"];
+/// if let Some(i) = select_fuzzy("Branch:", &branches) {
+///     println!("Checking out {}", branches[i]);
+/// }
+/// ```
+pub fn select_fuzzy<S: AsRef<str>>(message: S, options: &[S]) -> Option<usize> {
+    if options.is_empty() {
+        return None;
+    }
+
+    let message = message.as_ref();
+
+    #[cfg(unix)]
+    if let Some(guard) = EchoGuard::raw() {
+        let result = select_fuzzy_interactive(message, options);
+        drop(guard);
+        return result;
+    }
+
+    let mut query = String::new();
+    loop {
+        let matches = fuzzy_filter(&query, options);
+        let shown = &matches[..matches.len().min(FUZZY_ROWS * 2)];
+
+        if matches.is_empty() {
+            println!("No options match \"{}\"", query);
+        } else {
+            println!("{} {}", "INPT =>".blue().bold(), message);
+            for (i, &option) in shown.iter().enumerate() {
+                println!("  {}. {}", (i + 1).to_string().cyan(), options[option].as_ref());
+            }
+            if matches.len() > shown.len() {
+                println!("  {}", format!("... and {} more", matches.len() - shown.len()).dimmed());
+            }
+        }
+
+        print!("Choose a number, or type to search: ");
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        match io::stdin().read_line(&mut input) {
+            Ok(0) => return None,
+            Ok(_) => {
+                let input = input.trim();
+                match input.parse::<usize>() {
+                    Ok(choice) if (1..=shown.len()).contains(&choice) => return Some(shown[choice - 1]),
+                    _ => query = input.to_string(),
+                }
+            },
+            Err(_) => {
+                println!("Failed to read input");
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn select_fuzzy_interactive<S: AsRef<str>>(message: &str, options: &[S]) -> Option<usize> {
+    let rows = options.len().min(FUZZY_ROWS);
+    let mut query = String::new();
+    let mut matches = fuzzy_filter(&query, options);
+    let mut cursor = 0;
+    let mut offset = 0;
+
+    loop {
+        // Keep the cursor within the visible rows
+        if cursor < offset {
+            offset = cursor;
+        } else if cursor >= offset + rows {
+            offset = cursor + 1 - rows;
+        }
+
+        let mut out = format!("\r\x1b[2K{} {} {}\r\n", "INPT =>".blue().bold(), message, query);
+        for row in 0..rows {
+            let line = match matches.get(offset + row) {
+                Some(&option) if offset + row == cursor => format!("{} {}", ">".cyan().bold(), options[option].as_ref().cyan()),
+                Some(&option) => format!("  {}", options[option].as_ref()),
+                None => String::new(),
+            };
+            out.push_str(&format!("\x1b[2K{}\r\n", line));
+        }
+        out.push_str(&format!("\x1b[2K  {}", format!("{}/{} (↑/↓ move, enter confirms)", matches.len(), options.len()).dimmed()));
+        print!("{}", out);
+        io::stdout().flush().unwrap();
+
+        let key = read_key();
+        print!("\r\x1b[{}A", rows + 1);
+
+        match key? {
+            Key::Up => cursor = cursor.saturating_sub(1),
+            Key::Down => cursor = (cursor + 1).min(matches.len().saturating_sub(1)),
+            Key::Enter if !matches.is_empty() => break,
+            Key::Interrupt | Key::Escape => {
+                print!("\x1b[{}B\r\n", rows + 1);
+                return None;
+            },
+            Key::Char(c) => {
+                query.push(c);
+                matches = fuzzy_filter(&query, options);
+                cursor = 0;
+            },
+            Key::Backspace => {
+                query.pop();
+                matches = fuzzy_filter(&query, options);
+                cursor = 0;
+            },
+            _ => {},
+        }
+    }
+
+    // Collapse the list into the chosen option
+    let choice = matches[cursor];
+    print!("\r\x1b[2K{} {} {}\r\n\x1b[J", "INPT =>".blue().bold(), message, options[choice].as_ref().cyan());
+    io::stdout().flush().unwrap();
+
+    Some(choice)
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "derive")]
+// The cli module is deprecated in favor of clap, but still supported
+#![allow(deprecated)]
+
+use std::path::PathBuf;
+
+#[derive(fox::FoxArgs)]
+#[fox(name = "serve", version = "1.0.0")]
+/// Serve a directory over HTTP
+struct Args {
+    /// Directory to serve
+    root: PathBuf,
+    #[fox(default = "8080")]
+    port: u16,
+    #[fox(long = "--log")]
+    log_file: Option<String>,
+    #[fox(short = "-v")]
+    verbose: bool,
+}
+
+fn parse(line: &str) -> Result<Args, fox::cli::ParseError> {
+    Args::parser().try_parse_str(line).and_then(|args| Args::try_from_arguments(&args))
+}
+
+#[test]
+fn derived_args_fill_every_field() {
+    let args = parse("--root ./public --port 80 --log access.log -v").unwrap();
+    assert_eq!(args.root, PathBuf::from("./public"));
+    assert_eq!(args.port, 80);
+    assert_eq!(args.log_file.as_deref(), Some("access.log"));
+    assert!(args.verbose);
+}
+
+#[test]
+fn derived_args_fall_back_to_defaults() {
+    let args = parse("--root ./public").unwrap();
+    assert_eq!(args.port, 8080);
+    assert_eq!(args.log_file, None);
+    assert!(!args.verbose);
+
+    assert!(matches!(parse("--port 80"), Err(fox::cli::ParseError::MissingRequired(_))));
+    assert!(matches!(parse("--root . --port eighty"), Err(fox::cli::ParseError::InvalidValue { .. })));
+}
@@ -0,0 +1,59 @@
+//! Dry-run mode is global, so this runs in its own test binary to keep it from affecting other tests.
+
+use fox::disk;
+use std::path::PathBuf;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("fox-dry-run-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::write(dir.join("src/file.txt"), "content").unwrap();
+    dir
+}
+
+#[test]
+fn dry_run_leaves_targets_missing() {
+    let dir = temp_dir("copy");
+    let src = dir.join("src");
+    disk::set_dry_run(true);
+
+    disk::write_file(dir.join("written.txt"), "content").unwrap();
+    assert_eq!(disk::copy_fast(src.join("file.txt"), dir.join("fast.txt")).unwrap(), disk::CopyMethod::Copy);
+    assert_eq!(disk::copy_with_progress(&src, dir.join("copied"), |_, _| {}).unwrap(), 7);
+    for target in ["written.txt", "fast.txt", "copied"] {
+        assert!(!dir.join(target).exists(), "{} was created", target);
+    }
+
+    let server = fox::net::test::Server::start().unwrap();
+    server.when("GET", "/file").respond(200, "content");
+    assert_eq!(fox::net::download(format!("{}/file", server.url()), dir.join("downloaded.txt")).unwrap(), 0);
+    assert!(!dir.join("downloaded.txt").exists());
+    assert!(server.requests().is_empty());
+
+    #[cfg(feature = "csv")]
+    {
+        disk::write_csv(dir.join("rows.csv"), &[("a", 1)]).unwrap();
+        assert!(!dir.join("rows.csv").exists());
+    }
+
+    #[cfg(feature = "archive")]
+    {
+        disk::zip(&src, dir.join("src.zip")).unwrap();
+        disk::tar_gz(&src, dir.join("src.tar.gz")).unwrap();
+        assert!(!dir.join("src.zip").exists());
+        assert!(!dir.join("src.tar.gz").exists());
+
+        disk::set_dry_run(false);
+        disk::zip(&src, dir.join("src.zip")).unwrap();
+        disk::tar_gz(&src, dir.join("src.tar.gz")).unwrap();
+        disk::set_dry_run(true);
+
+        disk::unzip(dir.join("src.zip"), dir.join("unzipped")).unwrap();
+        disk::untar_gz(dir.join("src.tar.gz"), dir.join("untarred")).unwrap();
+        assert!(!dir.join("unzipped").exists());
+        assert!(!dir.join("untarred").exists());
+    }
+
+    disk::set_dry_run(false);
+    let _ = std::fs::remove_dir_all(dir);
+}